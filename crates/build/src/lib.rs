@@ -7,6 +7,13 @@ use std::collections::HashMap;
 use std::io::prelude::*;
 use std::path::Path;
 use std::process::{Command, Stdio};
+use wasm_encoder::{CustomSection, Encode};
+
+/// The file in a plugin's project directory that holds the JSON Schema for the plugin's config.
+pub const CONFIG_SCHEMA_FILENAME: &str = "config.schema.json";
+
+/// The name of the custom WASM section that a plugin's config schema is embedded in.
+pub const CONFIG_SCHEMA_SECTION: &str = "bulwark-config-schema";
 
 /// Returns the name of the plugin as read from the Cargo metadata.
 fn plugin_name(path: impl AsRef<Path>) -> Result<String, BuildError> {
@@ -39,6 +46,18 @@ fn adapt_wasm_output(wasm_bytes: Vec<u8>, adapter_bytes: Vec<u8>) -> Result<Vec<
     Ok(component.to_vec())
 }
 
+/// Appends the plugin's config schema to the component as a custom section.
+fn embed_config_schema(mut component_bytes: Vec<u8>, schema_bytes: &[u8]) -> Vec<u8> {
+    let section = CustomSection {
+        name: CONFIG_SCHEMA_SECTION.into(),
+        data: schema_bytes.into(),
+    };
+    // Custom sections use the same id in components as in core modules.
+    component_bytes.push(0);
+    section.encode(&mut component_bytes);
+    component_bytes
+}
+
 fn installed_targets() -> Result<HashMap<String, bool>, BuildError> {
     let mut command = Command::new("rustup")
         .args(["target", "list"])
@@ -107,7 +126,8 @@ pub fn interactive_build_plugin(
 ///
 /// Compiles the plugin with the `wasm32-wasi` target, and installs it if it is missing.
 /// Uses an embeded adapter WASM file to adapt from preview 1 to preview 2 for the component
-/// model.
+/// model. If the plugin's project directory contains a [`CONFIG_SCHEMA_FILENAME`] file, it
+/// will be embedded in the plugin so that its config can be validated when it's loaded.
 ///
 /// Calls out to `cargo` via [`Command`], so `cargo` must be available on the path for this
 /// function to work.
@@ -149,7 +169,11 @@ pub fn build_plugin(
         let wasm_bytes = std::fs::read(&wasm_path)
             .map_err(|err| BuildError::NotFound(wasm_path.to_string_lossy().to_string(), err))?;
 
-        let adapted_bytes = adapt_wasm_output(wasm_bytes, adapter_bytes.to_vec())?;
+        let mut adapted_bytes = adapt_wasm_output(wasm_bytes, adapter_bytes.to_vec())?;
+        let schema_path = path.join(CONFIG_SCHEMA_FILENAME);
+        if schema_path.exists() {
+            adapted_bytes = embed_config_schema(adapted_bytes, &std::fs::read(schema_path)?);
+        }
         std::fs::create_dir_all(output_dir)?;
         std::fs::write(output, adapted_bytes)?;
     } else {
@@ -1,14 +1,17 @@
 //! The config module provides the internal representation of Bulwark's configuration.
 
-use crate::ResolutionError;
+use crate::{PluginSourceError, ResolutionError};
+use base64::Engine as _;
 use itertools::Itertools;
 use regex::Regex;
 use serde::Serialize;
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use validator::Validate;
 
 lazy_static! {
     static ref RE_VALID_REFERENCE: Regex = Regex::new(r"^[_a-z]+$").unwrap();
+    static ref RE_VALID_SHA256: Regex = Regex::new(r"^[0-9a-f]{64}$").unwrap();
 }
 
 /// The root of a Bulwark configuration.
@@ -24,8 +27,12 @@ pub struct Config {
     pub state: State,
     /// Configuration for the decision thresholds.
     pub thresholds: Thresholds,
+    /// Configuration for the actions taken in response to each outcome.
+    pub actions: Actions,
     /// Configuration for metrics collection.
     pub metrics: Metrics,
+    /// Configuration for capturing processed traffic for later replay.
+    pub capture: Capture,
     /// A list of configurations for individual plugins.
     pub plugins: Vec<Plugin>,
     /// A list of plugin groups that allows a plugin set to be loaded with a single reference.
@@ -66,7 +73,7 @@ impl Config {
 }
 
 /// Configuration for the services being launched.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Service {
     /// The port for the primary service.
     pub port: u16,
@@ -101,12 +108,16 @@ impl Default for Service {
 }
 
 /// Configuration for the runtime environment.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Runtime {
     /// The maximum number of concurrent incoming requests that the runtime will process before blocking.
     pub max_concurrent_requests: usize,
     /// The maximum number of concurrent plugin tasks that the runtime will launch.
     pub max_plugin_tasks: usize,
+    /// The time limits applied to plugins when neither the plugin nor its resource sets one.
+    ///
+    /// Every phase is set, see [`DEFAULT_TIMEOUT`].
+    pub default_timeout: Timeout,
 }
 
 /// The default [`Runtime::max_concurrent_requests`] value.
@@ -115,18 +126,61 @@ pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 8;
 /// The default [`Runtime::max_plugin_tasks`] value.
 pub const DEFAULT_MAX_PLUGIN_TASKS: usize = 16;
 
+/// The default time limit in milliseconds for each plugin execution phase.
+pub const DEFAULT_TIMEOUT: u64 = 10;
+
 impl Default for Runtime {
     /// Default runtime config
     fn default() -> Self {
         Self {
             max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
             max_plugin_tasks: DEFAULT_MAX_PLUGIN_TASKS,
+            default_timeout: Timeout::uniform(DEFAULT_TIMEOUT),
+        }
+    }
+}
+
+/// Time limits in milliseconds for each plugin execution phase.
+///
+/// Timeouts may be set on the [`Runtime`], a [`Resource`], or a [`Plugin`]. Each phase is resolved independently,
+/// with a plugin's timeout taking priority over its resource's, which in turn takes priority over the runtime
+/// default. Unset phases fall through to the next level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Timeout {
+    /// The time limit for the `handle_init` phase.
+    pub init: Option<u64>,
+    /// The time limit for the `handle_request_enrichment` phase.
+    pub enrichment: Option<u64>,
+    /// The time limit for the `handle_request_decision` and `handle_response_decision` phases.
+    pub decision: Option<u64>,
+    /// The time limit for the `handle_decision_feedback` phase.
+    pub feedback: Option<u64>,
+}
+
+impl Timeout {
+    /// Creates a `Timeout` that applies the same time limit to every phase.
+    pub fn uniform(millis: u64) -> Self {
+        Self {
+            init: Some(millis),
+            enrichment: Some(millis),
+            decision: Some(millis),
+            feedback: Some(millis),
+        }
+    }
+
+    /// Fills any phases left unset with the corresponding values from `fallback`.
+    pub fn or(self, fallback: Timeout) -> Self {
+        Self {
+            init: self.init.or(fallback.init),
+            enrichment: self.enrichment.or(fallback.enrichment),
+            decision: self.decision.or(fallback.decision),
+            feedback: self.feedback.or(fallback.feedback),
         }
     }
 }
 
 /// Configuration for state managed by Bulwark plugins.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct State {
     /// The URI for the external Redis state store.
     pub redis_uri: Option<String>,
@@ -149,7 +203,7 @@ impl Default for State {
 /// No threshold is necessary for the default `allowed` outcome because it is defined by the range between the
 /// `suspicious` threshold and the `trusted` threshold. The thresholds must have values in descending order, with
 /// `restrict` > `suspicious` > `trusted`. None of the threshold values may be equal.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
 pub struct Thresholds {
     /// True if the primary service should take no action in response to restrict decisions.
     pub observe_only: bool,
@@ -161,6 +215,9 @@ pub struct Thresholds {
     /// Any decision value below the `trust` threshold will cause the corresponding request to be flagged as trusted.
     /// This primarily affects plugins which use feedback loops.
     pub trust: f64,
+    /// A [decisive](Plugin::decisive) plugin whose `accept` or `restrict` value reaches the `decisive` threshold
+    /// finalizes the decision for its request without waiting on any other plugin.
+    pub decisive: f64,
 }
 
 /// The default [`Thresholds::observe_only`] value.
@@ -171,6 +228,8 @@ pub const DEFAULT_RESTRICT_THRESHOLD: f64 = 0.8;
 pub const DEFAULT_SUSPICIOUS_THRESHOLD: f64 = 0.6;
 /// The default [`Thresholds::trust`] value.
 pub const DEFAULT_TRUST_THRESHOLD: f64 = 0.2;
+/// The default [`Thresholds::decisive`] value.
+pub const DEFAULT_DECISIVE_THRESHOLD: f64 = 0.99;
 
 impl Default for Thresholds {
     /// Default decision thresholds.
@@ -180,12 +239,95 @@ impl Default for Thresholds {
             restrict: DEFAULT_RESTRICT_THRESHOLD,
             suspicious: DEFAULT_SUSPICIOUS_THRESHOLD,
             trust: DEFAULT_TRUST_THRESHOLD,
+            decisive: DEFAULT_DECISIVE_THRESHOLD,
+        }
+    }
+}
+
+/// Configuration for the actions taken in response to each [`Outcome`](bulwark_decision::Outcome).
+///
+/// Actions other than [`Action::Allow`] are not enforced when [`Thresholds::observe_only`] is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Actions {
+    /// The action taken for trusted requests.
+    pub trusted: Action,
+    /// The action taken for accepted requests.
+    pub accepted: Action,
+    /// The action taken for suspected requests.
+    pub suspected: Action,
+    /// The action taken for restricted requests.
+    pub restricted: Action,
+}
+
+impl Actions {
+    /// Returns the action taken in response to an `outcome`.
+    pub fn action(&self, outcome: bulwark_decision::Outcome) -> &Action {
+        match outcome {
+            bulwark_decision::Outcome::Trusted => &self.trusted,
+            bulwark_decision::Outcome::Accepted => &self.accepted,
+            bulwark_decision::Outcome::Suspected => &self.suspected,
+            bulwark_decision::Outcome::Restricted => &self.restricted,
+        }
+    }
+}
+
+impl Default for Actions {
+    /// Allows every request except restricted requests, which are blocked.
+    fn default() -> Self {
+        Self {
+            trusted: Action::Allow,
+            accepted: Action::Allow,
+            suspected: Action::Allow,
+            restricted: Action::Block,
+        }
+    }
+}
+
+/// An action taken in response to an [`Outcome`](bulwark_decision::Outcome).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    /// Allows the request to continue on to the upstream service.
+    Allow,
+    /// Blocks the request with a `403` response.
+    Block,
+    /// Redirects the request to a challenge, e.g. a CAPTCHA, with a `302` response.
+    Redirect {
+        /// The URL of the challenge.
+        location: String,
+    },
+    /// Rejects the request with a `429` response.
+    RateLimit {
+        /// The number of seconds sent in the `Retry-After` header.
+        retry_after: u64,
+    },
+    /// Holds the request for a period of time before allowing it.
+    Tarpit {
+        /// The number of milliseconds to delay the request by.
+        delay: u64,
+    },
+    /// Ends the request without sending a response.
+    ///
+    /// Processing is aborted with an error, so Envoy must not be configured to allow requests through when
+    /// its processor fails.
+    Drop,
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Allow => write!(f, "allow"),
+            Action::Block => write!(f, "block"),
+            Action::Redirect { .. } => write!(f, "redirect"),
+            Action::RateLimit { .. } => write!(f, "rate_limit"),
+            Action::Tarpit { .. } => write!(f, "tarpit"),
+            Action::Drop => write!(f, "drop"),
         }
     }
 }
 
 /// Configuration for metrics collection.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Metrics {
     pub statsd_host: Option<String>,
     pub statsd_port: Option<u16>,
@@ -216,19 +358,79 @@ impl Default for Metrics {
     }
 }
 
+/// Configuration for capturing processed traffic for later replay.
+#[derive(Debug, Clone, Serialize)]
+pub struct Capture {
+    /// The NDJSON file that each processed request is appended to. Capture is disabled if unset.
+    ///
+    /// Rotated files are kept alongside it with a numbered suffix, e.g. `capture.ndjson.1`.
+    pub path: Option<String>,
+    /// The maximum number of bytes kept from each request and response body.
+    pub max_body_size: usize,
+    /// The size in bytes at which the capture file is rotated.
+    pub max_file_size: u64,
+    /// The number of rotated capture files kept in addition to the current one.
+    pub max_files: usize,
+    /// The request and response headers whose values are replaced with `[redacted]` in the capture file.
+    ///
+    /// Header names are normalized to lowercase. Defaults to the headers that carry credentials, see
+    /// [`DEFAULT_CAPTURE_REDACT_HEADERS`].
+    pub redact_headers: Vec<String>,
+}
+
+/// The default [`Capture::max_body_size`] value.
+pub const DEFAULT_CAPTURE_MAX_BODY_SIZE: usize = 4096;
+
+/// The default [`Capture::max_file_size`] value.
+pub const DEFAULT_CAPTURE_MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
+
+/// The default [`Capture::max_files`] value.
+pub const DEFAULT_CAPTURE_MAX_FILES: usize = 5;
+
+/// The default [`Capture::redact_headers`] value.
+pub const DEFAULT_CAPTURE_REDACT_HEADERS: [&str; 4] = [
+    "authorization",
+    "cookie",
+    "proxy-authorization",
+    "set-cookie",
+];
+
+impl Default for Capture {
+    /// Default capture config, with capture disabled
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_body_size: DEFAULT_CAPTURE_MAX_BODY_SIZE,
+            max_file_size: DEFAULT_CAPTURE_MAX_FILE_SIZE,
+            max_files: DEFAULT_CAPTURE_MAX_FILES,
+            redact_headers: DEFAULT_CAPTURE_REDACT_HEADERS
+                .iter()
+                .map(|header| header.to_string())
+                .collect(),
+        }
+    }
+}
+
 /// The configuration for an individual plugin.
 ///
 /// This structure will be wrapped by structs in the host environment.
-#[derive(Debug, Validate, Clone, Default)]
+#[derive(Debug, Validate, Clone, Default, Serialize)]
 pub struct Plugin {
     /// The plugin reference key. Should be limited to ASCII lowercase a-z plus underscores. Maximum 96 characters.
     #[validate(length(min = 1, max = 96), regex(path = "RE_VALID_REFERENCE"))]
+    #[serde(rename = "ref")]
     pub reference: String,
-    // TODO: plugin path should be absolute; once it's in this structure the config base path is no longer known
-    // TODO: should this be a URI? That would allow e.g. data: URI values to embed WASM into config over the wire
-    /// The path to the plugin WASM file.
+    /// The location of the plugin WASM.
+    ///
+    /// May be a file path, a `file://` URI, or a base64-encoded `data:` URI that embeds the WASM directly into
+    /// the config. File paths will have been resolved to absolute paths by the config loader. See [`Plugin::source`].
     #[validate(length(min = 1))]
     pub path: String,
+    /// The expected SHA-256 digest of the plugin WASM as a lowercase hex string.
+    ///
+    /// If set, the host will refuse to load a plugin whose digest does not match.
+    #[validate(regex(path = "RE_VALID_SHA256"))]
+    pub sha256: Option<String>,
     /// A weight to multiply this plugin's decision values by.
     ///
     /// A 1.0 value has no effect on the decision. See [`bulwark_decision::Decision::weight`].
@@ -243,13 +445,131 @@ pub struct Plugin {
     ///
     /// Any attempt to perform an operation within the plugin sandbox that requires a permission to be set will fail.
     pub permissions: Permissions,
+    /// The limits applied to outbound HTTP requests made by this plugin.
+    pub http_client: HttpClient,
+    /// The maximum amount of time this plugin may take for each execution phase.
+    ///
+    /// Takes priority over the timeout of any resource the plugin is used by.
+    pub timeout: Timeout,
+    /// References to plugins whose request enrichment must complete before this plugin's begins.
+    ///
+    /// This plugin receives the labels emitted by its dependencies during the enrichment phase. Dependencies only
+    /// affect ordering, so a dependency that isn't run by the same resource is ignored.
+    pub after: Vec<String>,
+    /// True if a certain decision from this plugin should finalize the request's decision.
+    ///
+    /// Once a decisive plugin's weighted decision reaches the [`Thresholds::decisive`] threshold, any other plugins
+    /// still deciding on the request are cancelled and the decisive plugin's decision is used as is.
+    pub decisive: bool,
 }
 
 /// The default [`Plugin::weight`] value.
 pub const DEFAULT_PLUGIN_WEIGHT: f64 = 1.0;
 
+impl Plugin {
+    /// Interprets the plugin's [`path`](Plugin::path) as a [`PluginSource`].
+    ///
+    /// Embedded `data:` URIs are decoded by this function, so callers should avoid calling it repeatedly.
+    pub fn source(&self) -> Result<PluginSource, PluginSourceError> {
+        PluginSource::parse(&self.path)
+    }
+
+    /// Resolves this plugin's [`after`](Plugin::after) references, producing a list of every plugin it depends
+    /// on, directly or indirectly.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - A [`Config`] reference to perform lookups againsts.
+    ///
+    /// See [`Config::plugin`].
+    pub fn resolve_dependencies<'a>(
+        &'a self,
+        config: &'a Config,
+    ) -> Result<Vec<&'a Plugin>, ResolutionError> {
+        let mut dependencies = Vec::new();
+        self.resolve_dependencies_recursive(
+            config,
+            &mut vec![self.reference.as_str()],
+            &mut HashSet::new(),
+            &mut dependencies,
+        )?;
+        Ok(dependencies)
+    }
+
+    /// Resolves all dependencies depth-first, checking for cycles along the current path.
+    ///
+    /// Dependencies shared by several plugins are only walked the first time they're reached. Any dependency that
+    /// was reached before but isn't on the current path has already been fully resolved.
+    fn resolve_dependencies_recursive<'a>(
+        &'a self,
+        config: &'a Config,
+        path: &mut Vec<&'a str>,
+        visited: &mut HashSet<&'a str>,
+        dependencies: &mut Vec<&'a Plugin>,
+    ) -> Result<(), ResolutionError> {
+        for ref_name in &self.after {
+            if path.contains(&ref_name.as_str()) {
+                return Err(ResolutionError::CircularDependency(ref_name.to_string()));
+            }
+            if !visited.insert(ref_name) {
+                continue;
+            }
+            let dependency = config
+                .plugin(ref_name)
+                .ok_or(ResolutionError::Missing(ref_name.to_string()))?;
+            dependencies.push(dependency);
+            path.push(ref_name);
+            dependency.resolve_dependencies_recursive(config, path, visited, dependencies)?;
+            path.pop();
+        }
+        Ok(())
+    }
+}
+
+/// The location that a plugin's WASM will be loaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginSource {
+    /// A path to a `*.wasm` or `*.wat` file.
+    File(PathBuf),
+    /// WASM bytes embedded in the config via a base64-encoded `data:` URI.
+    Data(Vec<u8>),
+}
+
+impl PluginSource {
+    /// Parses a plugin path, `file://` URI, or `data:` URI into a [`PluginSource`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A plugin location as it would appear in [`Plugin::path`].
+    pub fn parse(path: &str) -> Result<Self, PluginSourceError> {
+        if let Some(data) = path.strip_prefix("data:") {
+            let (media_type, payload) = data.split_once(',').ok_or_else(|| {
+                PluginSourceError::InvalidDataUri("missing ',' separator".to_string())
+            })?;
+            if !media_type.ends_with(";base64") {
+                return Err(PluginSourceError::InvalidDataUri(
+                    "only base64-encoded data is supported".to_string(),
+                ));
+            }
+            Ok(PluginSource::Data(
+                base64::engine::general_purpose::STANDARD.decode(payload)?,
+            ))
+        } else if path.starts_with("file:") {
+            let url = url::Url::parse(path)
+                .map_err(|_| PluginSourceError::InvalidFileUri(path.to_string()))?;
+            Ok(PluginSource::File(url.to_file_path().map_err(|_| {
+                PluginSourceError::InvalidFileUri(path.to_string())
+            })?))
+        } else if let Some((scheme, _)) = path.split_once("://") {
+            Err(PluginSourceError::UnsupportedScheme(scheme.to_string()))
+        } else {
+            Ok(PluginSource::File(PathBuf::from(path)))
+        }
+    }
+}
+
 /// The permissions granted to an associated plugin.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Permissions {
     /// A list of environment variables a plugin may acquire values for.
     ///
@@ -265,6 +585,90 @@ pub struct Permissions {
     pub state: Vec<String>,
 }
 
+/// The limits applied to outbound HTTP requests made by an associated plugin.
+///
+/// Plugins may ask for shorter timeouts than these, but never longer ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HttpClient {
+    /// The maximum time in milliseconds to wait for a connection to be established.
+    pub connect_timeout: u64,
+    /// The maximum time in milliseconds to wait for the first byte of a response, and between any subsequent bytes.
+    pub first_byte_timeout: u64,
+    /// The maximum size in bytes of a response body.
+    pub max_response_size: u64,
+    /// The maximum number of outbound requests the plugin may have in flight at once, across all of its instances
+    /// and every resource that runs it.
+    ///
+    /// Requests over the limit wait for an earlier request to complete.
+    pub max_concurrent_requests: usize,
+    /// Caches responses to the plugin's `GET` requests. Caching is disabled if unset.
+    pub cache: Option<HttpCache>,
+}
+
+/// The default [`HttpClient::connect_timeout`] value.
+pub const DEFAULT_HTTP_CONNECT_TIMEOUT: u64 = 1000;
+
+/// The default [`HttpClient::first_byte_timeout`] value.
+pub const DEFAULT_HTTP_FIRST_BYTE_TIMEOUT: u64 = 5000;
+
+/// The default [`HttpClient::max_response_size`] value.
+pub const DEFAULT_HTTP_MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
+
+/// The default [`HttpClient::max_concurrent_requests`] value.
+pub const DEFAULT_HTTP_MAX_CONCURRENT_REQUESTS: usize = 16;
+
+impl Default for HttpClient {
+    /// Default outbound HTTP limits
+    fn default() -> Self {
+        Self {
+            connect_timeout: DEFAULT_HTTP_CONNECT_TIMEOUT,
+            first_byte_timeout: DEFAULT_HTTP_FIRST_BYTE_TIMEOUT,
+            max_response_size: DEFAULT_HTTP_MAX_RESPONSE_SIZE,
+            max_concurrent_requests: DEFAULT_HTTP_MAX_CONCURRENT_REQUESTS,
+            cache: None,
+        }
+    }
+}
+
+/// Configuration for caching responses to a plugin's outbound `GET` requests.
+///
+/// Responses are only cached when their `Cache-Control` header gives a `max-age`, and never for longer than
+/// [`HttpCache::max_ttl`]. Requests made with `Cache-Control: no-cache` bypass the cache.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HttpCache {
+    /// The longest time in seconds that a response may be cached for.
+    pub max_ttl: u64,
+    /// The request headers whose values are part of the cache key, in addition to the method and URL.
+    ///
+    /// Responses that vary on any other header are not cached.
+    pub headers: Vec<String>,
+    /// Where cached responses are stored.
+    pub backend: HttpCacheBackend,
+    /// The most bytes of responses that the in-memory backend holds at once.
+    ///
+    /// The least recently used responses are evicted to make room for new ones.
+    pub max_memory_size: u64,
+}
+
+/// The default [`HttpCache::max_ttl`] value.
+pub const DEFAULT_HTTP_CACHE_MAX_TTL: u64 = 300;
+
+/// The default [`HttpCache::max_memory_size`] value.
+pub const DEFAULT_HTTP_CACHE_MAX_MEMORY_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The storage used by an [`HttpCache`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpCacheBackend {
+    /// Responses are cached in memory and shared by every instance of the plugin in the same process.
+    #[default]
+    Memory,
+    /// Responses are cached in the external Redis state store and shared across processes.
+    ///
+    /// Caching is skipped if no Redis URI is configured.
+    Redis,
+}
+
 /// A mapping between a reference identifier and a list of plugins that form a preset plugin group.
 #[derive(Debug, Validate, Clone)]
 pub struct Preset {
@@ -331,16 +735,46 @@ impl Preset {
 }
 
 /// A mapping between a route pattern and the plugins that should be run for matching requests.
+///
+/// When several resources could apply to a request, the most specific one wins. Resources for an exact host take
+/// priority over wildcard hosts, with longer wildcard domains first, and both take priority over resources that
+/// match any host. Within the same host and route, resources that restrict methods take priority, followed by
+/// those with more header conditions, and finally by their order in the config.
 #[derive(Debug, Clone)]
 pub struct Resource {
     /// The route pattern used to match requests with.
     ///
     /// Uses `matchit` router patterns.
     pub route: String,
+    /// The hostnames this resource applies to.
+    ///
+    /// A leading `*.` matches any subdomain of the given domain, but not the domain itself. Hostnames are
+    /// normalized to lowercase. An empty list matches every host.
+    pub hosts: Vec<String>,
+    /// The HTTP methods this resource applies to.
+    ///
+    /// Methods are normalized to uppercase. An empty list matches every method.
+    pub methods: Vec<String>,
+    /// Request headers that must be present for this resource to apply, mapped to their required value.
+    ///
+    /// A value of `*` only requires that the header is present. Header names are normalized to lowercase.
+    pub headers: HashMap<String, String>,
     /// The plugin references for this route.
     pub plugins: Vec<Reference>,
+    /// Adjustments to the settings of referenced plugins that only apply to this resource.
+    ///
+    /// Keyed by the reference the override was given for. An override for a preset applies to every plugin in
+    /// the preset, and is applied before any override for the plugin itself.
+    pub overrides: HashMap<String, PluginOverride>,
     /// The maximum amount of time a plugin may take for each execution phase.
-    pub timeout: Option<u64>,
+    ///
+    /// Takes priority over [`Runtime::default_timeout`].
+    pub timeout: Timeout,
+    /// The actions taken in response to each outcome for requests matching this resource.
+    ///
+    /// Any outcome the resource doesn't set an action for will have been filled in from [`Config::actions`] by
+    /// the config loader.
+    pub actions: Actions,
 }
 
 impl Resource {
@@ -352,6 +786,9 @@ impl Resource {
     ///   `Resource`s do not maintain their own references to their parent [`Config`] so this must be passed in.
     ///
     /// See [`Config::plugin`] and [`Config::preset`].
+    ///
+    /// The returned plugins are the declared ones, without this resource's [`overrides`](Resource::overrides).
+    /// See [`Resource::resolve_plugins_with_overrides`] for the plugins as this resource runs them.
     pub fn resolve_plugins<'a>(
         &'a self,
         config: &'a Config,
@@ -381,6 +818,94 @@ impl Resource {
             .copied()
             .collect())
     }
+
+    /// Resolves all references within a `Resource` like [`Resource::resolve_plugins`], returning copies of the
+    /// [`Plugin`]s with any of this resource's [`overrides`](Resource::overrides) applied.
+    pub fn resolve_plugins_with_overrides(
+        &self,
+        config: &Config,
+    ) -> Result<Vec<Plugin>, ResolutionError> {
+        let mut plugins: Vec<Plugin> = Vec::with_capacity(self.plugins.len());
+        for reference in &self.plugins {
+            match reference {
+                Reference::Plugin(ref_name) => {
+                    if let Some(plugin) = config.plugin(ref_name.as_str()) {
+                        let mut plugin = plugin.clone();
+                        if let Some(plugin_override) = self.overrides.get(ref_name) {
+                            plugin_override.apply(&mut plugin);
+                        }
+                        plugins.push(plugin);
+                    }
+                }
+                Reference::Preset(ref_name) => {
+                    if let Some(preset) = config.preset(ref_name.as_str()) {
+                        let preset_override = self.overrides.get(ref_name);
+                        for inner_plugin in preset.resolve_plugins(config)? {
+                            let mut plugin = inner_plugin.clone();
+                            if let Some(preset_override) = preset_override {
+                                preset_override.apply(&mut plugin);
+                            }
+                            if let Some(plugin_override) = self.overrides.get(&plugin.reference) {
+                                plugin_override.apply(&mut plugin);
+                            }
+                            plugins.push(plugin);
+                        }
+                    }
+                }
+                Reference::Missing(ref_name) => {
+                    return Err(ResolutionError::Missing(ref_name.to_string()));
+                }
+            }
+        }
+        Ok(plugins
+            .into_iter()
+            .sorted_by(|a, b| Ord::cmp(&a.reference, &b.reference))
+            .collect())
+    }
+}
+
+/// Adjustments to a plugin's settings that only apply within a single [`Resource`].
+///
+/// Allows the same plugin to be used with different settings on different routes without declaring it twice.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PluginOverride {
+    /// Replaces the plugin's [`weight`](Plugin::weight) if set.
+    pub weight: Option<f64>,
+    /// Merged into the plugin's [`config`](Plugin::config), with nested objects merged key by key.
+    pub config: serde_json::map::Map<String, serde_json::Value>,
+    /// True if `config` should replace the plugin's config entirely instead of being merged into it.
+    pub replace_config: bool,
+}
+
+impl PluginOverride {
+    /// Applies the override to a copy of a [`Plugin`].
+    pub fn apply(&self, plugin: &mut Plugin) {
+        if let Some(weight) = self.weight {
+            plugin.weight = weight;
+        }
+        if self.replace_config {
+            plugin.config = self.config.clone();
+        } else {
+            merge_json_maps(&mut plugin.config, &self.config);
+        }
+    }
+}
+
+/// Merges `patch` into `base`, recursing into objects that are present in both.
+fn merge_json_maps(
+    base: &mut serde_json::map::Map<String, serde_json::Value>,
+    patch: &serde_json::map::Map<String, serde_json::Value>,
+) {
+    for (key, value) in patch {
+        match (base.get_mut(key), value) {
+            (Some(serde_json::Value::Object(base)), serde_json::Value::Object(patch)) => {
+                merge_json_maps(base, patch)
+            }
+            (_, value) => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
 }
 
 /// Wraps reference strings and differentiates what the reference points to.
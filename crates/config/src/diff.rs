@@ -0,0 +1,686 @@
+//! The diff module compares two configurations by what they do rather than by how they are written.
+
+use crate::{Actions, EffectiveConfig, EffectivePlugin, EffectiveResource, Permissions, Plugin};
+use bulwark_decision::Outcome;
+use serde::Serialize;
+use std::fmt;
+
+/// A single semantic difference between two [`EffectiveConfig`]s.
+///
+/// Changes are described in terms of resolved routes and plugins, so moving plugins between presets or files
+/// produces no changes unless it alters what a route runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigChange {
+    /// A decision threshold changed.
+    Threshold {
+        name: &'static str,
+        before: String,
+        after: String,
+    },
+    /// The action taken for an outcome changed.
+    Action {
+        outcome: &'static str,
+        before: String,
+        after: String,
+    },
+    /// A plugin was added.
+    PluginAdded(String),
+    /// A plugin was removed.
+    PluginRemoved(String),
+    /// A setting of a plugin present in both configs changed.
+    Plugin {
+        reference: String,
+        field: &'static str,
+        before: String,
+        after: String,
+    },
+    /// A plugin was granted a permission.
+    PermissionGranted {
+        reference: String,
+        kind: &'static str,
+        value: String,
+    },
+    /// A plugin had a permission revoked.
+    PermissionRevoked {
+        reference: String,
+        kind: &'static str,
+        value: String,
+    },
+    /// A route was added, along with the plugins it runs.
+    RouteAdded { route: String, plugins: Vec<String> },
+    /// A route was removed.
+    RouteRemoved(String),
+    /// A route runs a plugin that it did not run before.
+    RoutePluginAdded { route: String, reference: String },
+    /// A route no longer runs a plugin.
+    RoutePluginRemoved { route: String, reference: String },
+    /// A setting of a plugin run by a route present in both configs changed.
+    RoutePlugin {
+        route: String,
+        reference: String,
+        field: &'static str,
+        before: String,
+        after: String,
+    },
+    /// The action a route present in both configs takes for an outcome changed.
+    RouteAction {
+        route: String,
+        outcome: &'static str,
+        before: String,
+        after: String,
+    },
+}
+
+impl fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigChange::Threshold {
+                name,
+                before,
+                after,
+            } => write!(f, "~ thresholds.{}: {} -> {}", name, before, after),
+            ConfigChange::Action {
+                outcome,
+                before,
+                after,
+            } => write!(f, "~ actions.{}: {} -> {}", outcome, before, after),
+            ConfigChange::PluginAdded(reference) => write!(f, "+ plugin {}", reference),
+            ConfigChange::PluginRemoved(reference) => write!(f, "- plugin {}", reference),
+            ConfigChange::Plugin {
+                reference,
+                field,
+                before,
+                after,
+            } => write!(
+                f,
+                "~ plugin {} {}: {} -> {}",
+                reference, field, before, after
+            ),
+            ConfigChange::PermissionGranted {
+                reference,
+                kind,
+                value,
+            } => write!(f, "+ plugin {} permission {}: {}", reference, kind, value),
+            ConfigChange::PermissionRevoked {
+                reference,
+                kind,
+                value,
+            } => write!(f, "- plugin {} permission {}: {}", reference, kind, value),
+            ConfigChange::RouteAdded { route, plugins } => {
+                write!(f, "+ route {}: [{}]", route, plugins.join(", "))
+            }
+            ConfigChange::RouteRemoved(route) => write!(f, "- route {}", route),
+            ConfigChange::RoutePluginAdded { route, reference } => {
+                write!(f, "+ route {} plugin {}", route, reference)
+            }
+            ConfigChange::RoutePluginRemoved { route, reference } => {
+                write!(f, "- route {} plugin {}", route, reference)
+            }
+            ConfigChange::RoutePlugin {
+                route,
+                reference,
+                field,
+                before,
+                after,
+            } => write!(
+                f,
+                "~ route {} plugin {} {}: {} -> {}",
+                route, reference, field, before, after
+            ),
+            ConfigChange::RouteAction {
+                route,
+                outcome,
+                before,
+                after,
+            } => write!(
+                f,
+                "~ route {} action {}: {} -> {}",
+                route, outcome, before, after
+            ),
+        }
+    }
+}
+
+impl EffectiveConfig {
+    /// Lists the semantic differences between this config and a newer one.
+    ///
+    /// Covers thresholds, actions, plugins and their permissions, and the plugins run by each route. Routes are
+    /// matched up by [`EffectiveResource::label`].
+    pub fn diff(&self, after: &EffectiveConfig) -> Vec<ConfigChange> {
+        let mut changes = Vec::new();
+        diff_thresholds(self, after, &mut changes);
+        diff_actions(&self.actions, &after.actions, &mut changes);
+        diff_plugins(&self.plugins, &after.plugins, &mut changes);
+        diff_resources(self, after, &mut changes);
+        changes
+    }
+}
+
+/// Renders a value the same way `config show` does.
+fn render<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn diff_thresholds(
+    before: &EffectiveConfig,
+    after: &EffectiveConfig,
+    changes: &mut Vec<ConfigChange>,
+) {
+    let (before, after) = (before.thresholds, after.thresholds);
+    let values = [
+        (
+            "observe_only",
+            render(&before.observe_only),
+            render(&after.observe_only),
+        ),
+        (
+            "restrict",
+            render(&before.restrict),
+            render(&after.restrict),
+        ),
+        (
+            "suspicious",
+            render(&before.suspicious),
+            render(&after.suspicious),
+        ),
+        ("trust", render(&before.trust), render(&after.trust)),
+        (
+            "decisive",
+            render(&before.decisive),
+            render(&after.decisive),
+        ),
+    ];
+    for (name, before, after) in values {
+        if before != after {
+            changes.push(ConfigChange::Threshold {
+                name,
+                before,
+                after,
+            });
+        }
+    }
+}
+
+/// The outcomes that actions are configured for, along with the names they're configured under.
+const OUTCOMES: [(&str, Outcome); 4] = [
+    ("trusted", Outcome::Trusted),
+    ("accepted", Outcome::Accepted),
+    ("suspected", Outcome::Suspected),
+    ("restricted", Outcome::Restricted),
+];
+
+fn diff_actions(before: &Actions, after: &Actions, changes: &mut Vec<ConfigChange>) {
+    for (outcome, value) in OUTCOMES {
+        let (before, after) = (render(before.action(value)), render(after.action(value)));
+        if before != after {
+            changes.push(ConfigChange::Action {
+                outcome,
+                before,
+                after,
+            });
+        }
+    }
+}
+
+fn diff_plugins(before: &[Plugin], after: &[Plugin], changes: &mut Vec<ConfigChange>) {
+    fn find<'a>(plugins: &'a [Plugin], reference: &str) -> Option<&'a Plugin> {
+        plugins.iter().find(|plugin| plugin.reference == reference)
+    }
+    for plugin in before {
+        if find(after, &plugin.reference).is_none() {
+            changes.push(ConfigChange::PluginRemoved(plugin.reference.clone()));
+        }
+    }
+    for plugin in after {
+        match find(before, &plugin.reference) {
+            None => {
+                changes.push(ConfigChange::PluginAdded(plugin.reference.clone()));
+                diff_permissions(
+                    &plugin.reference,
+                    &Permissions::default(),
+                    &plugin.permissions,
+                    changes,
+                );
+            }
+            Some(previous) => {
+                let values = [
+                    ("path", render(&previous.path), render(&plugin.path)),
+                    ("sha256", render(&previous.sha256), render(&plugin.sha256)),
+                    ("weight", render(&previous.weight), render(&plugin.weight)),
+                    ("config", render(&previous.config), render(&plugin.config)),
+                    (
+                        "http_client",
+                        render(&previous.http_client),
+                        render(&plugin.http_client),
+                    ),
+                    (
+                        "timeout",
+                        render(&previous.timeout),
+                        render(&plugin.timeout),
+                    ),
+                    ("after", render(&previous.after), render(&plugin.after)),
+                    (
+                        "decisive",
+                        render(&previous.decisive),
+                        render(&plugin.decisive),
+                    ),
+                ];
+                for (field, before, after) in values {
+                    if before != after {
+                        changes.push(ConfigChange::Plugin {
+                            reference: plugin.reference.clone(),
+                            field,
+                            before,
+                            after,
+                        });
+                    }
+                }
+                diff_permissions(
+                    &plugin.reference,
+                    &previous.permissions,
+                    &plugin.permissions,
+                    changes,
+                );
+            }
+        }
+    }
+}
+
+fn diff_permissions(
+    reference: &str,
+    before: &Permissions,
+    after: &Permissions,
+    changes: &mut Vec<ConfigChange>,
+) {
+    let kinds = [
+        ("env", &before.env, &after.env),
+        ("http", &before.http, &after.http),
+        ("state", &before.state, &after.state),
+    ];
+    for (kind, before, after) in kinds {
+        for value in before.iter().filter(|value| !after.contains(value)) {
+            changes.push(ConfigChange::PermissionRevoked {
+                reference: reference.to_string(),
+                kind,
+                value: value.clone(),
+            });
+        }
+        for value in after.iter().filter(|value| !before.contains(value)) {
+            changes.push(ConfigChange::PermissionGranted {
+                reference: reference.to_string(),
+                kind,
+                value: value.clone(),
+            });
+        }
+    }
+}
+
+fn diff_resources(
+    before_config: &EffectiveConfig,
+    after_config: &EffectiveConfig,
+    changes: &mut Vec<ConfigChange>,
+) {
+    let (before, after) = (&before_config.resources, &after_config.resources);
+    // Resources with identical labels can only be told apart by their order, so each one is paired at most once.
+    let mut unpaired: Vec<Option<&EffectiveResource>> = after.iter().map(Some).collect();
+    for resource in before {
+        let label = resource.label();
+        let paired = unpaired.iter_mut().find(|candidate| {
+            candidate
+                .map(|candidate| candidate.label() == label)
+                .unwrap_or(false)
+        });
+        match paired.and_then(Option::take) {
+            None => changes.push(ConfigChange::RouteRemoved(label)),
+            Some(successor) => {
+                diff_route_plugins(
+                    &label,
+                    (before_config, &resource.plugins),
+                    (after_config, &successor.plugins),
+                    changes,
+                );
+                diff_route_actions(
+                    &label,
+                    (&before_config.actions, &resource.actions),
+                    (&after_config.actions, &successor.actions),
+                    changes,
+                );
+            }
+        }
+    }
+    for resource in unpaired.into_iter().flatten() {
+        changes.push(ConfigChange::RouteAdded {
+            route: resource.label(),
+            plugins: resource
+                .plugins
+                .iter()
+                .map(|plugin| plugin.reference.clone())
+                .collect(),
+        });
+    }
+}
+
+fn diff_route_plugins(
+    route: &str,
+    (before_config, before): (&EffectiveConfig, &[EffectivePlugin]),
+    (after_config, after): (&EffectiveConfig, &[EffectivePlugin]),
+    changes: &mut Vec<ConfigChange>,
+) {
+    for plugin in before {
+        if !after
+            .iter()
+            .any(|successor| successor.reference == plugin.reference)
+        {
+            changes.push(ConfigChange::RoutePluginRemoved {
+                route: route.to_string(),
+                reference: plugin.reference.clone(),
+            });
+        }
+    }
+    for plugin in after {
+        let Some(previous) = before
+            .iter()
+            .find(|previous| previous.reference == plugin.reference)
+        else {
+            changes.push(ConfigChange::RoutePluginAdded {
+                route: route.to_string(),
+                reference: plugin.reference.clone(),
+            });
+            continue;
+        };
+        // Weight and config changes that a route merely inherits are already reported for the plugin itself.
+        let declared = |config: &EffectiveConfig| {
+            config
+                .plugins
+                .iter()
+                .find(|declared| declared.reference == plugin.reference)
+                .map(|declared| (render(&declared.weight), render(&declared.config)))
+        };
+        let (declared_before, declared_after) = (
+            declared(before_config).unwrap_or_default(),
+            declared(after_config).unwrap_or_default(),
+        );
+        let values = [
+            (
+                "weight",
+                render(&previous.weight),
+                render(&plugin.weight),
+                Some((declared_before.0, declared_after.0)),
+            ),
+            (
+                "config",
+                render(&previous.config),
+                render(&plugin.config),
+                Some((declared_before.1, declared_after.1)),
+            ),
+            // Timeouts depend on the resource, so they are always reported per route.
+            (
+                "timeout",
+                render(&previous.timeout),
+                render(&plugin.timeout),
+                None,
+            ),
+        ];
+        for (field, before, after, declared) in values {
+            let inherited = declared == Some((before.clone(), after.clone()));
+            if before != after && !inherited {
+                changes.push(ConfigChange::RoutePlugin {
+                    route: route.to_string(),
+                    reference: plugin.reference.clone(),
+                    field,
+                    before,
+                    after,
+                });
+            }
+        }
+    }
+}
+
+fn diff_route_actions(
+    route: &str,
+    (before_default, before): (&Actions, &Actions),
+    (after_default, after): (&Actions, &Actions),
+    changes: &mut Vec<ConfigChange>,
+) {
+    for (outcome, value) in OUTCOMES {
+        let (before, after) = (before.action(value), after.action(value));
+        // Action changes that a route merely inherits are already reported for the top-level actions.
+        let inherited =
+            before == before_default.action(value) && after == after_default.action(value);
+        if before != after && !inherited {
+            changes.push(ConfigChange::RouteAction {
+                route: route.to_string(),
+                outcome,
+                before: render(before),
+                after: render(after),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, Reference, Resource, Timeout};
+
+    fn plugin(reference: &str) -> Plugin {
+        Plugin {
+            reference: reference.to_string(),
+            path: format!("{}.wasm", reference),
+            weight: crate::DEFAULT_PLUGIN_WEIGHT,
+            ..Default::default()
+        }
+    }
+
+    fn resource(route: &str, plugins: &[&str]) -> Resource {
+        Resource {
+            route: route.to_string(),
+            hosts: vec![],
+            methods: vec![],
+            headers: Default::default(),
+            plugins: plugins
+                .iter()
+                .map(|reference| Reference::Plugin(reference.to_string()))
+                .collect(),
+            overrides: Default::default(),
+            timeout: Timeout::default(),
+            actions: Default::default(),
+        }
+    }
+
+    fn config(plugins: Vec<Plugin>, resources: Vec<Resource>) -> Config {
+        Config {
+            service: Default::default(),
+            runtime: Default::default(),
+            state: Default::default(),
+            thresholds: Default::default(),
+            actions: Default::default(),
+            metrics: Default::default(),
+            capture: Default::default(),
+            plugins,
+            presets: vec![],
+            resources,
+        }
+    }
+
+    #[test]
+    fn test_diff_unchanged() -> Result<(), Box<dyn std::error::Error>> {
+        let before = config(
+            vec![plugin("evil_bit")],
+            vec![resource("/*path", &["evil_bit"])],
+        );
+        let before = EffectiveConfig::new(&before)?;
+        assert_eq!(before.diff(&before), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() -> Result<(), Box<dyn std::error::Error>> {
+        let before = config(
+            vec![plugin("evil_bit"), plugin("blank_slate")],
+            vec![
+                resource("/", &["evil_bit"]),
+                resource("/login", &["evil_bit", "blank_slate"]),
+            ],
+        );
+
+        let mut evil_bit = plugin("evil_bit");
+        evil_bit.weight = 0.5;
+        evil_bit.permissions.http = vec!["example.com".to_string()];
+        let mut login = resource("/login", &["evil_bit", "rate_limit"]);
+        login.timeout = Timeout::uniform(25);
+        let mut after = config(
+            vec![evil_bit, plugin("rate_limit")],
+            vec![login, resource("/logout", &["rate_limit"])],
+        );
+        after.thresholds.restrict = 0.9;
+
+        let changes = EffectiveConfig::new(&before)?.diff(&EffectiveConfig::new(&after)?);
+        let timeout = |millis| render(&Timeout::uniform(millis));
+        assert_eq!(
+            changes,
+            vec![
+                ConfigChange::Threshold {
+                    name: "restrict",
+                    before: "0.8".to_string(),
+                    after: "0.9".to_string(),
+                },
+                ConfigChange::PluginRemoved("blank_slate".to_string()),
+                ConfigChange::Plugin {
+                    reference: "evil_bit".to_string(),
+                    field: "weight",
+                    before: "1.0".to_string(),
+                    after: "0.5".to_string(),
+                },
+                ConfigChange::PermissionGranted {
+                    reference: "evil_bit".to_string(),
+                    kind: "http",
+                    value: "example.com".to_string(),
+                },
+                ConfigChange::PluginAdded("rate_limit".to_string()),
+                ConfigChange::RouteRemoved("/".to_string()),
+                ConfigChange::RoutePluginRemoved {
+                    route: "/login".to_string(),
+                    reference: "blank_slate".to_string(),
+                },
+                ConfigChange::RoutePlugin {
+                    route: "/login".to_string(),
+                    reference: "evil_bit".to_string(),
+                    field: "timeout",
+                    before: timeout(crate::DEFAULT_TIMEOUT),
+                    after: timeout(25),
+                },
+                ConfigChange::RoutePluginAdded {
+                    route: "/login".to_string(),
+                    reference: "rate_limit".to_string(),
+                },
+                ConfigChange::RouteAdded {
+                    route: "/logout".to_string(),
+                    plugins: vec!["rate_limit".to_string()],
+                },
+            ]
+        );
+        assert_eq!(
+            changes[3].to_string(),
+            "+ plugin evil_bit permission http: example.com"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_overrides() -> Result<(), Box<dyn std::error::Error>> {
+        let limit = |limit: u64| {
+            serde_json::json!({ "limit": limit })
+                .as_object()
+                .unwrap()
+                .clone()
+        };
+        let mut rate_limit = plugin("rate_limit");
+        rate_limit.config = limit(10);
+        let mut login = resource("/login", &["rate_limit"]);
+        login.overrides.insert(
+            "rate_limit".to_string(),
+            crate::PluginOverride {
+                config: limit(5),
+                ..Default::default()
+            },
+        );
+        let before = config(
+            vec![rate_limit.clone()],
+            vec![login.clone(), resource("/search", &["rate_limit"])],
+        );
+
+        rate_limit.config = limit(20);
+        login.overrides.get_mut("rate_limit").unwrap().config = limit(3);
+        let after = config(
+            vec![rate_limit],
+            vec![login, resource("/search", &["rate_limit"])],
+        );
+
+        // The search route inherits the plugin's config, so its change is only reported once.
+        let changes = EffectiveConfig::new(&before)?.diff(&EffectiveConfig::new(&after)?);
+        assert_eq!(
+            changes,
+            vec![
+                ConfigChange::Plugin {
+                    reference: "rate_limit".to_string(),
+                    field: "config",
+                    before: render(&limit(10)),
+                    after: render(&limit(20)),
+                },
+                ConfigChange::RoutePlugin {
+                    route: "/login".to_string(),
+                    reference: "rate_limit".to_string(),
+                    field: "config",
+                    before: render(&limit(5)),
+                    after: render(&limit(3)),
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_actions() -> Result<(), Box<dyn std::error::Error>> {
+        let redirect = crate::Action::Redirect {
+            location: "/challenge".to_string(),
+        };
+        let mut signup = resource("/signup", &["evil_bit"]);
+        signup.actions.suspected = redirect.clone();
+        let before = config(
+            vec![plugin("evil_bit")],
+            vec![signup.clone(), resource("/search", &["evil_bit"])],
+        );
+
+        let tarpit = crate::Action::Tarpit { delay: 500 };
+        signup.actions.restricted = crate::Action::Drop;
+        let mut search = resource("/search", &["evil_bit"]);
+        search.actions.suspected = tarpit.clone();
+        let mut after = config(vec![plugin("evil_bit")], vec![signup, search]);
+        after.actions.suspected = tarpit.clone();
+
+        // The search route inherits the top-level action, so its change is only reported once.
+        let changes = EffectiveConfig::new(&before)?.diff(&EffectiveConfig::new(&after)?);
+        assert_eq!(
+            changes,
+            vec![
+                ConfigChange::Action {
+                    outcome: "suspected",
+                    before: render(&crate::Action::Allow),
+                    after: render(&tarpit),
+                },
+                ConfigChange::RouteAction {
+                    route: "/signup".to_string(),
+                    outcome: "restricted",
+                    before: render(&crate::Action::Block),
+                    after: render(&crate::Action::Drop),
+                },
+            ]
+        );
+        assert_eq!(
+            changes[1].to_string(),
+            r#"~ route /signup action restricted: {"type":"block"} -> {"type":"drop"}"#
+        );
+        Ok(())
+    }
+}
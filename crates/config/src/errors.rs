@@ -6,19 +6,29 @@ pub enum ConfigFileError {
     #[error(transparent)]
     Deserialization(#[from] toml::de::Error),
     #[error(transparent)]
+    YamlDeserialization(#[from] serde_yaml::Error),
+    #[error(transparent)]
+    JsonDeserialization(#[from] serde_json::Error),
+    #[error(transparent)]
+    Serialization(#[from] toml::ser::Error),
+    #[error(transparent)]
     Validation(#[from] validator::ValidationError),
     #[error(transparent)]
     Validations(#[from] validator::ValidationErrors),
     #[error(transparent)]
     Resolution(#[from] ResolutionError),
+    #[error(transparent)]
+    PluginSource(#[from] PluginSourceError),
     #[error("missing parent: '{0}'")]
     MissingParent(String),
     #[error("invalid circular include: '{0}'")]
     CircularInclude(String),
     #[error("duplicate named plugin or preset: '{0}'")]
     Duplicate(String),
-    #[error("invalid plugin config: {0}")]
-    InvalidPluginConfig(String),
+    #[error("missing profile: '{0}'")]
+    MissingProfile(String),
+    #[error("duplicate profile: '{0}'")]
+    DuplicateProfile(String),
 }
 
 /// This error will be returned if an attempt to serialize a config structure fails.
@@ -35,4 +45,19 @@ pub enum ResolutionError {
     Missing(String),
     #[error("invalid circular preset reference: '{0}'")]
     CircularPreset(String),
+    #[error("invalid circular plugin dependency: '{0}'")]
+    CircularDependency(String),
+}
+
+/// This error will be returned if a plugin's path cannot be interpreted as a source of plugin WASM.
+#[derive(thiserror::Error, Debug)]
+pub enum PluginSourceError {
+    #[error(transparent)]
+    Base64(#[from] base64::DecodeError),
+    #[error("invalid data uri: {0}")]
+    InvalidDataUri(String),
+    #[error("invalid file uri: '{0}'")]
+    InvalidFileUri(String),
+    #[error("unsupported plugin uri scheme: '{0}'")]
+    UnsupportedScheme(String),
 }
@@ -0,0 +1,2325 @@
+//! The `file` module provides deserialization and parsing for Bulwark's configuration files.
+//!
+//! Config files may be written in TOML, YAML, or JSON. Every format shares the serialization structures in this
+//! module, and only the parser differs. See [`ConfigFormat`](crate::ConfigFormat).
+
+// Due to the need for multiple serialization mappings, file deserialization is not done
+// directly in the [`bulwark_config`](crate) module's structs.
+
+use crate::ConfigFileError;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsString,
+    fs,
+    path::Path,
+    path::PathBuf,
+};
+use validator::Validate;
+
+mod schema;
+
+pub use schema::config_schema;
+
+lazy_static! {
+    static ref RE_VALID_REFERENCE: Regex = Regex::new(r"^[_a-z]+$").unwrap();
+    static ref RE_VALID_SHA256: Regex = Regex::new(r"^[0-9a-fA-F]{64}$").unwrap();
+    static ref RE_VALID_HOST: Regex =
+        Regex::new(r"^(\*\.)?[0-9A-Za-z-]+(\.[0-9A-Za-z-]+)*$").unwrap();
+    static ref RE_VALID_TOKEN: Regex = Regex::new(r"^[!#$%&'*+.^_`|~0-9A-Za-z-]+$").unwrap();
+}
+
+/// The file serialization for a [Config](crate::Config) structure.
+#[derive(Serialize, Deserialize, Default, JsonSchema)]
+#[schemars(
+    title = "Bulwark config",
+    description = "The root of a Bulwark configuration."
+)]
+struct Config {
+    #[serde(default)]
+    service: Service,
+    #[serde(default)]
+    runtime: Runtime,
+    #[serde(default)]
+    state: State,
+    #[serde(default)]
+    thresholds: Thresholds,
+    #[serde(default)]
+    actions: Actions,
+    #[serde(default)]
+    metrics: Metrics,
+    #[serde(default)]
+    capture: Capture,
+    /// Other config files to merge into this one.
+    #[serde(default, rename(serialize = "include", deserialize = "include"))]
+    includes: Vec<Include>,
+    /// Configurations for individual plugins.
+    #[serde(default, rename(serialize = "plugin", deserialize = "plugin"))]
+    plugins: Vec<Plugin>,
+    /// Plugin groups that allow a plugin set to be loaded with a single reference.
+    #[serde(default, rename(serialize = "preset", deserialize = "preset"))]
+    presets: Vec<Preset>,
+    /// Routes that map from resource paths to plugins or presets.
+    #[serde(default, rename(serialize = "resource", deserialize = "resource"))]
+    resources: Vec<Resource>,
+    /// Named sets of overrides that may be selected when the config is loaded.
+    #[serde(default, rename(serialize = "profile", deserialize = "profile"))]
+    profiles: HashMap<String, Profile>,
+}
+
+/// The file serialization for a [Service](crate::Service) config structure.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Configuration for the services being launched.")]
+struct Service {
+    /// The port for the primary service.
+    #[serde(default = "default_port")]
+    port: u16,
+    /// The port for the internal admin service.
+    #[serde(default = "default_admin_port")]
+    admin_port: u16,
+    /// Whether the admin service should be enabled.
+    #[serde(default = "default_admin")]
+    admin_enabled: bool,
+    /// The number of trusted proxy hops expected in front of Bulwark.
+    #[serde(default = "default_proxy_hops")]
+    proxy_hops: u8,
+}
+
+/// The default port for the primary service.
+///
+/// See [`DEFAULT_PORT`].
+fn default_port() -> u16 {
+    crate::DEFAULT_PORT
+}
+
+/// The default port for the internal admin service.
+///
+/// See [`DEFAULT_ADMIN_PORT`].
+fn default_admin_port() -> u16 {
+    crate::DEFAULT_ADMIN_PORT
+}
+
+/// The default for whether the admin service should be enabled or not.
+fn default_admin() -> bool {
+    true
+}
+
+/// The default number of internal proxy hops expected in front of Bulwark.
+fn default_proxy_hops() -> u8 {
+    0
+}
+
+impl Default for Service {
+    fn default() -> Self {
+        Self {
+            port: default_port(),
+            admin_port: default_admin_port(),
+            admin_enabled: default_admin(),
+            proxy_hops: default_proxy_hops(),
+        }
+    }
+}
+
+impl From<Service> for crate::Service {
+    fn from(service: Service) -> Self {
+        Self {
+            port: service.port,
+            admin_port: service.admin_port,
+            admin_enabled: service.admin_enabled,
+            proxy_hops: service.proxy_hops,
+        }
+    }
+}
+
+/// The file serialization for a [Runtime](crate::Runtime) config structure.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Configuration for the runtime environment.")]
+struct Runtime {
+    /// The maximum number of concurrent incoming requests before blocking.
+    #[serde(default = "default_max_concurrent_requests")]
+    max_concurrent_requests: usize,
+    /// The maximum number of concurrent plugin tasks.
+    #[serde(default = "default_max_plugin_tasks")]
+    max_plugin_tasks: usize,
+    /// The time limits applied to plugins that don't set their own.
+    #[serde(default = "default_timeout")]
+    default_timeout: Timeout,
+}
+
+/// The default maximum number of concurrent incoming requests that the runtime will process before blocking.
+///
+/// See [`DEFAULT_MAX_CONCURRENT_REQUESTS`].
+fn default_max_concurrent_requests() -> usize {
+    crate::DEFAULT_MAX_CONCURRENT_REQUESTS
+}
+
+/// The default maximum number of concurrent plugin tasks that the runtime will launch.
+///
+/// See [`DEFAULT_MAX_PLUGIN_TASKS`].
+fn default_max_plugin_tasks() -> usize {
+    crate::DEFAULT_MAX_PLUGIN_TASKS
+}
+
+/// The default time limit for each plugin execution phase.
+///
+/// See [`DEFAULT_TIMEOUT`].
+fn default_timeout() -> Timeout {
+    Timeout::Uniform(crate::DEFAULT_TIMEOUT)
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: default_max_concurrent_requests(),
+            max_plugin_tasks: default_max_plugin_tasks(),
+            default_timeout: default_timeout(),
+        }
+    }
+}
+
+impl From<Runtime> for crate::Runtime {
+    fn from(service: Runtime) -> Self {
+        Self {
+            max_concurrent_requests: service.max_concurrent_requests,
+            max_plugin_tasks: service.max_plugin_tasks,
+            // Phases missing from a partial table still need a value at the runtime level.
+            default_timeout: crate::Timeout::from(service.default_timeout)
+                .or(crate::Timeout::uniform(crate::DEFAULT_TIMEOUT)),
+        }
+    }
+}
+
+/// The file serialization for a [Timeout](crate::Timeout) structure.
+///
+/// Either a single number of milliseconds for every phase, or a table with a value for some or all phases.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(untagged)]
+#[schemars(description = "Time limits in milliseconds for plugin execution phases.")]
+enum Timeout {
+    /// A time limit applied to every plugin execution phase.
+    Uniform(u64),
+    Phases(TimeoutPhases),
+}
+
+/// The file serialization for a [Timeout](crate::Timeout) structure with separate values for each phase.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(description = "Separate time limits for each plugin execution phase.")]
+struct TimeoutPhases {
+    init: Option<u64>,
+    enrichment: Option<u64>,
+    decision: Option<u64>,
+    feedback: Option<u64>,
+}
+
+impl From<Timeout> for crate::Timeout {
+    fn from(timeout: Timeout) -> Self {
+        match timeout {
+            Timeout::Uniform(millis) => Self::uniform(millis),
+            Timeout::Phases(phases) => Self {
+                init: phases.init,
+                enrichment: phases.enrichment,
+                decision: phases.decision,
+                feedback: phases.feedback,
+            },
+        }
+    }
+}
+
+/// The file serialization for a [State](crate::State) config structure.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Configuration for state managed by Bulwark plugins.")]
+struct State {
+    /// The URI for the external Redis state store.
+    #[serde(default = "default_redis_uri")]
+    redis_uri: Option<String>,
+    /// The size of the Redis connection pool.
+    #[serde(default = "default_redis_pool_size")]
+    // The default depends on the number of CPUs where the schema is generated, so it isn't documented.
+    #[schemars(skip_serializing_if = "schema::omit_default")]
+    redis_pool_size: usize,
+}
+
+/// The default for the network address to access remote state.
+fn default_redis_uri() -> Option<String> {
+    None
+}
+
+/// The default for the remote state connection pool size.
+fn default_redis_pool_size() -> usize {
+    num_cpus::get_physical() * 4
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            redis_uri: default_redis_uri(),
+            redis_pool_size: default_redis_pool_size(),
+        }
+    }
+}
+
+impl From<State> for crate::State {
+    fn from(state: State) -> Self {
+        Self {
+            redis_uri: state.redis_uri,
+            redis_pool_size: state.redis_pool_size,
+        }
+    }
+}
+
+/// The file serialization for a [Thresholds](crate::Thresholds) structure.
+#[derive(Validate, Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Configuration for the decision thresholds.")]
+struct Thresholds {
+    /// Whether to take no action in response to restrict decisions.
+    #[serde(default = "default_observe_only")]
+    observe_only: bool,
+    /// The score above which requests will be restricted.
+    #[serde(default = "default_restrict_threshold")]
+    #[validate(range(min = 0.0, max = 1.0))]
+    restrict: f64,
+    /// The score above which requests will be treated as suspicious.
+    #[serde(default = "default_suspicious_threshold")]
+    #[validate(range(min = 0.0, max = 1.0))]
+    suspicious: f64,
+    /// The score below which requests will be trusted.
+    #[serde(default = "default_trust_threshold")]
+    #[validate(range(min = 0.0, max = 1.0))]
+    trust: f64,
+    /// The accept or restrict value at which a decisive plugin finalizes the decision.
+    #[serde(default = "default_decisive_threshold")]
+    #[validate(range(min = 0.0, max = 1.0))]
+    decisive: f64,
+}
+
+/// The default for whether the primary service should take no action in response to restrict decisions.
+fn default_observe_only() -> bool {
+    crate::DEFAULT_OBSERVE_ONLY
+}
+
+/// The default threshold for restricting a request.
+fn default_restrict_threshold() -> f64 {
+    crate::DEFAULT_RESTRICT_THRESHOLD
+}
+
+/// The default threshold for treating a request as suspicious.
+fn default_suspicious_threshold() -> f64 {
+    crate::DEFAULT_SUSPICIOUS_THRESHOLD
+}
+
+/// The default threshold for trusting a request.
+fn default_trust_threshold() -> f64 {
+    crate::DEFAULT_TRUST_THRESHOLD
+}
+
+/// The default threshold for a decisive plugin to finalize a decision.
+fn default_decisive_threshold() -> f64 {
+    crate::DEFAULT_DECISIVE_THRESHOLD
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            observe_only: default_observe_only(),
+            restrict: default_restrict_threshold(),
+            suspicious: default_suspicious_threshold(),
+            trust: default_trust_threshold(),
+            decisive: default_decisive_threshold(),
+        }
+    }
+}
+
+impl From<Thresholds> for crate::Thresholds {
+    fn from(thresholds: Thresholds) -> Self {
+        Self {
+            observe_only: thresholds.observe_only,
+            restrict: thresholds.restrict,
+            suspicious: thresholds.suspicious,
+            trust: thresholds.trust,
+            decisive: thresholds.decisive,
+        }
+    }
+}
+
+/// The file serialization for an [Actions](crate::Actions) structure.
+#[derive(Validate, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(description = "Configuration for the actions taken in response to each outcome.")]
+struct Actions {
+    /// The action taken for trusted requests.
+    #[serde(default = "default_allow_action")]
+    #[validate(custom = "validate_action")]
+    trusted: Action,
+    /// The action taken for accepted requests.
+    #[serde(default = "default_allow_action")]
+    #[validate(custom = "validate_action")]
+    accepted: Action,
+    /// The action taken for suspected requests.
+    #[serde(default = "default_allow_action")]
+    #[validate(custom = "validate_action")]
+    suspected: Action,
+    /// The action taken for restricted requests.
+    #[serde(default = "default_restricted_action")]
+    #[validate(custom = "validate_action")]
+    restricted: Action,
+}
+
+/// The default action for every outcome other than restricted.
+fn default_allow_action() -> Action {
+    Action::Allow
+}
+
+/// The default action for restricted requests.
+fn default_restricted_action() -> Action {
+    Action::Block
+}
+
+impl Default for Actions {
+    fn default() -> Self {
+        Self {
+            trusted: default_allow_action(),
+            accepted: default_allow_action(),
+            suspected: default_allow_action(),
+            restricted: default_restricted_action(),
+        }
+    }
+}
+
+impl From<Actions> for crate::Actions {
+    fn from(actions: Actions) -> Self {
+        Self {
+            trusted: actions.trusted.into(),
+            accepted: actions.accepted.into(),
+            suspected: actions.suspected.into(),
+            restricted: actions.restricted.into(),
+        }
+    }
+}
+
+/// The file serialization for an [Action](crate::Action) structure.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+#[schemars(description = "An action taken in response to an outcome.")]
+enum Action {
+    /// Allows the request to continue on to the upstream service.
+    Allow,
+    /// Blocks the request with a 403 response.
+    Block,
+    /// Redirects the request to a challenge with a 302 response.
+    Redirect {
+        /// The URL or path of the challenge.
+        #[schemars(length(min = 1))]
+        location: String,
+    },
+    /// Rejects the request with a 429 response.
+    RateLimit {
+        /// The number of seconds sent in the Retry-After header.
+        retry_after: u64,
+    },
+    /// Holds the request for a period of time before allowing it.
+    Tarpit {
+        /// The number of milliseconds to delay the request by.
+        delay: u64,
+    },
+    /// Ends the request without sending a response.
+    Drop,
+}
+
+impl From<Action> for crate::Action {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::Allow => Self::Allow,
+            Action::Block => Self::Block,
+            Action::Redirect { location } => Self::Redirect { location },
+            Action::RateLimit { retry_after } => Self::RateLimit { retry_after },
+            Action::Tarpit { delay } => Self::Tarpit { delay },
+            Action::Drop => Self::Drop,
+        }
+    }
+}
+
+fn validate_action(action: &Action) -> Result<(), validator::ValidationError> {
+    if let Action::Redirect { location } = action {
+        // Redirects may be to an absolute URL or to a path on the same host.
+        if (!location.starts_with('/') && url::Url::parse(location).is_err())
+            || location.chars().any(char::is_control)
+        {
+            return Err(validator::ValidationError::new("invalid_location"));
+        }
+    }
+    Ok(())
+}
+
+/// The file serialization for a [Metrics](crate::Metrics) structure.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Configuration for metrics collection.")]
+struct Metrics {
+    /// The StatsD host. Prometheus metrics are used if unset.
+    #[serde(default)]
+    statsd_host: Option<String>,
+    /// The StatsD port.
+    #[serde(default = "default_statsd_port")]
+    statsd_port: Option<u16>,
+    /// The StatsD client queue size.
+    #[serde(default = "default_statsd_queue_size")]
+    statsd_queue_size: usize,
+    /// The StatsD client buffer size.
+    #[serde(default = "default_statsd_buffer_size")]
+    statsd_buffer_size: usize,
+    /// The prefix added to StatsD metric names.
+    #[serde(default)]
+    statsd_prefix: String,
+}
+
+fn default_statsd_port() -> Option<u16> {
+    crate::DEFAULT_STATSD_PORT
+}
+
+fn default_statsd_queue_size() -> usize {
+    crate::DEFAULT_STATSD_QUEUE_SIZE
+}
+
+fn default_statsd_buffer_size() -> usize {
+    crate::DEFAULT_STATSD_BUFFER_SIZE
+}
+
+impl Default for Metrics {
+    /// Default metrics config
+    fn default() -> Self {
+        Self {
+            statsd_host: None,
+            statsd_port: default_statsd_port(),
+            statsd_queue_size: default_statsd_queue_size(),
+            statsd_buffer_size: default_statsd_buffer_size(),
+            statsd_prefix: String::from(""),
+        }
+    }
+}
+
+impl From<Metrics> for crate::Metrics {
+    fn from(metrics: Metrics) -> Self {
+        Self {
+            statsd_host: metrics.statsd_host.clone(),
+            statsd_port: metrics.statsd_port,
+            statsd_queue_size: metrics.statsd_queue_size,
+            statsd_buffer_size: metrics.statsd_buffer_size,
+            statsd_prefix: metrics.statsd_prefix,
+        }
+    }
+}
+
+/// The file serialization for a [Capture](crate::Capture) structure.
+#[derive(Validate, Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Configuration for capturing processed traffic for later replay.")]
+struct Capture {
+    /// The NDJSON file each processed request is appended to. Capture is disabled if unset.
+    #[serde(default)]
+    path: Option<String>,
+    /// The maximum number of bytes kept from each request and response body.
+    #[serde(default = "default_capture_max_body_size")]
+    max_body_size: usize,
+    /// The size in bytes at which the capture file is rotated.
+    #[serde(default = "default_capture_max_file_size")]
+    #[schemars(range(min = 1))]
+    max_file_size: u64,
+    /// The number of rotated capture files kept in addition to the current one.
+    #[serde(default = "default_capture_max_files")]
+    max_files: usize,
+    /// The request and response headers whose values are redacted in the capture file.
+    #[serde(default = "default_capture_redact_headers")]
+    #[validate(custom = "validate_header_names")]
+    #[schemars(inner(regex(path = "RE_VALID_TOKEN")))]
+    redact_headers: Vec<String>,
+}
+
+fn default_capture_max_body_size() -> usize {
+    crate::DEFAULT_CAPTURE_MAX_BODY_SIZE
+}
+
+fn default_capture_max_file_size() -> u64 {
+    crate::DEFAULT_CAPTURE_MAX_FILE_SIZE
+}
+
+fn default_capture_max_files() -> usize {
+    crate::DEFAULT_CAPTURE_MAX_FILES
+}
+
+fn default_capture_redact_headers() -> Vec<String> {
+    crate::DEFAULT_CAPTURE_REDACT_HEADERS
+        .iter()
+        .map(|header| header.to_string())
+        .collect()
+}
+
+impl Default for Capture {
+    /// Default capture config
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_body_size: default_capture_max_body_size(),
+            max_file_size: default_capture_max_file_size(),
+            max_files: default_capture_max_files(),
+            redact_headers: default_capture_redact_headers(),
+        }
+    }
+}
+
+impl From<Capture> for crate::Capture {
+    fn from(capture: Capture) -> Self {
+        Self {
+            path: capture.path,
+            max_body_size: capture.max_body_size,
+            max_file_size: capture.max_file_size,
+            max_files: capture.max_files,
+            redact_headers: capture
+                .redact_headers
+                .iter()
+                .map(|header| header.to_lowercase())
+                .collect(),
+        }
+    }
+}
+
+/// The file serialization for an [Include](crate::Include) structure.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Another config file to merge into this one.")]
+struct Include {
+    /// The path to the included file, relative to this file.
+    #[schemars(length(min = 1))]
+    path: String,
+}
+
+/// The file serialization for a Plugin structure.
+#[derive(Validate, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(description = "Configuration for an individual plugin.")]
+struct Plugin {
+    /// The plugin reference key.
+    #[serde(rename(serialize = "ref", deserialize = "ref"))]
+    #[validate(length(min = 1, max = 96), regex(path = "RE_VALID_REFERENCE"))]
+    reference: String,
+    /// The location of the plugin WASM as a file path, file URI, or base64 data URI.
+    #[validate(length(min = 1))]
+    path: String,
+    /// The expected SHA-256 digest of the plugin WASM as a hex string.
+    #[serde(default)]
+    #[validate(regex(path = "RE_VALID_SHA256"))]
+    sha256: Option<String>,
+    /// A weight to multiply this plugin's decision values by.
+    #[serde(default = "default_plugin_weight")]
+    #[validate(range(min = 0.0))]
+    weight: f64,
+    /// Configuration passed into the plugin environment.
+    #[serde(default)]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    config: ConfigTable,
+    #[serde(default)]
+    permissions: TomlPermissions,
+    #[serde(default)]
+    #[validate]
+    http_client: HttpClient,
+    timeout: Option<Timeout>,
+    /// Plugins whose request enrichment must complete before this plugin's begins.
+    #[serde(default)]
+    #[validate(custom = "validate_references")]
+    #[schemars(inner(regex(path = "RE_VALID_REFERENCE")))]
+    after: Vec<String>,
+    /// Whether a certain decision from this plugin finalizes the decision.
+    #[serde(default)]
+    decisive: bool,
+}
+
+fn validate_references(references: &[String]) -> Result<(), validator::ValidationError> {
+    for reference in references {
+        if !RE_VALID_REFERENCE.is_match(reference) {
+            return Err(validator::ValidationError::new("invalid_reference"));
+        }
+    }
+    Ok(())
+}
+
+/// The default weight for a plugin.
+///
+/// See DEFAULT_PLUGIN_WEIGHT.
+fn default_plugin_weight() -> f64 {
+    crate::DEFAULT_PLUGIN_WEIGHT
+}
+
+impl From<&Plugin> for crate::config::Plugin {
+    fn from(plugin: &Plugin) -> Self {
+        Self {
+            reference: plugin.reference.clone(),
+            path: plugin.path.clone(),
+            sha256: plugin
+                .sha256
+                .as_ref()
+                .map(|digest| digest.to_ascii_lowercase()),
+            weight: plugin.weight,
+            config: config_table_to_json(plugin.config.clone()),
+            permissions: plugin.permissions.clone().into(),
+            http_client: plugin.http_client.clone().into(),
+            timeout: plugin.timeout.map(Timeout::into).unwrap_or_default(),
+            after: plugin.after.clone(),
+            decisive: plugin.decisive,
+        }
+    }
+}
+
+/// A value in a plugin's config, as written in any of the config file formats.
+///
+/// Plugin config is free-form, so it's kept in this format-neutral form until it's converted to JSON for the plugin.
+/// Unlike `toml::Value` it accepts the nulls and unsigned integers that YAML and JSON may contain, and unlike
+/// `serde_json::Value` it accepts TOML datetimes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+enum ConfigValue {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    UnsignedInteger(u64),
+    Float(f64),
+    String(String),
+    // Datetimes deserialize from a specially keyed table, so they have to be tried before other tables.
+    Datetime(toml::value::Datetime),
+    Array(Vec<ConfigValue>),
+    Table(ConfigTable),
+}
+
+/// A table of plugin config values. See [`ConfigValue`].
+type ConfigTable = BTreeMap<String, ConfigValue>;
+
+fn config_table_to_json(table: ConfigTable) -> serde_json::map::Map<String, serde_json::Value> {
+    table
+        .into_iter()
+        .map(|(key, value)| (key, config_value_to_json(value)))
+        .collect()
+}
+
+fn config_value_to_json(value: ConfigValue) -> serde_json::Value {
+    match value {
+        ConfigValue::Null => serde_json::Value::Null,
+        ConfigValue::Boolean(v) => serde_json::Value::Bool(v),
+        ConfigValue::Integer(v) => serde_json::Value::Number(serde_json::Number::from(v)),
+        ConfigValue::UnsignedInteger(v) => serde_json::Value::Number(serde_json::Number::from(v)),
+        ConfigValue::Float(v) => {
+            // TODO: probably should return a result instead of panicking although NaN in a config would be weird
+            serde_json::Value::Number(serde_json::Number::from_f64(v).unwrap())
+        }
+        ConfigValue::String(v) => serde_json::Value::String(v),
+        ConfigValue::Datetime(v) => {
+            // TODO: probably should return a result instead of panicking
+            let ts = chrono::DateTime::parse_from_rfc3339(v.to_string().as_str()).unwrap();
+            serde_json::Value::String(ts.to_rfc3339())
+        }
+        ConfigValue::Array(v) => {
+            serde_json::Value::Array(v.into_iter().map(config_value_to_json).collect())
+        }
+        ConfigValue::Table(v) => serde_json::Value::Object(config_table_to_json(v)),
+    }
+}
+
+/// The file serialization for an [HttpClient](crate::HttpClient) structure.
+#[derive(Validate, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(description = "The limits applied to outbound HTTP requests made by a plugin.")]
+struct HttpClient {
+    /// The maximum time in milliseconds to wait for a connection to be established.
+    #[serde(default = "default_http_connect_timeout")]
+    #[validate(range(min = 1))]
+    connect_timeout: u64,
+    /// The maximum time in milliseconds to wait for the first byte of a response, and between any subsequent bytes.
+    #[serde(default = "default_http_first_byte_timeout")]
+    #[validate(range(min = 1))]
+    first_byte_timeout: u64,
+    /// The maximum size in bytes of a response body.
+    #[serde(default = "default_http_max_response_size")]
+    max_response_size: u64,
+    /// The maximum number of outbound requests the plugin may have in flight at once.
+    #[serde(default = "default_http_max_concurrent_requests")]
+    #[validate(range(min = 1))]
+    max_concurrent_requests: usize,
+    #[serde(default)]
+    #[validate]
+    cache: Option<HttpCache>,
+}
+
+fn default_http_connect_timeout() -> u64 {
+    crate::DEFAULT_HTTP_CONNECT_TIMEOUT
+}
+
+fn default_http_first_byte_timeout() -> u64 {
+    crate::DEFAULT_HTTP_FIRST_BYTE_TIMEOUT
+}
+
+fn default_http_max_response_size() -> u64 {
+    crate::DEFAULT_HTTP_MAX_RESPONSE_SIZE
+}
+
+fn default_http_max_concurrent_requests() -> usize {
+    crate::DEFAULT_HTTP_MAX_CONCURRENT_REQUESTS
+}
+
+impl Default for HttpClient {
+    /// Default outbound HTTP limits
+    fn default() -> Self {
+        Self {
+            connect_timeout: default_http_connect_timeout(),
+            first_byte_timeout: default_http_first_byte_timeout(),
+            max_response_size: default_http_max_response_size(),
+            max_concurrent_requests: default_http_max_concurrent_requests(),
+            cache: None,
+        }
+    }
+}
+
+impl From<HttpClient> for crate::HttpClient {
+    fn from(http_client: HttpClient) -> Self {
+        Self {
+            connect_timeout: http_client.connect_timeout,
+            first_byte_timeout: http_client.first_byte_timeout,
+            max_response_size: http_client.max_response_size,
+            max_concurrent_requests: http_client.max_concurrent_requests,
+            cache: http_client.cache.map(HttpCache::into),
+        }
+    }
+}
+
+/// The file serialization for an [HttpCache](crate::HttpCache) structure.
+#[derive(Validate, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(description = "Caching for responses to a plugin's outbound GET requests.")]
+struct HttpCache {
+    /// The longest time in seconds that a response may be cached for.
+    #[serde(default = "default_http_cache_max_ttl")]
+    #[validate(range(min = 1))]
+    max_ttl: u64,
+    /// The request headers whose values are part of the cache key.
+    #[serde(default)]
+    #[validate(custom = "validate_header_names")]
+    #[schemars(inner(regex(path = "RE_VALID_TOKEN")))]
+    headers: Vec<String>,
+    /// Where cached responses are stored.
+    #[serde(default)]
+    backend: HttpCacheBackend,
+    /// The most bytes of responses held by the in-memory backend.
+    #[serde(default = "default_http_cache_max_memory_size")]
+    max_memory_size: u64,
+}
+
+fn default_http_cache_max_ttl() -> u64 {
+    crate::DEFAULT_HTTP_CACHE_MAX_TTL
+}
+
+fn default_http_cache_max_memory_size() -> u64 {
+    crate::DEFAULT_HTTP_CACHE_MAX_MEMORY_SIZE
+}
+
+fn validate_header_names(headers: &[String]) -> Result<(), validator::ValidationError> {
+    for header in headers {
+        if !RE_VALID_TOKEN.is_match(header) {
+            return Err(validator::ValidationError::new("invalid_header_name"));
+        }
+    }
+    Ok(())
+}
+
+/// The file serialization for an [HttpCacheBackend](crate::HttpCacheBackend) enum.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum HttpCacheBackend {
+    #[default]
+    Memory,
+    Redis,
+}
+
+impl From<HttpCache> for crate::HttpCache {
+    fn from(cache: HttpCache) -> Self {
+        Self {
+            max_ttl: cache.max_ttl,
+            headers: cache
+                .headers
+                .iter()
+                .map(|header| header.to_ascii_lowercase())
+                .collect(),
+            backend: match cache.backend {
+                HttpCacheBackend::Memory => crate::HttpCacheBackend::Memory,
+                HttpCacheBackend::Redis => crate::HttpCacheBackend::Redis,
+            },
+            max_memory_size: cache.max_memory_size,
+        }
+    }
+}
+
+/// The file serialization for a [Permissions](crate::Permissions) structure.
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[schemars(description = "The permissions granted to a plugin.")]
+struct TomlPermissions {
+    /// Environment variables the plugin may read.
+    #[serde(default)]
+    env: Vec<String>,
+    /// Domains the plugin may send HTTP requests to.
+    #[serde(default)]
+    http: Vec<String>,
+    /// Key prefixes the plugin may access in remote state.
+    #[serde(default)]
+    state: Vec<String>,
+}
+
+impl From<TomlPermissions> for crate::config::Permissions {
+    fn from(permissions: TomlPermissions) -> Self {
+        Self {
+            env: permissions.env,
+            http: permissions.http,
+            state: permissions.state,
+        }
+    }
+}
+
+/// The file serialization for a named set of overrides applied on top of the rest of the config.
+///
+/// Settings tables are merged into the corresponding top-level tables key by key. A profile is only
+/// applied when selected, see [`load_config_with_profile`].
+#[derive(Serialize, Deserialize, Default, JsonSchema)]
+#[schemars(description = "A named set of overrides applied on top of the rest of the config.")]
+struct Profile {
+    // Settings tables are kept as tables so they can be merged, but they take the same keys as the top-level tables.
+    #[serde(default)]
+    #[schemars(with = "Service")]
+    service: toml::Table,
+    #[serde(default)]
+    #[schemars(with = "Runtime")]
+    runtime: toml::Table,
+    #[serde(default)]
+    #[schemars(with = "State")]
+    state: toml::Table,
+    #[serde(default)]
+    #[schemars(with = "Thresholds")]
+    thresholds: toml::Table,
+    #[serde(default)]
+    actions: ActionOverrides,
+    #[serde(default)]
+    #[schemars(with = "Metrics")]
+    metrics: toml::Table,
+    #[serde(default)]
+    #[schemars(with = "Capture")]
+    capture: toml::Table,
+    /// Overrides for individual plugins.
+    #[serde(default, rename(serialize = "plugin", deserialize = "plugin"))]
+    plugins: Vec<ProfilePlugin>,
+}
+
+impl Profile {
+    /// Applies the profile's overrides to a config.
+    fn apply(self, root: &mut Config) -> Result<(), ConfigFileError> {
+        root.service = overlay(&root.service, self.service)?;
+        root.runtime = overlay(&root.runtime, self.runtime)?;
+        root.state = overlay(&root.state, self.state)?;
+        root.thresholds = overlay(&root.thresholds, self.thresholds)?;
+        self.actions.apply(&mut root.actions);
+        root.metrics = overlay(&root.metrics, self.metrics)?;
+        root.capture = overlay(&root.capture, self.capture)?;
+        for plugin_override in self.plugins {
+            let plugin = root
+                .plugins
+                .iter_mut()
+                .find(|plugin| plugin.reference == plugin_override.reference)
+                .ok_or(crate::ResolutionError::Missing(
+                    plugin_override.reference.clone(),
+                ))?;
+            plugin_override.apply(plugin);
+        }
+        Ok(())
+    }
+}
+
+/// The file serialization for a profile's overrides to a single plugin.
+///
+/// The plugin's config is patched by merging tables key by key, unless `replace_config` is set, in
+/// which case it is replaced entirely.
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "Overrides for a plugin declared elsewhere in the config.")]
+struct ProfilePlugin {
+    /// The reference key of the overridden plugin.
+    #[serde(rename(serialize = "ref", deserialize = "ref"))]
+    reference: String,
+    /// Replaces the location of the plugin WASM.
+    #[schemars(length(min = 1))]
+    path: Option<String>,
+    /// Replaces the expected SHA-256 digest of the plugin WASM.
+    #[schemars(regex(path = "RE_VALID_SHA256"))]
+    sha256: Option<String>,
+    /// Replaces the plugin's weight.
+    #[schemars(range(min = 0.0))]
+    weight: Option<f64>,
+    /// Merged into the plugin's config.
+    #[schemars(with = "Option<serde_json::Map<String, serde_json::Value>>")]
+    config: Option<ConfigTable>,
+    /// Whether to replace the plugin's config entirely instead of merging into it.
+    #[serde(default)]
+    replace_config: bool,
+    permissions: Option<TomlPermissions>,
+    http_client: Option<HttpClient>,
+    timeout: Option<Timeout>,
+    /// Replaces the plugins whose request enrichment must complete before this plugin's begins.
+    #[schemars(inner(regex(path = "RE_VALID_REFERENCE")))]
+    after: Option<Vec<String>>,
+    /// Replaces whether a certain decision from this plugin finalizes the decision.
+    decisive: Option<bool>,
+}
+
+impl ProfilePlugin {
+    fn apply(self, plugin: &mut Plugin) {
+        if let Some(path) = self.path {
+            plugin.path = path;
+        }
+        if let Some(sha256) = self.sha256 {
+            plugin.sha256 = Some(sha256);
+        }
+        if let Some(weight) = self.weight {
+            plugin.weight = weight;
+        }
+        if let Some(config) = self.config {
+            if self.replace_config {
+                plugin.config = config;
+            } else {
+                merge_config_tables(&mut plugin.config, config);
+            }
+        }
+        if let Some(permissions) = self.permissions {
+            plugin.permissions = permissions;
+        }
+        if let Some(http_client) = self.http_client {
+            plugin.http_client = http_client;
+        }
+        if let Some(timeout) = self.timeout {
+            plugin.timeout = Some(timeout);
+        }
+        if let Some(after) = self.after {
+            plugin.after = after;
+        }
+        if let Some(decisive) = self.decisive {
+            plugin.decisive = decisive;
+        }
+    }
+}
+
+/// Merges `patch` into `base`, recursing into tables that are present in both.
+///
+/// Any other value in `patch`, including arrays, replaces the value in `base`.
+fn merge_tables(base: &mut toml::Table, patch: toml::Table) {
+    for (key, value) in patch {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(patch)) => {
+                merge_tables(base, patch)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Merges `patch` into a plugin's config the same way as [`merge_tables`].
+fn merge_config_tables(base: &mut ConfigTable, patch: ConfigTable) {
+    for (key, value) in patch {
+        match (base.get_mut(&key), value) {
+            (Some(ConfigValue::Table(base)), ConfigValue::Table(patch)) => {
+                merge_config_tables(base, patch)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Applies a table of overrides to a settings table, such as [`Service`].
+fn overlay<T>(base: &T, patch: toml::Table) -> Result<T, ConfigFileError>
+where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    let mut table = toml::Table::try_from(base)?;
+    merge_tables(&mut table, patch);
+    Ok(toml::Value::Table(table).try_into()?)
+}
+
+/// The file serialization for a [Preset](crate::Preset) structure.
+#[derive(Validate, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(description = "A group of plugins that may be loaded with a single reference.")]
+struct Preset {
+    /// The preset reference key.
+    #[serde(rename(serialize = "ref", deserialize = "ref"))]
+    #[validate(length(min = 1, max = 96), regex(path = "RE_VALID_REFERENCE"))]
+    reference: String,
+    /// References to the plugins or presets in this group.
+    #[validate(length(min = 1))]
+    plugins: Vec<String>,
+}
+
+/// The file serialization for a [Resource](crate::Resource) structure.
+#[derive(Validate, Serialize, Deserialize, Clone, JsonSchema)]
+#[schemars(
+    description = "A mapping between a route pattern and the plugins that should be run for matching requests."
+)]
+struct Resource {
+    /// The route pattern used to match requests with.
+    route: String,
+    /// The hostnames this resource applies to, optionally with a leading '*.' wildcard.
+    #[serde(default)]
+    #[validate(custom = "validate_hosts")]
+    #[schemars(inner(regex(path = "RE_VALID_HOST")))]
+    hosts: Vec<String>,
+    /// The HTTP methods this resource applies to.
+    #[serde(default)]
+    #[validate(custom = "validate_methods")]
+    #[schemars(inner(regex(path = "RE_VALID_TOKEN")))]
+    methods: Vec<String>,
+    /// Request headers that must be present, mapped to their required value or '*'.
+    #[serde(default)]
+    #[validate(custom = "validate_headers")]
+    #[schemars(schema_with = "schema::header_conditions")]
+    headers: HashMap<String, String>,
+    /// References to the plugins or presets for this route.
+    #[validate(custom = "validate_resource_plugins")]
+    plugins: Vec<ResourcePlugin>,
+    timeout: Option<Timeout>,
+    #[serde(default)]
+    #[validate]
+    actions: ActionOverrides,
+}
+
+/// The file serialization for actions that take the place of the top-level actions for some outcomes.
+///
+/// Used by resources and by profiles. Each action is replaced as a whole rather than merged.
+#[derive(Validate, Serialize, Deserialize, Clone, Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(
+    description = "Actions that take the place of the top-level actions for the same outcome."
+)]
+struct ActionOverrides {
+    /// The action taken for trusted requests.
+    #[validate(custom = "validate_action")]
+    trusted: Option<Action>,
+    /// The action taken for accepted requests.
+    #[validate(custom = "validate_action")]
+    accepted: Option<Action>,
+    /// The action taken for suspected requests.
+    #[validate(custom = "validate_action")]
+    suspected: Option<Action>,
+    /// The action taken for restricted requests.
+    #[validate(custom = "validate_action")]
+    restricted: Option<Action>,
+}
+
+impl ActionOverrides {
+    /// Replaces the top-level actions for any outcome with an action.
+    fn apply(self, actions: &mut Actions) {
+        let overrides = [
+            (self.trusted, &mut actions.trusted),
+            (self.accepted, &mut actions.accepted),
+            (self.suspected, &mut actions.suspected),
+            (self.restricted, &mut actions.restricted),
+        ];
+        for (action, replaced) in overrides {
+            if let Some(action) = action {
+                *replaced = action;
+            }
+        }
+    }
+
+    /// Fills in any outcome without an action from the top-level actions.
+    fn resolve(&self, actions: &crate::Actions) -> crate::Actions {
+        let resolve = |action: &Option<Action>, fallback: &crate::Action| {
+            action
+                .clone()
+                .map(Action::into)
+                .unwrap_or_else(|| fallback.clone())
+        };
+        crate::Actions {
+            trusted: resolve(&self.trusted, &actions.trusted),
+            accepted: resolve(&self.accepted, &actions.accepted),
+            suspected: resolve(&self.suspected, &actions.suspected),
+            restricted: resolve(&self.restricted, &actions.restricted),
+        }
+    }
+}
+
+/// The file serialization for a resource's reference to a plugin or preset.
+///
+/// Either a bare reference, or a table with a reference and overrides for the referenced plugins.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(untagged)]
+#[schemars(
+    description = "A plugin or preset reference, or a table with a reference and overrides for the referenced plugins."
+)]
+enum ResourcePlugin {
+    /// The plugin or preset reference key.
+    Reference(String),
+    Override(PluginOverride),
+}
+
+impl ResourcePlugin {
+    fn reference(&self) -> &String {
+        match self {
+            ResourcePlugin::Reference(reference) => reference,
+            ResourcePlugin::Override(plugin_override) => &plugin_override.reference,
+        }
+    }
+}
+
+/// The file serialization for a [PluginOverride](crate::PluginOverride) structure.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(
+    description = "A reference to a plugin or preset with settings that only apply to this resource."
+)]
+struct PluginOverride {
+    /// The plugin or preset reference key.
+    #[serde(rename(serialize = "ref", deserialize = "ref"))]
+    reference: String,
+    /// Replaces the weight of the referenced plugins.
+    #[schemars(range(min = 0.0))]
+    weight: Option<f64>,
+    /// Merged into the config of the referenced plugins.
+    #[serde(default)]
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    config: ConfigTable,
+    /// Whether to replace the plugins' config entirely instead of merging into it.
+    #[serde(default)]
+    replace_config: bool,
+}
+
+impl From<&PluginOverride> for crate::PluginOverride {
+    fn from(plugin_override: &PluginOverride) -> Self {
+        Self {
+            weight: plugin_override.weight,
+            config: config_table_to_json(plugin_override.config.clone()),
+            replace_config: plugin_override.replace_config,
+        }
+    }
+}
+
+fn validate_resource_plugins(plugins: &[ResourcePlugin]) -> Result<(), validator::ValidationError> {
+    for plugin in plugins {
+        if let ResourcePlugin::Override(PluginOverride {
+            weight: Some(weight),
+            ..
+        }) = plugin
+        {
+            if *weight < 0.0 {
+                return Err(validator::ValidationError::new("invalid_weight"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_hosts(hosts: &[String]) -> Result<(), validator::ValidationError> {
+    for host in hosts {
+        if !RE_VALID_HOST.is_match(host) {
+            return Err(validator::ValidationError::new("invalid_host"));
+        }
+    }
+    Ok(())
+}
+
+fn validate_methods(methods: &[String]) -> Result<(), validator::ValidationError> {
+    for method in methods {
+        if !RE_VALID_TOKEN.is_match(method) {
+            return Err(validator::ValidationError::new("invalid_method"));
+        }
+    }
+    Ok(())
+}
+
+fn validate_headers(headers: &HashMap<String, String>) -> Result<(), validator::ValidationError> {
+    for (name, value) in headers {
+        if !RE_VALID_TOKEN.is_match(name) {
+            return Err(validator::ValidationError::new("invalid_header_name"));
+        }
+        // Header values may contain anything but control characters, with the exception of horizontal tabs.
+        if value
+            .bytes()
+            .any(|byte| (byte < b' ' && byte != b'\t') || byte == 0x7f)
+        {
+            return Err(validator::ValidationError::new("invalid_header_value"));
+        }
+    }
+    Ok(())
+}
+
+fn resolve_path<'a, B, P>(base: &'a B, path: &'a P) -> Result<PathBuf, ConfigFileError>
+where
+    B: 'a + ?Sized + AsRef<Path>,
+    P: 'a + ?Sized + AsRef<Path>,
+{
+    let joined_path = base
+        .as_ref()
+        .parent()
+        .ok_or(ConfigFileError::MissingParent(
+            base.as_ref().to_string_lossy().to_string(),
+        ))?
+        .join(path);
+    Ok(fs::canonicalize(joined_path)?)
+}
+
+/// Resolves a plugin's location relative to the config file it was declared in.
+fn resolve_plugin_path<'a, B>(config_path: &'a B, path: &str) -> Result<String, ConfigFileError>
+where
+    B: 'a + ?Sized + AsRef<Path>,
+{
+    Ok(match crate::PluginSource::parse(path)? {
+        crate::PluginSource::File(path) => resolve_path(config_path, &path)?
+            .to_string_lossy()
+            .to_string(),
+        // Embedded plugins have no location to resolve.
+        crate::PluginSource::Data(_) => path.to_string(),
+    })
+}
+
+/// Loads a config file into a [`Config`](crate::Config) structure.
+///
+/// The format of the config file and of each of its includes is selected by file extension,
+/// so a YAML config may include TOML or JSON files and vice versa.
+pub fn load_config<'a, P>(config_path: &'a P) -> Result<crate::Config, ConfigFileError>
+where
+    P: 'a + ?Sized + AsRef<Path>,
+{
+    load_config_with_profile(config_path, None)
+}
+
+/// Loads a config file into a [`Config`](crate::Config) structure, applying the named profile.
+///
+/// Profiles may be declared in the config file or any of its includes. Selecting a profile that
+/// isn't declared is an error. See [`load_config`].
+pub fn load_config_with_profile<'a, P>(
+    config_path: &'a P,
+    profile: Option<&str>,
+) -> Result<crate::Config, ConfigFileError>
+where
+    P: 'a + ?Sized + AsRef<Path>,
+{
+    let mut loaded_files = HashSet::new();
+
+    fn load_config_recursive<'a, P>(
+        config_path: &'a P,
+        loaded_files: &mut HashSet<OsString>,
+    ) -> Result<Config, ConfigFileError>
+    where
+        P: 'a + ?Sized + AsRef<Path>,
+    {
+        let path_string = config_path.as_ref().as_os_str().to_os_string();
+        if loaded_files.contains(&path_string) {
+            return Err(ConfigFileError::CircularInclude(
+                path_string.to_string_lossy().to_string(),
+            ));
+        } else {
+            loaded_files.insert(path_string);
+        }
+        let config_data = fs::read_to_string(config_path)?;
+        let mut root: Config =
+            crate::ConfigFormat::from_path(config_path).deserialize(&config_data)?;
+        let base = config_path
+            .as_ref()
+            .parent()
+            .ok_or(ConfigFileError::MissingParent(
+                config_path.as_ref().to_string_lossy().to_string(),
+            ))?;
+
+        for include in &root.includes {
+            let include_path = base.join(&include.path);
+            let include_root = load_config_recursive(&include_path, loaded_files)?;
+
+            // TODO: clean this up
+            let root_plugins = root.plugins;
+            let mut combined_plugins: Vec<Plugin> =
+                Vec::with_capacity(root_plugins.len() + include_root.plugins.len());
+            combined_plugins.extend_from_slice(root_plugins.as_slice());
+            combined_plugins.extend_from_slice(include_root.plugins.as_slice());
+            root.plugins = combined_plugins;
+
+            let root_presets = root.presets;
+            let mut combined_presets: Vec<Preset> =
+                Vec::with_capacity(root_presets.len() + include_root.presets.len());
+            combined_presets.extend_from_slice(root_presets.as_slice());
+            combined_presets.extend_from_slice(include_root.presets.as_slice());
+            root.presets = combined_presets;
+
+            let root_resources = root.resources;
+            let mut combined_resources: Vec<Resource> =
+                Vec::with_capacity(root_resources.len() + include_root.resources.len());
+            combined_resources.extend_from_slice(root_resources.as_slice());
+            combined_resources.extend_from_slice(include_root.resources.as_slice());
+            root.resources = combined_resources;
+
+            for (name, profile) in include_root.profiles {
+                if root.profiles.contains_key(&name) {
+                    return Err(ConfigFileError::DuplicateProfile(name));
+                }
+                root.profiles.insert(name, profile);
+            }
+        }
+
+        // Strip includes once processed
+        root.includes = vec![];
+
+        // Resolve plugins relative to config path
+        root.plugins = root
+            .plugins
+            .iter()
+            .map(|plugin| -> Result<Plugin, ConfigFileError> {
+                Ok(Plugin {
+                    reference: plugin.reference.clone(),
+                    path: resolve_plugin_path(config_path, &plugin.path)?,
+                    sha256: plugin.sha256.clone(),
+                    weight: plugin.weight,
+                    config: plugin.config.clone(),
+                    permissions: plugin.permissions.clone(),
+                    http_client: plugin.http_client.clone(),
+                    timeout: plugin.timeout,
+                    after: plugin.after.clone(),
+                    decisive: plugin.decisive,
+                })
+            })
+            .collect::<Result<Vec<Plugin>, ConfigFileError>>()?;
+        for profile in root.profiles.values_mut() {
+            for plugin_override in profile.plugins.iter_mut() {
+                if let Some(path) = &plugin_override.path {
+                    plugin_override.path = Some(resolve_plugin_path(config_path, path)?);
+                }
+            }
+        }
+
+        Ok(root)
+    }
+
+    // Load the raw serialization format and resolve includes
+    let mut root = load_config_recursive(config_path, &mut loaded_files)?;
+
+    // Apply the selected profile before validation so that overrides are validated too
+    if let Some(name) = profile {
+        let profile = root
+            .profiles
+            .remove(name)
+            .ok_or(ConfigFileError::MissingProfile(name.to_string()))?;
+        profile.apply(&mut root)?;
+    }
+
+    // Validate presets and plugins and their references
+    let mut references: HashSet<&String> = HashSet::new();
+    for preset in &root.presets {
+        preset.validate()?;
+        if references.contains(&preset.reference) {
+            return Err(ConfigFileError::Duplicate(preset.reference.to_string()));
+        } else {
+            references.insert(&preset.reference);
+        }
+    }
+    for plugin in &root.plugins {
+        plugin.validate()?;
+        if references.contains(&plugin.reference) {
+            return Err(ConfigFileError::Duplicate(plugin.reference.to_string()));
+        } else {
+            references.insert(&plugin.reference);
+        }
+    }
+    root.thresholds.validate()?;
+    root.capture.validate()?;
+    root.actions.validate()?;
+    for resource in &root.resources {
+        resource.validate()?;
+    }
+    let resolve_reference = |ref_name: &String| {
+        let mut reference = crate::config::Reference::Missing(ref_name.clone());
+        for preset in &root.presets {
+            if preset.reference == *ref_name {
+                reference = crate::config::Reference::Preset(ref_name.clone());
+            }
+        }
+        for plugin in &root.plugins {
+            if plugin.reference == *ref_name {
+                reference = crate::config::Reference::Plugin(ref_name.clone());
+            }
+        }
+        reference
+    };
+    // Transfer to the public config type, checking reference enums
+    let actions: crate::Actions = root.actions.into();
+    let config = crate::Config {
+        service: root.service.into(),
+        runtime: root.runtime.into(),
+        state: root.state.into(),
+        thresholds: root.thresholds.into(),
+        actions: actions.clone(),
+        metrics: root.metrics.into(),
+        capture: root.capture.into(),
+        plugins: root.plugins.iter().map(|plugin| plugin.into()).collect(),
+        presets: root
+            .presets
+            .iter()
+            .map(|preset| crate::config::Preset {
+                reference: preset.reference.clone(),
+                plugins: preset.plugins.iter().map(resolve_reference).collect(),
+            })
+            .collect(),
+        resources: root
+            .resources
+            .iter()
+            .map(|resource| crate::config::Resource {
+                route: resource.route.clone(),
+                hosts: resource
+                    .hosts
+                    .iter()
+                    .map(|host| host.to_ascii_lowercase())
+                    .collect(),
+                methods: resource
+                    .methods
+                    .iter()
+                    .map(|method| method.to_ascii_uppercase())
+                    .collect(),
+                headers: resource
+                    .headers
+                    .iter()
+                    .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+                    .collect(),
+                plugins: resource
+                    .plugins
+                    .iter()
+                    .map(|plugin| resolve_reference(plugin.reference()))
+                    .collect(),
+                overrides: resource
+                    .plugins
+                    .iter()
+                    .filter_map(|plugin| match plugin {
+                        ResourcePlugin::Reference(_) => None,
+                        ResourcePlugin::Override(plugin_override) => {
+                            Some((plugin_override.reference.clone(), plugin_override.into()))
+                        }
+                    })
+                    .collect(),
+                timeout: resource.timeout.map(Timeout::into).unwrap_or_default(),
+                actions: resource.actions.resolve(&actions),
+            })
+            .collect(),
+    };
+    for plugin in &config.plugins {
+        // Resolve dependencies to surface missing references and cycles immediately
+        plugin.resolve_dependencies(&config)?;
+    }
+    for resource in &config.resources {
+        // Resolve plugins to surface resolution errors immediately
+        resource.resolve_plugins(&config)?;
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_plugins() -> Result<(), Box<dyn std::error::Error>> {
+        let project_root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../..");
+
+        if !project_root
+            .join("tests/dist/plugins/bulwark_blank_slate.wasm")
+            .exists()
+        {
+            bulwark_build::build_plugin(
+                project_root.join("crates/sdk/examples/blank-slate"),
+                project_root.join("tests/dist/plugins/bulwark_blank_slate.wasm"),
+                &[],
+                true,
+            )?;
+            assert!(project_root
+                .join("tests/dist/plugins/bulwark_blank_slate.wasm")
+                .exists());
+        }
+
+        if !project_root
+            .join("tests/dist/plugins/bulwark_evil_bit.wasm")
+            .exists()
+        {
+            bulwark_build::build_plugin(
+                project_root.join("crates/sdk/examples/evil-bit"),
+                project_root.join("tests/dist/plugins/bulwark_evil_bit.wasm"),
+                &[],
+                true,
+            )?;
+            assert!(project_root
+                .join("tests/dist/plugins/bulwark_evil_bit.wasm")
+                .exists());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root: Config = toml::from_str(
+            r#"
+        [service]
+        port = 10002
+
+        [state]
+        redis_uri = "redis://10.0.0.1:6379"
+
+        [thresholds]
+        restrict = 0.75
+
+        [metrics]
+        statsd_host = "10.0.0.2"
+        statsd_prefix = "bulwark_"
+
+        [[include]]
+        path = "default.toml"
+
+        [[plugin]]
+        ref = "evil_bit"
+        path = "bulwark_evil_bit.wasm"
+        decisive = true
+
+        [[preset]]
+        ref = "custom"
+        plugins = ["evil-bit"]
+
+        [[resource]]
+        route = "/"
+        plugins = ["custom"]
+        timeout = 25
+    "#,
+        )?;
+
+        assert_eq!(root.service.port, 10002); // non-default
+        assert_eq!(root.service.admin_port, crate::DEFAULT_ADMIN_PORT);
+        assert_eq!(
+            root.state.redis_uri,
+            Some(String::from("redis://10.0.0.1:6379"))
+        );
+
+        assert_eq!(root.metrics.statsd_host, Some(String::from("10.0.0.2")));
+        assert_eq!(root.metrics.statsd_port, Some(8125));
+        assert_eq!(root.metrics.statsd_prefix, String::from("bulwark_"));
+
+        assert_eq!(root.thresholds.restrict, 0.75); // non-default
+        assert_eq!(
+            root.thresholds.suspicious,
+            crate::DEFAULT_SUSPICIOUS_THRESHOLD
+        );
+        assert_eq!(root.thresholds.trust, crate::DEFAULT_TRUST_THRESHOLD);
+        assert_eq!(root.thresholds.decisive, crate::DEFAULT_DECISIVE_THRESHOLD);
+
+        assert_eq!(root.includes.len(), 1);
+        assert_eq!(root.includes.first().unwrap().path, "default.toml");
+
+        assert_eq!(root.plugins.len(), 1);
+        assert_eq!(root.plugins.first().unwrap().reference, "evil_bit");
+        assert!(root.plugins.first().unwrap().decisive);
+        assert!(root
+            .plugins
+            .first()
+            .unwrap()
+            .path
+            .ends_with("bulwark_evil_bit.wasm"));
+        assert_eq!(root.plugins.first().unwrap().config, ConfigTable::default());
+
+        assert_eq!(root.presets.len(), 1);
+        assert_eq!(root.presets.first().unwrap().reference, "custom");
+        assert_eq!(root.presets.first().unwrap().plugins, vec!["evil-bit"]);
+
+        assert_eq!(root.resources.len(), 1);
+        assert_eq!(root.resources.first().unwrap().route, "/");
+        assert_eq!(
+            root.resources
+                .first()
+                .unwrap()
+                .plugins
+                .iter()
+                .map(ResourcePlugin::reference)
+                .collect::<Vec<_>>(),
+            vec!["custom"]
+        );
+        assert_eq!(
+            root.resources.first().unwrap().timeout,
+            Some(Timeout::Uniform(25))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root: crate::config::Config = load_config("tests/main.toml")?;
+
+        assert_eq!(root.service.port, 10002); // non-default
+        assert_eq!(root.service.admin_port, crate::DEFAULT_ADMIN_PORT);
+
+        assert_eq!(
+            root.state.redis_uri,
+            Some(String::from("redis://127.0.0.1:6379"))
+        );
+        // We don't know how many CPUs will be present but we know it's not zero,
+        // so pool size has to be at least 4.
+        assert!(root.state.redis_pool_size >= 4);
+
+        assert_eq!(root.metrics.statsd_host, Some(String::from("10.0.0.2")));
+        assert_eq!(root.metrics.statsd_port, Some(8125));
+        assert_eq!(root.metrics.statsd_prefix, String::from("bulwark_"));
+
+        assert_eq!(root.thresholds.restrict, 0.75); // non-default
+        assert_eq!(
+            root.thresholds.suspicious,
+            crate::DEFAULT_SUSPICIOUS_THRESHOLD
+        );
+        assert_eq!(root.thresholds.trust, crate::DEFAULT_TRUST_THRESHOLD);
+
+        assert_eq!(
+            root.capture.redact_headers,
+            crate::DEFAULT_CAPTURE_REDACT_HEADERS
+        );
+
+        assert_eq!(root.plugins.len(), 2);
+        assert_eq!(root.plugins.first().unwrap().reference, "evil_bit");
+        assert!(root
+            .plugins
+            .first()
+            .unwrap()
+            .path
+            .ends_with("bulwark_evil_bit.wasm"));
+        assert_eq!(
+            root.plugins.first().unwrap().config,
+            serde_json::map::Map::default()
+        );
+
+        assert_eq!(root.presets.len(), 2);
+        assert_eq!(root.presets.first().unwrap().reference, "default");
+        assert_eq!(root.presets.last().unwrap().reference, "starter_preset");
+        assert_eq!(
+            root.presets.first().unwrap().plugins,
+            vec![
+                crate::config::Reference::Plugin("evil_bit".to_string()),
+                crate::config::Reference::Preset("starter_preset".to_string())
+            ]
+        );
+        assert_eq!(
+            root.presets.get(1).unwrap().plugins,
+            vec![crate::config::Reference::Plugin("blank_slate".to_string())]
+        );
+
+        assert_eq!(root.resources.len(), 2);
+        assert_eq!(root.resources.first().unwrap().route, "/");
+        assert_eq!(root.resources.last().unwrap().route, "/*params");
+        assert_eq!(
+            root.resources.first().unwrap().plugins,
+            vec![crate::config::Reference::Preset("default".to_string())]
+        );
+        assert_eq!(
+            root.resources.first().unwrap().timeout,
+            crate::Timeout::uniform(25)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_value() -> Result<(), Box<dyn std::error::Error>> {
+        let toml_table: ConfigTable = toml::from_str(
+            r#"
+            since = 1979-05-27T07:32:00Z
+            limits = { count = -1, ratio = 0.5, names = ["a", "b"] }
+            "#,
+        )?;
+        assert_eq!(
+            serde_json::Value::Object(config_table_to_json(toml_table)),
+            serde_json::json!({
+                "since": "1979-05-27T07:32:00+00:00",
+                "limits": { "count": -1, "ratio": 0.5, "names": ["a", "b"] }
+            })
+        );
+
+        let json = serde_json::json!({
+            "fallback": null,
+            "max_id": u64::MAX,
+            "nested": { "enabled": true }
+        });
+        let json_table: ConfigTable = serde_json::from_value(json.clone())?;
+        assert_eq!(
+            serde_json::Value::Object(config_table_to_json(json_table)),
+            json
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_formats() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        // Each fixture describes the same config, with includes in a different format than the root.
+        let toml_root: crate::config::Config = load_config("tests/main.toml")?;
+        let mut yaml_root: crate::config::Config = load_config("tests/main.yaml")?;
+        let mut json_root: crate::config::Config = load_config("tests/main.json")?;
+
+        // TOML has no null and no integers beyond the range of i64, so only the other fixtures' plugin config has them.
+        for root in [&mut yaml_root, &mut json_root] {
+            let config = &mut root
+                .plugins
+                .iter_mut()
+                .find(|plugin| plugin.reference == "evil_bit")
+                .unwrap()
+                .config;
+            assert_eq!(config.remove("fallback"), Some(serde_json::Value::Null));
+            assert_eq!(config.remove("max_id"), Some(serde_json::json!(u64::MAX)));
+        }
+
+        // The config structs don't implement PartialEq, but none of these fixtures have unordered maps.
+        assert_eq!(format!("{:?}", yaml_root), format!("{:?}", toml_root));
+        assert_eq!(format!("{:?}", json_root), format!("{:?}", toml_root));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_actions() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root: crate::config::Config = load_config("tests/actions.toml")?;
+        assert_eq!(root.actions.trusted, crate::Action::Allow);
+        assert_eq!(root.actions.suspected, crate::Action::Tarpit { delay: 250 });
+        assert_eq!(
+            root.actions.restricted,
+            crate::Action::RateLimit { retry_after: 30 }
+        );
+
+        // Outcomes without a resource action fall back to the top-level actions.
+        let signup = &root.resources[0].actions;
+        assert_eq!(
+            signup.suspected,
+            crate::Action::Redirect {
+                location: "https://challenge.example.com/captcha".to_string()
+            }
+        );
+        assert_eq!(signup.restricted, root.actions.restricted);
+        assert_eq!(root.resources[1].actions, root.actions);
+
+        let root = load_config_with_profile("tests/actions.toml", Some("strict"))?;
+        assert_eq!(root.actions.restricted, crate::Action::Drop);
+        assert_eq!(
+            root.actions.suspected,
+            crate::Action::Redirect {
+                location: "/challenge".to_string()
+            }
+        );
+        assert_eq!(root.resources[0].actions.restricted, crate::Action::Drop);
+
+        let result = load_config("tests/invalid_action.toml");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("invalid_location"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_plugin_overrides() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root: crate::config::Config = load_config("tests/plugin_overrides.toml")?;
+        let json_map = |value: serde_json::Value| value.as_object().unwrap().clone();
+
+        // The declared plugin is unchanged by the overrides.
+        let evil_bit = root.plugin("evil_bit").unwrap();
+        assert_eq!(evil_bit.weight, crate::DEFAULT_PLUGIN_WEIGHT);
+        assert_eq!(
+            evil_bit.config,
+            json_map(serde_json::json!({ "limit": 10, "window": { "unit": "s", "size": 60 } }))
+        );
+
+        let login = root.resources[0].resolve_plugins_with_overrides(&root)?;
+        assert_eq!(login.len(), 2);
+        assert_eq!(login[0].reference, "blank_slate");
+        assert_eq!(login[0].weight, crate::DEFAULT_PLUGIN_WEIGHT);
+        assert_eq!(
+            login[0].config,
+            json_map(serde_json::json!({ "mode": "strict" }))
+        );
+        assert_eq!(login[1].reference, "evil_bit");
+        assert_eq!(login[1].weight, 2.0);
+        assert_eq!(
+            login[1].config,
+            json_map(serde_json::json!({ "limit": 5, "window": { "unit": "s", "size": 10 } }))
+        );
+
+        let search = root.resources[1].resolve_plugins_with_overrides(&root)?;
+        assert_eq!(search[1].reference, "evil_bit");
+        assert_eq!(
+            search[1].config,
+            json_map(serde_json::json!({ "limit": 50 }))
+        );
+
+        let fallback = root.resources[2].resolve_plugins_with_overrides(&root)?;
+        assert_eq!(fallback[1].config, evil_bit.config);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_profiles() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root: crate::config::Config = load_config("tests/profiles.toml")?;
+        assert_eq!(root.service.port, 10002);
+        assert_eq!(root.thresholds.restrict, 0.75);
+        assert!(!root.thresholds.observe_only);
+        assert_eq!(
+            root.plugin("evil_bit").unwrap().config,
+            serde_json::json!({ "limit": 10, "window": { "unit": "s", "size": 60 } })
+                .as_object()
+                .unwrap()
+                .clone()
+        );
+
+        let root: crate::config::Config =
+            load_config_with_profile("tests/profiles.toml", Some("staging"))?;
+        assert_eq!(root.service.port, 20002);
+        assert_eq!(root.service.admin_port, crate::DEFAULT_ADMIN_PORT);
+        assert_eq!(root.thresholds.restrict, 0.9);
+        assert!(root.thresholds.observe_only);
+        assert_eq!(
+            root.thresholds.suspicious,
+            crate::DEFAULT_SUSPICIOUS_THRESHOLD
+        );
+        let evil_bit = root.plugin("evil_bit").unwrap();
+        assert_eq!(evil_bit.weight, 1.5);
+        assert!(evil_bit.path.ends_with("bulwark_evil_bit.wasm"));
+        // Nested tables are patched rather than replaced.
+        assert_eq!(
+            evil_bit.config,
+            serde_json::json!({ "limit": 100, "window": { "unit": "s", "size": 10 } })
+                .as_object()
+                .unwrap()
+                .clone()
+        );
+        assert_eq!(evil_bit.permissions.http, vec!["staging.example.com"]);
+        assert_eq!(
+            root.plugin("blank_slate").unwrap().config,
+            serde_json::json!({ "mode": "strict" })
+                .as_object()
+                .unwrap()
+                .clone()
+        );
+
+        let root: crate::config::Config =
+            load_config_with_profile("tests/profiles.toml", Some("dev"))?;
+        assert_eq!(root.service.port, 10002);
+        assert_eq!(
+            root.state.redis_uri,
+            Some(String::from("redis://127.0.0.1:6379"))
+        );
+        assert_eq!(root.plugin("evil_bit").unwrap().weight, 0.0);
+
+        let result = load_config_with_profile("tests/profiles.toml", Some("prod"));
+        assert_eq!(result.unwrap_err().to_string(), "missing profile: 'prod'");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_overlapping_preset() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root: crate::config::Config = load_config("tests/overlapping_preset.toml")?;
+
+        let resource = root.resources.first().unwrap();
+        let plugins = resource.resolve_plugins(&root)?;
+        assert_eq!(plugins.len(), 2);
+        assert_eq!(
+            plugins.first().unwrap().reference,
+            root.plugin("blank_slate").unwrap().reference
+        );
+        assert_eq!(
+            plugins.last().unwrap().reference,
+            root.plugin("evil_bit").unwrap().reference
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_circular_include() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let result = load_config("tests/circular_include.toml");
+        // This needs to be an error, not a panic.
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid circular include"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_circular_preset() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let result = load_config("tests/circular_preset.toml");
+        // This needs to be an error, not a panic.
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid circular preset reference"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_circular_dependency() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let result = load_config("tests/circular_dependency.toml");
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid circular plugin dependency"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_shared_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+        // Each plugin depends on both plugins in the next layer, so there are 2^40 paths through the layers. This
+        // only resolves in reasonable time if each shared dependency is walked once.
+        let layers = 40;
+        let reference = |layer: usize, side: &str| format!("layer_{}_{}", layer, side);
+        let after = |layer: usize| {
+            if layer < layers {
+                vec![reference(layer, "a"), reference(layer, "b")]
+            } else {
+                vec![]
+            }
+        };
+        let mut plugins = vec![crate::Plugin {
+            reference: "root".to_string(),
+            after: after(0),
+            ..Default::default()
+        }];
+        for layer in 0..layers {
+            for side in ["a", "b"] {
+                plugins.push(crate::Plugin {
+                    reference: reference(layer, side),
+                    after: after(layer + 1),
+                    ..Default::default()
+                });
+            }
+        }
+        let config = crate::Config {
+            service: crate::Service::default(),
+            runtime: crate::Runtime::default(),
+            state: crate::State::default(),
+            thresholds: crate::Thresholds::default(),
+            actions: crate::Actions::default(),
+            metrics: crate::Metrics::default(),
+            capture: crate::Capture::default(),
+            plugins,
+            presets: vec![],
+            resources: vec![],
+        };
+
+        let dependencies = config.plugins[0].resolve_dependencies(&config)?;
+        assert_eq!(dependencies.len(), layers * 2);
+        // Dependencies are still listed depth-first.
+        assert_eq!(
+            dependencies
+                .iter()
+                .take(3)
+                .map(|plugin| plugin.reference.as_str())
+                .collect::<Vec<_>>(),
+            vec!["layer_0_a", "layer_1_a", "layer_2_a"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_duplicate_plugin() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let result = load_config("tests/duplicate_plugin.toml");
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "duplicate named plugin or preset: 'blank_slate'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_duplicate_preset() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let result = load_config("tests/duplicate_preset.toml");
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "duplicate named plugin or preset: 'default'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_duplicate_mixed() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let result = load_config("tests/duplicate_mixed.toml");
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "duplicate named plugin or preset: 'blank_slate'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_nested_config_array() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root = load_config("tests/nested_config_array.toml")?;
+        assert_eq!(
+            root.plugins.first().unwrap().config.get("key"),
+            Some(&serde_json::json!([{ "subkey": "nested" }]))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_nested_config_object() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root = load_config("tests/nested_config_object.toml")?;
+        assert_eq!(
+            root.plugins.first().unwrap().config.get("key"),
+            Some(&serde_json::json!({ "subkey": ["nested"] }))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_resolution_missing() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let result = load_config("tests/missing.toml");
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "missing named plugin or preset: 'blank_slate'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_missing_include() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let result = load_config("tests/missing_include.toml");
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("No such file or directory"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_plugin_uri() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root: crate::config::Config = load_config("tests/plugin_uri.toml")?;
+
+        let embedded = root.plugin("embedded").unwrap();
+        assert_eq!(embedded.path, "data:application/wasm;base64,AGFzbQEAAAA=");
+        assert_eq!(
+            embedded.sha256,
+            Some("93a44bbb96c751218e4c00d479e4c14358122a389acca16205b1e4d0dc5f9476".to_string())
+        );
+        assert_eq!(
+            embedded.source()?,
+            crate::PluginSource::Data(b"\0asm\x01\0\0\0".to_vec())
+        );
+
+        let blank_slate = root.plugin("blank_slate").unwrap();
+        assert!(Path::new(&blank_slate.path).is_absolute());
+        assert_eq!(blank_slate.sha256, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_resource_matching() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root: crate::config::Config = load_config("tests/resource_matching.toml")?;
+
+        let login = root.resources.first().unwrap();
+        assert_eq!(login.hosts, vec!["shop.example.com", "*.shop.example.com"]);
+        assert_eq!(login.methods, vec!["POST"]);
+        assert_eq!(login.headers.len(), 2);
+        assert_eq!(
+            login.headers.get("content-type").map(String::as_str),
+            Some("application/x-www-form-urlencoded")
+        );
+        assert_eq!(login.headers.get("cookie").map(String::as_str), Some("*"));
+
+        let fallback = root.resources.last().unwrap();
+        assert!(fallback.hosts.is_empty());
+        assert!(fallback.methods.is_empty());
+        assert!(fallback.headers.is_empty());
+
+        let result = load_config("tests/invalid_header_value.toml");
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("invalid_header_value"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_timeouts() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root: crate::config::Config = load_config("tests/timeouts.toml")?;
+
+        assert_eq!(
+            root.runtime.default_timeout,
+            crate::Timeout {
+                init: Some(50),
+                enrichment: Some(crate::DEFAULT_TIMEOUT),
+                decision: Some(20),
+                feedback: Some(crate::DEFAULT_TIMEOUT),
+            }
+        );
+
+        let blank_slate = root.plugin("blank_slate").unwrap();
+        assert_eq!(
+            blank_slate.timeout,
+            crate::Timeout {
+                decision: Some(250),
+                ..Default::default()
+            }
+        );
+        let evil_bit = root.plugin("evil_bit").unwrap();
+        assert_eq!(evil_bit.timeout, crate::Timeout::default());
+
+        let resource = root.resources.first().unwrap();
+        assert_eq!(resource.timeout, crate::Timeout::uniform(25));
+        assert_eq!(
+            blank_slate
+                .timeout
+                .or(resource.timeout)
+                .or(root.runtime.default_timeout),
+            crate::Timeout {
+                init: Some(25),
+                enrichment: Some(25),
+                decision: Some(250),
+                feedback: Some(25),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_http_client() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let root: crate::config::Config = load_config("tests/http_client.toml")?;
+
+        let blank_slate = root.plugin("blank_slate").unwrap();
+        assert_eq!(
+            blank_slate.http_client,
+            crate::HttpClient {
+                connect_timeout: 250,
+                max_response_size: 65536,
+                ..Default::default()
+            }
+        );
+        assert_eq!(blank_slate.http_client.cache, None);
+        let evil_bit = root.plugin("evil_bit").unwrap();
+        assert_eq!(
+            evil_bit.http_client.first_byte_timeout,
+            crate::DEFAULT_HTTP_FIRST_BYTE_TIMEOUT
+        );
+        assert_eq!(
+            evil_bit.http_client.cache,
+            Some(crate::HttpCache {
+                max_ttl: 60,
+                headers: vec!["accept".to_string()],
+                backend: crate::HttpCacheBackend::Redis,
+                max_memory_size: crate::DEFAULT_HTTP_CACHE_MAX_MEMORY_SIZE,
+            })
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_invalid_sha256() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let result = load_config("tests/invalid_sha256.toml");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().starts_with("sha256"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_config_invalid_threshold() -> Result<(), Box<dyn std::error::Error>> {
+        build_plugins()?;
+
+        let result = load_config("tests/invalid_threshold.toml");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().starts_with("decisive"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plugin_source() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(
+            crate::PluginSource::parse("plugins/example.wasm")?,
+            crate::PluginSource::File(PathBuf::from("plugins/example.wasm"))
+        );
+        assert_eq!(
+            crate::PluginSource::parse("file:///opt/bulwark/example%20plugin.wasm")?,
+            crate::PluginSource::File(PathBuf::from("/opt/bulwark/example plugin.wasm"))
+        );
+        assert_eq!(
+            crate::PluginSource::parse("data:;base64,AGFzbQ==")?,
+            crate::PluginSource::Data(b"\0asm".to_vec())
+        );
+        assert_eq!(
+            crate::PluginSource::parse("data:application/wasm,AGFzbQ==")
+                .unwrap_err()
+                .to_string(),
+            "invalid data uri: only base64-encoded data is supported"
+        );
+        assert_eq!(
+            crate::PluginSource::parse("https://example.com/example.wasm")
+                .unwrap_err()
+                .to_string(),
+            "unsupported plugin uri scheme: 'https'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_path() -> Result<(), Box<dyn std::error::Error>> {
+        let base = PathBuf::new().join(".");
+        let path = Path::new("./src");
+        let resolved_path = resolve_path(&base, path)?;
+
+        assert!(resolved_path.ends_with("crates/config/src"));
+        Ok(())
+    }
+}
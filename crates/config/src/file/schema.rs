@@ -0,0 +1,198 @@
+//! Describes the file serialization structures as a [JSON Schema](https://json-schema.org/).
+//!
+//! The schema is derived with [`schemars`] from the same structures that config files are deserialized into, so the
+//! keys it describes can't drift from the keys the loader accepts. Descriptions come from the doc comments and
+//! `schemars` attributes on those structures.
+
+use super::*;
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{InstanceType, Schema, SchemaObject, StringValidation},
+    visit::{self, Visitor},
+};
+use serde_json::Value;
+
+/// Returns a JSON Schema describing the Bulwark config file format.
+///
+/// Editors and CI tooling may use this schema to validate config files without running Bulwark. Like the loader,
+/// the schema allows keys it doesn't recognize in most tables, so any file the loader accepts is also valid against
+/// the schema.
+pub fn config_schema() -> Value {
+    let settings = SchemaSettings::draft2019_09().with(|settings| {
+        // TOML has no null, so optional keys are left out rather than being nullable.
+        settings.option_nullable = false;
+        settings.option_add_null_type = false;
+        // The structure names are an implementation detail, so every table is described where it's used.
+        settings.inline_subschemas = true;
+        settings.visitors.push(Box::new(OmitDefaults));
+    });
+    let schema = settings.into_generator().into_root_schema_for::<Config>();
+    serde_json::to_value(schema).expect("schema should serialize")
+}
+
+/// Removes defaults that would be misleading in a TOML config.
+///
+/// Optional keys default to null, which TOML can't express, and every key in a table already describes its own
+/// default, so neither is kept.
+#[derive(Debug, Clone)]
+struct OmitDefaults;
+
+impl Visitor for OmitDefaults {
+    fn visit_schema_object(&mut self, schema: &mut SchemaObject) {
+        let is_table = schema.has_type(InstanceType::Object)
+            && schema
+                .object
+                .as_ref()
+                .is_some_and(|object| !object.properties.is_empty());
+        if let Some(metadata) = schema.metadata.as_mut() {
+            if is_table || metadata.default == Some(Value::Null) {
+                metadata.default = None;
+            }
+        }
+        visit::visit_schema_object(self, schema);
+    }
+}
+
+/// Leaves a field's default out of the schema.
+///
+/// Used for defaults that depend on the machine the schema is generated on.
+pub(super) fn omit_default<T>(_: &T) -> bool {
+    true
+}
+
+/// Describes resource header conditions, which map header names to a required value or `*`.
+pub(super) fn header_conditions(gen: &mut SchemaGenerator) -> Schema {
+    let mut schema = gen.subschema_for::<HashMap<String, String>>().into_object();
+    schema.object().property_names = Some(Box::new(
+        SchemaObject {
+            string: Some(Box::new(StringValidation {
+                pattern: Some(RE_VALID_TOKEN.to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into(),
+    ));
+    schema.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A config that sets at least one key in every table.
+    const FULL_CONFIG: &str = r#"
+        [service]
+        proxy_hops = 1
+
+        [runtime]
+        default_timeout = { init = 50, decision = 20 }
+
+        [state]
+        redis_uri = "redis://127.0.0.1:6379"
+        redis_pool_size = 8
+
+        [thresholds]
+        decisive = 0.95
+
+        [actions]
+        suspected = { type = "tarpit", delay = 500 }
+        restricted = { type = "rate_limit", retry_after = 60 }
+
+        [metrics]
+        statsd_host = "127.0.0.1"
+
+        [capture]
+        path = "capture.ndjson"
+        redact_headers = ["authorization", "cookie", "x-api-key"]
+
+        [[include]]
+        path = "include.toml"
+
+        [[plugin]]
+        ref = "evil_bit"
+        path = "bulwark_evil_bit.wasm"
+        sha256 = "93a44bbb96c751218e4c00d479e4c14358122a389acca16205b1e4d0dc5f9476"
+        timeout = 25
+        config = { key = "value", nested = { list = [1, 2] } }
+        permissions = { env = ["HOME"] }
+        http_client = { connect_timeout = 250, cache = { headers = ["accept"], backend = "redis" } }
+        after = ["blank_slate"]
+        decisive = true
+
+        [[preset]]
+        ref = "default"
+        plugins = ["evil_bit"]
+
+        [[resource]]
+        route = "/"
+        hosts = ["*.example.com"]
+        methods = ["GET"]
+        headers = { "cookie" = "*" }
+        plugins = ["default", { ref = "evil_bit", weight = 2.0, config = { key = "route" } }]
+        timeout = { decision = 25 }
+        actions = { suspected = { type = "redirect", location = "/challenge" } }
+
+        [profile.staging]
+        thresholds = { restrict = 0.9 }
+        actions = { restricted = { type = "block" } }
+
+        [[profile.staging.plugin]]
+        ref = "evil_bit"
+        config = { key = "staging" }
+        replace_config = true
+    "#;
+
+    /// Converts a TOML document to the JSON value that the schema applies to.
+    fn document(toml: &str) -> Value {
+        let document: ::toml::Value = ::toml::from_str(toml).unwrap();
+        serde_json::to_value(document).unwrap()
+    }
+
+    #[test]
+    fn test_config_schema() -> Result<(), Box<dyn std::error::Error>> {
+        // Every key that the loader accepts must be allowed by the schema.
+        ::toml::from_str::<Config>(FULL_CONFIG)?;
+        let schema = config_schema();
+        let validator = jsonschema::validator_for(&schema)?;
+        let errors: Vec<String> = validator
+            .iter_errors(&document(FULL_CONFIG))
+            .map(|error| format!("{}: {}", error.instance_path, error))
+            .collect();
+        assert!(errors.is_empty(), "{errors:?}");
+
+        for invalid in [
+            "[thresholds]\nrestrict = 1.5",
+            "[actions]\nrestricted = { type = \"redirect\" }",
+            "[[plugin]]\nref = \"Evil-Bit\"\npath = \"bulwark_evil_bit.wasm\"",
+            "[[plugin]]\nref = \"evil_bit\"",
+            "[[resource]]\nroute = \"/\"\nhosts = [\"exa mple.com\"]\nplugins = []",
+            "[[resource]]\nroute = \"/\"\nheaders = { \"bad header\" = \"*\" }\nplugins = []",
+        ] {
+            assert!(!validator.is_valid(&document(invalid)), "{invalid}");
+        }
+        // The loader ignores unknown keys in these tables, so the schema has to allow them too.
+        let unknown = "[service]\nadmin = false\n\n[profile.staging.service]\nprot = 8080";
+        ::toml::from_str::<Config>(unknown)?;
+        assert!(validator.is_valid(&document(unknown)));
+
+        assert_eq!(schema["title"], "Bulwark config");
+        assert_eq!(
+            schema["properties"]["service"]["properties"]["port"]["default"],
+            8089
+        );
+        assert_eq!(
+            schema["properties"]["plugin"]["items"]["required"],
+            json!(["path", "ref"])
+        );
+        // Tables, optional keys, and machine-dependent values have no default.
+        let state = &schema["properties"]["state"];
+        assert!(state.get("default").is_none());
+        assert!(state["properties"]["redis_uri"].get("default").is_none());
+        assert!(state["properties"]["redis_pool_size"]
+            .get("default")
+            .is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+//! The `format` module selects the parser used for each config file.
+//!
+//! Config files are parsed into the serialization structures of the [`file`](crate::file) module, so the format only
+//! decides how a file's text is read, not what it may contain.
+
+use crate::ConfigFileError;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+/// The file formats that Bulwark's configuration files may be written in.
+///
+/// All formats share the same structure and produce the same [`Config`](crate::Config). Each file,
+/// including any included file, has its format selected independently by its file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// TOML, selected by a `.toml` extension and used for any unrecognized extension.
+    Toml,
+    /// YAML, selected by a `.yaml` or `.yml` extension.
+    Yaml,
+    /// JSON, selected by a `.json` extension.
+    Json,
+}
+
+impl ConfigFormat {
+    /// Selects a config format based on a file's extension.
+    ///
+    /// Files without a recognized extension are assumed to be TOML.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("yaml") | Some("yml") => Self::Yaml,
+            Some("json") => Self::Json,
+            _ => Self::Toml,
+        }
+    }
+
+    /// Deserializes a config file's contents using this format.
+    pub(crate) fn deserialize<T>(&self, data: &str) -> Result<T, ConfigFileError>
+    where
+        T: DeserializeOwned,
+    {
+        Ok(match self {
+            Self::Toml => toml::from_str(data)?,
+            Self::Yaml => serde_yaml::from_str(data)?,
+            Self::Json => serde_json::from_str(data)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(ConfigFormat::from_path("bulwark.toml"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path("bulwark.yaml"), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path("bulwark.YML"), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path("bulwark.json"), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::from_path("bulwark.conf"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path("bulwark"), ConfigFormat::Toml);
+    }
+}
@@ -1,11 +1,21 @@
 //! Provides Bulwark's configuration and configuration management functionality.
 
 mod config;
+mod diff;
+mod effective;
 mod errors;
-pub mod toml;
+pub mod file;
+mod format;
 
 pub use crate::config::*;
+pub use crate::diff::*;
+pub use crate::effective::*;
 pub use crate::errors::*;
+pub use crate::file::{load_config, load_config_with_profile};
+pub use crate::format::*;
+
+/// The former name of the [`file`] module, kept so that existing callers continue to work.
+pub use crate::file as toml;
 
 #[macro_use]
 extern crate lazy_static;
@@ -1,4 +1,30 @@
-use bulwark_host::{ContextInstantiationError, PluginInstantiationError};
+use bulwark_host::{ContextInstantiationError, PluginInstantiationError, PluginLoadError};
+
+/// Returned when a [`BulwarkProcessor`](crate::BulwarkProcessor) cannot be initialized from its configuration.
+#[derive(thiserror::Error, Debug)]
+pub enum ProcessorInitError {
+    #[error(transparent)]
+    PluginLoad(#[from] PluginLoadError),
+    #[error(transparent)]
+    Resolution(#[from] bulwark_config::ResolutionError),
+    #[error(transparent)]
+    CreatePool(#[from] deadpool_redis::CreatePoolError),
+    #[error("error opening capture file: {0}")]
+    Capture(std::io::Error),
+    #[error("at least one resource required")]
+    ResourceMissing,
+    #[error("invalid {condition} condition '{value}' for resource '{route}'")]
+    InvalidCondition {
+        route: String,
+        condition: &'static str,
+        value: String,
+    },
+    #[error("route '{route}' conflicts with another resource: {source}")]
+    RouteConflict {
+        route: String,
+        source: matchit::InsertError,
+    },
+}
 
 /// Returned when trying to instantiate a plugin group and either the request context for a plugin or the plugin
 /// itself returns an instantiation error.
@@ -24,6 +50,19 @@ pub enum HandlerError {
     RouteMatch(#[from] matchit::MatchError),
 }
 
+/// Returned when a [`DecideRequest`](crate::DecideRequest) is invalid or could not be decided on.
+#[derive(thiserror::Error, Debug)]
+pub enum DecideError {
+    #[error("invalid base64 body: {0}")]
+    Body(#[from] base64::DecodeError),
+    #[error(transparent)]
+    Request(#[from] RequestError),
+    #[error(transparent)]
+    Response(#[from] ResponseError),
+    #[error(transparent)]
+    Handler(#[from] HandlerError),
+}
+
 /// Returned when trying to assemble a [`Request`](bulwark_sdk::Request) struct and Envoy sends missing
 /// or invalid information or an [HTTP error](http::Error) occurs.
 #[derive(thiserror::Error, Debug)]
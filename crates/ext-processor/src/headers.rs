@@ -1,11 +1,16 @@
-use bulwark_sdk::{Decision, Outcome};
+use crate::SfvError;
+use bulwark_sdk::{Decision, Outcome, Verdict};
 use sfv::{BareItem, Decimal, Dictionary, FromPrimitive, Item, List, ListEntry, SerializeValue};
 
+/// The header carrying the combined decision to the interior service.
+pub(crate) const DECISION_HEADER: &str = "bulwark-decision";
+/// The header carrying the combined tags to the interior service.
+pub(crate) const TAGS_HEADER: &str = "bulwark-tags";
+
 // TODO: capture the entire outcome: accepted/suspicious/restricted + threshold values
 // TODO: should this error for invalid Decision values?
 
 /// Serialize a combined [`Decision`] into a [SFV](sfv) header value to be sent with the request to the interior service.
-#[allow(dead_code)] // TODO: remove once moved to ext-auth
 pub(crate) fn serialize_decision_sfv(
     decision: Decision,
     outcome: Outcome,
@@ -35,7 +40,6 @@ pub(crate) fn serialize_decision_sfv(
 }
 
 /// Serialize a tag [`Vec`] into a [SFV](sfv) header value to be sent with the request to the interior service.
-#[allow(dead_code)] // TODO: remove once moved to ext-auth
 pub(crate) fn serialize_tags_sfv(tags: Vec<String>) -> std::result::Result<String, &'static str> {
     let list: List = tags
         .iter()
@@ -44,6 +48,27 @@ pub(crate) fn serialize_tags_sfv(tags: Vec<String>) -> std::result::Result<Strin
     list.serialize_value()
 }
 
+/// Serialize a [`Verdict`] into the headers to be sent with the request to the interior service.
+///
+/// The tags header is omitted when there are no tags.
+pub(crate) fn serialize_verdict_headers(
+    verdict: &Verdict,
+) -> Result<Vec<(&'static str, String)>, SfvError> {
+    let mut headers = vec![(
+        DECISION_HEADER,
+        serialize_decision_sfv(verdict.decision, verdict.outcome)
+            .map_err(|err| SfvError::Serialization(err.to_string()))?,
+    )];
+    if !verdict.tags.is_empty() {
+        headers.push((
+            TAGS_HEADER,
+            serialize_tags_sfv(verdict.tags.clone())
+                .map_err(|err| SfvError::Serialization(err.to_string()))?,
+        ));
+    }
+    Ok(headers)
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -1,9 +1,12 @@
-//! Provides an [Envoy external processing][1] service for Bulwark.
+//! Provides an [Envoy external processing][1] service for Bulwark, along with an [external authorization][2] service.
 //!
 //! [1]: https://www.envoyproxy.io/docs/envoy/latest/configuration/http/http_filters/ext_proc_filter
+//! [2]: https://www.envoyproxy.io/docs/envoy/latest/configuration/http/http_filters/ext_authz_filter
 
 mod errors;
 mod format;
+mod headers;
+mod router;
 mod service;
 
 pub use errors::*;
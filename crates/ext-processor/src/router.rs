@@ -0,0 +1,450 @@
+//! The router module maps incoming requests to the [`Resource`](bulwark_config::Resource) that should handle them.
+
+use crate::ProcessorInitError;
+use bulwark_config::Resource;
+use matchit::{Params, Router};
+use std::collections::HashMap;
+
+/// The match conditions of a single resource beyond its host and route.
+struct Candidate {
+    methods: Vec<http::Method>,
+    headers: Vec<(http::HeaderName, Option<http::HeaderValue>)>,
+    target: usize,
+}
+
+impl Candidate {
+    /// Parses the match conditions of a resource.
+    ///
+    /// Config validation rejects invalid methods and headers, but resources may also be built directly, so any
+    /// condition that can't be parsed is an error rather than being skipped, which would widen the match.
+    fn new(resource: &Resource, target: usize) -> Result<Self, ProcessorInitError> {
+        let invalid = |condition, value: &str| ProcessorInitError::InvalidCondition {
+            route: resource.route.clone(),
+            condition,
+            value: value.to_string(),
+        };
+        let methods = resource
+            .methods
+            .iter()
+            .map(|method| {
+                http::Method::from_bytes(method.as_bytes()).map_err(|_| invalid("method", method))
+            })
+            .collect::<Result<_, _>>()?;
+        let headers = resource
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let name = http::HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| invalid("header name", name))?;
+                if value == "*" {
+                    Ok((name, None))
+                } else {
+                    let value = http::HeaderValue::from_str(value)
+                        .map_err(|_| invalid("header value", value))?;
+                    Ok((name, Some(value)))
+                }
+            })
+            .collect::<Result<_, ProcessorInitError>>()?;
+        Ok(Self {
+            methods,
+            headers,
+            target,
+        })
+    }
+
+    fn matches<B>(&self, request: &http::Request<B>) -> bool {
+        if !self.methods.is_empty() && !self.methods.contains(request.method()) {
+            return false;
+        }
+        self.headers.iter().all(|(name, expected)| {
+            let mut values = request.headers().get_all(name).iter().peekable();
+            match expected {
+                Some(expected) => values.any(|value| value == expected),
+                None => values.peek().is_some(),
+            }
+        })
+    }
+}
+
+/// A set of routes for a single host selector.
+///
+/// Each route may be shared by several resources, ordered from most to least specific.
+#[derive(Default)]
+struct HostRoutes {
+    routes: Vec<(String, Vec<Candidate>)>,
+    router: Router<Vec<Candidate>>,
+}
+
+impl HostRoutes {
+    fn push(&mut self, route: &str, candidate: Candidate) {
+        match self.routes.iter_mut().find(|(r, _)| r == route) {
+            Some((_, candidates)) => candidates.push(candidate),
+            None => self.routes.push((route.to_string(), vec![candidate])),
+        }
+    }
+
+    fn build(&mut self) -> Result<(), ProcessorInitError> {
+        for (route, mut candidates) in self.routes.drain(..) {
+            // Stable sort, so config order breaks any remaining ties.
+            candidates.sort_by_key(|candidate| {
+                (
+                    candidate.methods.is_empty(),
+                    std::cmp::Reverse(candidate.headers.len()),
+                )
+            });
+            self.router
+                .insert(route.clone(), candidates)
+                .map_err(|source| ProcessorInitError::RouteConflict { route, source })?;
+        }
+        Ok(())
+    }
+
+    fn at<'a, B>(
+        &'a self,
+        path: &'a str,
+        request: &http::Request<B>,
+    ) -> Option<(usize, Params<'a, 'a>)> {
+        let route_match = self.router.at(path).ok()?;
+        let candidate = route_match
+            .value
+            .iter()
+            .find(|candidate| candidate.matches(request))?;
+        Some((candidate.target, route_match.params))
+    }
+}
+
+/// The result of successfully routing a request.
+pub(crate) struct RouteMatch<'a, T> {
+    /// The route target of the matching resource.
+    pub value: &'a T,
+    /// The parameters captured by the matching route pattern.
+    pub params: Params<'a, 'a>,
+}
+
+/// Routes requests to resources based on their host, path, method, and headers.
+///
+/// See [`Resource`] for the priority order used when several resources match the same request.
+pub(crate) struct ResourceRouter<T> {
+    targets: Vec<T>,
+    exact_hosts: HashMap<String, HostRoutes>,
+    wildcard_hosts: Vec<(String, HostRoutes)>,
+    any_host: HostRoutes,
+}
+
+impl<T> ResourceRouter<T> {
+    /// Builds a router from resources paired with the target each one routes to.
+    ///
+    /// Fails if a resource has a match condition that can't be parsed or a route that conflicts with another.
+    pub(crate) fn new(resources: Vec<(&Resource, T)>) -> Result<Self, ProcessorInitError> {
+        let mut targets = Vec::with_capacity(resources.len());
+        let mut exact_hosts: HashMap<String, HostRoutes> = HashMap::new();
+        let mut wildcard_hosts: Vec<(String, HostRoutes)> = Vec::new();
+        let mut any_host = HostRoutes::default();
+        for (index, (resource, target)) in resources.into_iter().enumerate() {
+            targets.push(target);
+            if resource.hosts.is_empty() {
+                any_host.push(&resource.route, Candidate::new(resource, index)?);
+            }
+            for host in &resource.hosts {
+                let host = host.to_ascii_lowercase();
+                let candidate = Candidate::new(resource, index)?;
+                if let Some(domain) = host.strip_prefix('*') {
+                    // The suffix keeps its leading dot so that the bare domain doesn't match.
+                    match wildcard_hosts
+                        .iter_mut()
+                        .find(|(suffix, _)| suffix == domain)
+                    {
+                        Some((_, routes)) => routes.push(&resource.route, candidate),
+                        None => {
+                            let mut routes = HostRoutes::default();
+                            routes.push(&resource.route, candidate);
+                            wildcard_hosts.push((domain.to_string(), routes));
+                        }
+                    }
+                } else {
+                    exact_hosts
+                        .entry(host)
+                        .or_default()
+                        .push(&resource.route, candidate);
+                }
+            }
+        }
+        // Longer domains are more specific, so they get checked first.
+        wildcard_hosts.sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+        for routes in exact_hosts.values_mut() {
+            routes.build()?;
+        }
+        for (_, routes) in wildcard_hosts.iter_mut() {
+            routes.build()?;
+        }
+        any_host.build()?;
+        Ok(Self {
+            targets,
+            exact_hosts,
+            wildcard_hosts,
+            any_host,
+        })
+    }
+
+    /// Finds the most specific resource target for a request, if any.
+    pub(crate) fn at<'a, B>(&'a self, request: &'a http::Request<B>) -> Option<RouteMatch<'a, T>> {
+        let path = request.uri().path();
+        let host = Self::request_host(request);
+        let host_routes = host
+            .as_ref()
+            .and_then(|host| self.exact_hosts.get(host))
+            .into_iter()
+            .chain(
+                self.wildcard_hosts
+                    .iter()
+                    .filter(|(suffix, _)| {
+                        host.as_ref()
+                            .map(|host| host.ends_with(suffix.as_str()))
+                            .unwrap_or(false)
+                    })
+                    .map(|(_, routes)| routes),
+            )
+            .chain(std::iter::once(&self.any_host));
+        for routes in host_routes {
+            if let Some((target, params)) = routes.at(path, request) {
+                return Some(RouteMatch {
+                    value: &self.targets[target],
+                    params,
+                });
+            }
+        }
+        None
+    }
+
+    /// Returns the lowercase hostname of a request, without any port.
+    fn request_host<B>(request: &http::Request<B>) -> Option<String> {
+        let authority = match request.uri().authority() {
+            Some(authority) => authority.host().to_string(),
+            None => {
+                let host = request.headers().get(http::header::HOST)?.to_str().ok()?;
+                host.parse::<http::uri::Authority>()
+                    .ok()?
+                    .host()
+                    .to_string()
+            }
+        };
+        Some(authority.to_ascii_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(
+        route: &str,
+        hosts: &[&str],
+        methods: &[&str],
+        headers: &[(&str, &str)],
+    ) -> Resource {
+        Resource {
+            route: route.to_string(),
+            hosts: hosts.iter().map(|host| host.to_string()).collect(),
+            methods: methods.iter().map(|method| method.to_string()).collect(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            plugins: vec![],
+            overrides: Default::default(),
+            timeout: Default::default(),
+            actions: Default::default(),
+        }
+    }
+
+    fn request(method: &str, uri: &str, headers: &[(&str, &str)]) -> http::Request<()> {
+        let mut request = http::Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(()).unwrap()
+    }
+
+    fn route(
+        router: &ResourceRouter<&'static str>,
+        request: &http::Request<()>,
+    ) -> Option<&'static str> {
+        router.at(request).map(|route_match| *route_match.value)
+    }
+
+    #[test]
+    fn test_host_priority() {
+        let resources = [
+            resource("/login", &[], &[], &[]),
+            resource("/login", &["*.example.com"], &[], &[]),
+            resource("/login", &["*.api.example.com"], &[], &[]),
+            resource("/login", &["www.example.com"], &[], &[]),
+        ];
+        let router = ResourceRouter::new(
+            resources
+                .iter()
+                .zip(["any", "wildcard", "api wildcard", "exact"])
+                .collect(),
+        )
+        .unwrap();
+
+        let cases = [
+            ("https://www.example.com/login", Some("exact")),
+            ("https://WWW.example.com:8443/login", Some("exact")),
+            ("https://shop.example.com/login", Some("wildcard")),
+            ("https://v1.api.example.com/login", Some("api wildcard")),
+            ("https://example.com/login", Some("any")),
+            ("https://example.net/login", Some("any")),
+            ("/login", Some("any")),
+            ("https://www.example.com/logout", None),
+        ];
+        for (uri, expected) in cases {
+            assert_eq!(
+                route(&router, &request("GET", uri, &[])),
+                expected,
+                "{}",
+                uri
+            );
+        }
+
+        // Without an absolute URI, the host header is used instead.
+        assert_eq!(
+            route(
+                &router,
+                &request("GET", "/login", &[("host", "www.example.com:80")])
+            ),
+            Some("exact")
+        );
+    }
+
+    #[test]
+    fn test_host_fallthrough() {
+        let resources = [
+            resource("/*path", &[], &[], &[]),
+            resource("/admin", &["www.example.com"], &["POST"], &[]),
+        ];
+        let router =
+            ResourceRouter::new(resources.iter().zip(["default", "admin"]).collect()).unwrap();
+
+        assert_eq!(
+            route(
+                &router,
+                &request("POST", "https://www.example.com/admin", &[])
+            ),
+            Some("admin")
+        );
+        // The exact host matches the route but not the method, so the next host selector is tried.
+        assert_eq!(
+            route(
+                &router,
+                &request("GET", "https://www.example.com/admin", &[])
+            ),
+            Some("default")
+        );
+        assert_eq!(
+            route(
+                &router,
+                &request("GET", "https://www.example.com/about", &[])
+            ),
+            Some("default")
+        );
+    }
+
+    #[test]
+    fn test_method_and_header_priority() {
+        let resources = [
+            resource("/api", &[], &[], &[]),
+            resource("/api", &[], &[], &[("authorization", "*")]),
+            resource(
+                "/api",
+                &[],
+                &[],
+                &[("authorization", "*"), ("x-api-version", "2")],
+            ),
+            resource("/api", &[], &["POST", "PUT"], &[]),
+        ];
+        let router = ResourceRouter::new(
+            resources
+                .iter()
+                .zip(["plain", "authorized", "authorized v2", "write"])
+                .collect(),
+        )
+        .unwrap();
+
+        assert_eq!(route(&router, &request("GET", "/api", &[])), Some("plain"));
+        assert_eq!(
+            route(
+                &router,
+                &request("GET", "/api", &[("authorization", "Bearer x")])
+            ),
+            Some("authorized")
+        );
+        assert_eq!(
+            route(
+                &router,
+                &request(
+                    "GET",
+                    "/api",
+                    &[("authorization", "Bearer x"), ("x-api-version", "1")]
+                )
+            ),
+            Some("authorized")
+        );
+        assert_eq!(
+            route(
+                &router,
+                &request(
+                    "GET",
+                    "/api",
+                    &[("authorization", "Bearer x"), ("x-api-version", "2")]
+                )
+            ),
+            Some("authorized v2")
+        );
+        // Method restrictions outrank header conditions.
+        assert_eq!(
+            route(
+                &router,
+                &request("PUT", "/api", &[("authorization", "Bearer x")])
+            ),
+            Some("write")
+        );
+    }
+
+    #[test]
+    fn test_route_params() {
+        let resources = [resource("/users/:id", &["example.com"], &[], &[])];
+        let router = ResourceRouter::new(resources.iter().zip([()]).collect()).unwrap();
+        let request = request("GET", "http://example.com/users/42", &[]);
+        let route_match = router.at(&request).unwrap();
+        assert_eq!(route_match.params.get("id"), Some("42"));
+    }
+
+    #[test]
+    fn test_invalid_resources() {
+        let resources = [resource("/api", &[], &[], &[("x-api-version", "2\n")])];
+        assert!(matches!(
+            ResourceRouter::new(resources.iter().zip([()]).collect()),
+            Err(ProcessorInitError::InvalidCondition { route, condition: "header value", .. })
+                if route == "/api"
+        ));
+
+        let resources = [resource("/api", &[], &["GET POST"], &[])];
+        assert!(matches!(
+            ResourceRouter::new(resources.iter().zip([()]).collect()),
+            Err(ProcessorInitError::InvalidCondition {
+                condition: "method",
+                ..
+            })
+        ));
+
+        let resources = [
+            resource("/users/:id", &[], &[], &[]),
+            resource("/users/:name", &[], &[], &[]),
+        ];
+        assert!(matches!(
+            ResourceRouter::new(resources.iter().zip([(), ()]).collect()),
+            Err(ProcessorInitError::RouteConflict { route, .. }) if route == "/users/:name"
+        ));
+    }
+}
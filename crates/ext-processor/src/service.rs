@@ -1,28 +1,35 @@
 //! The service module contains the main Envoy external processor service implementation.
-
-use crate::{PluginGroupInstantiationError, ProcessingMessageError, RequestError, ResponseError};
-use bulwark_config::Config;
+//!
+//! The [`authorization`] submodule allows the same processor to serve Envoy external authorization checks, while the
+//! [`auth_request`] submodule serves plain HTTP authorization subrequests from proxies other than Envoy. The [`decide`]
+//! submodule evaluates requests out-of-band, and the [`capture`] submodule records processed traffic for replay.
+
+use crate::router::ResourceRouter;
+use crate::{
+    HandlerError, PluginGroupInstantiationError, ProcessingMessageError, ProcessorInitError,
+    RequestError, ResponseError,
+};
+use bulwark_config::{Action, Actions, Config};
 use bulwark_sdk::Verdict;
 
 use bulwark_host::{
     ForwardedIP, HandlerOutput, Plugin, PluginCtx, PluginExecutionError, PluginInstance,
-    PluginLoadError, RedisCtx, ScriptRegistry,
+    PluginRegistry, RedisCtx, ScriptRegistry,
 };
 use bulwark_sdk::Decision;
 use envoy_control_plane::envoy::{
-    config::core::v3::HeaderMap,
+    config::core::v3::{HeaderMap, HeaderValue, HeaderValueOption},
     extensions::filters::http::ext_proc::v3::{processing_mode, ProcessingMode},
     r#type::v3::HttpStatus,
     service::ext_proc::v3::{
         external_processor_server::ExternalProcessor, processing_request, processing_response,
-        BodyResponse, CommonResponse, HeadersResponse, HttpBody, HttpHeaders, ImmediateResponse,
-        ProcessingRequest, ProcessingResponse,
+        BodyResponse, CommonResponse, HeaderMutation, HeadersResponse, HttpBody, HttpHeaders,
+        ImmediateResponse, ProcessingRequest, ProcessingResponse,
     },
 };
 use forwarded_header_value::ForwardedHeaderValue;
 use futures::lock::Mutex;
 use futures::{channel::mpsc::UnboundedSender, SinkExt, Stream};
-use matchit::Router;
 use std::{
     collections::{HashMap, HashSet},
     net::IpAddr,
@@ -31,7 +38,11 @@ use std::{
     sync::Arc,
     time::Duration,
 };
-use tokio::{sync::RwLock, sync::Semaphore, task::JoinSet, time::timeout};
+use tokio::{
+    sync::{OwnedSemaphorePermit, RwLock, Semaphore},
+    task::JoinSet,
+    time::timeout,
+};
 use tonic::Streaming;
 use tracing::{debug, error, info, instrument, trace, warn, Instrument};
 
@@ -43,8 +54,18 @@ macro_rules! format_f64 {
 
 extern crate redis;
 
+mod auth_request;
+mod authorization;
+mod capture;
+mod decide;
+
+use capture::CaptureWriter;
+pub use capture::{CaptureRecord, CapturedVerdict};
+pub use decide::{DecideRequest, DecideResponse, DecisionValues, ObservedResponse, PluginOutput};
+
 type ExternalProcessorStream =
     Pin<Box<dyn Stream<Item = Result<ProcessingResponse, tonic::Status>> + Send>>;
+type ProcessingSender = Arc<Mutex<UnboundedSender<Result<ProcessingResponse, tonic::Status>>>>;
 type PluginList = Vec<Arc<Plugin>>;
 
 /// A RouteTarget allows a router to map from a routing pattern to a plugin group and associated config values.
@@ -52,7 +73,37 @@ type PluginList = Vec<Arc<Plugin>>;
 /// See [`bulwark_config::Resource`] for its configuration.
 struct RouteTarget {
     plugins: PluginList,
-    timeout: Option<u64>,
+    /// The resolved time limits for each plugin, in the same order as `plugins`.
+    timeouts: Vec<PhaseTimeouts>,
+    /// The indices of the plugins each plugin depends on during enrichment, in the same order as `plugins`.
+    dependencies: Vec<Vec<usize>>,
+    /// The actions taken in response to each outcome.
+    actions: Actions,
+}
+
+/// The time limits for each execution phase of a single plugin.
+///
+/// See [`bulwark_config::Timeout`] for how these are resolved.
+#[derive(Clone, Copy)]
+struct PhaseTimeouts {
+    init: Duration,
+    enrichment: Duration,
+    decision: Duration,
+    feedback: Duration,
+}
+
+impl From<bulwark_config::Timeout> for PhaseTimeouts {
+    fn from(timeout: bulwark_config::Timeout) -> Self {
+        let duration = |millis: Option<u64>| {
+            Duration::from_millis(millis.unwrap_or(bulwark_config::DEFAULT_TIMEOUT))
+        };
+        Self {
+            init: duration(timeout.init),
+            enrichment: duration(timeout.enrichment),
+            decision: duration(timeout.decision),
+            feedback: duration(timeout.feedback),
+        }
+    }
 }
 
 /// Helper function that joins everything in a joinset, ignoring success and raising warnings as needed
@@ -68,20 +119,77 @@ async fn join_all<T, F>(
 
     while let Some(r) = join_set.join_next().await {
         match r {
-            Ok(Ok(Ok(output))) => success(output),
-            // These 3 errors are only logged, not bubbled up
-            Ok(Ok(Err(err))) => {
-                error!(
-                    message = "plugin execution error",
-                    elapsed = ?err,
-                );
+            Ok(result) => {
+                if let Some(output) = plugin_output(result) {
+                    success(output);
+                }
             }
-            Ok(Err(err)) => {
+            // Join errors are only logged, not bubbled up
+            Err(err) => {
                 warn!(
-                    message = "timeout on plugin execution",
-                    elapsed = ?err,
+                    message = "join error on plugin execution",
+                    error_message = ?err,
                 );
             }
+        }
+    }
+}
+
+/// Helper function that unwraps the output of a plugin task, raising warnings as needed
+fn plugin_output<T>(
+    result: Result<Result<T, PluginExecutionError>, tokio::time::error::Elapsed>,
+) -> Option<T> {
+    match result {
+        Ok(Ok(output)) => Some(output),
+        // These errors are only logged, not bubbled up
+        Ok(Err(err)) => {
+            error!(
+                message = "plugin execution error",
+                elapsed = ?err,
+            );
+            None
+        }
+        Err(err) => {
+            warn!(
+                message = "timeout on plugin execution",
+                elapsed = ?err,
+            );
+            None
+        }
+    }
+}
+
+/// The result of a decision phase task: the labels the plugin emitted and its decision, if that decision was decisive.
+type DecisionTaskResult = Result<
+    Result<(HashMap<String, String>, Option<Decision>), PluginExecutionError>,
+    tokio::time::error::Elapsed,
+>;
+
+/// Joins the decision phase tasks, merging the labels they emit, until all of them have finished or one of them
+/// returns a decisive decision.
+///
+/// Any tasks still running once a decisive decision arrives are cancelled, and the decisive decision is returned.
+async fn join_decision_tasks(
+    mut join_set: JoinSet<DecisionTaskResult>,
+    labels: &mut HashMap<String, String>,
+) -> Option<Decision> {
+    let mut decisive_decision = None;
+    // efficiently hand execution off to the the tasks we're joining
+    tokio::task::yield_now().await;
+
+    while let Some(r) = join_set.join_next().await {
+        match r {
+            Ok(result) => {
+                if let Some((new_labels, decision)) = plugin_output(result) {
+                    // Merge labels from each plugin
+                    labels.extend(new_labels);
+                    if decision.is_some() {
+                        decisive_decision = decision;
+                        break;
+                    }
+                }
+            }
+            // Join errors are only logged, not bubbled up
             Err(err) => {
                 warn!(
                     message = "join error on plugin execution",
@@ -90,20 +198,135 @@ async fn join_all<T, F>(
             }
         }
     }
+    if decisive_decision.is_some() && !join_set.is_empty() {
+        info!(
+            message = "decisive plugin decision, cancelling remaining plugins",
+            cancelled = join_set.len(),
+        );
+        // Waiting on the cancelled tasks ensures their plugin instances are released before the response phase.
+        join_set.shutdown().await;
+    }
+    decisive_decision
+}
+
+/// Runs enrichment for each plugin once every plugin it depends on has finished, returning the merged labels.
+///
+/// Plugins without dependencies receive only the router labels. Plugins with dependencies also receive the labels
+/// their dependencies received and emitted. A dependency that fails or times out still unblocks its dependents, they
+/// just don't receive its labels.
+///
+/// # Arguments
+///
+/// * `dependencies` - The indices of the plugins each plugin depends on.
+/// * `timeouts` - The enrichment time limit of each plugin.
+/// * `router_labels` - The labels extracted from the route.
+/// * `plugin_semaphore` - Limits the number of plugin tasks running at once.
+/// * `dispatch` - Starts enrichment for the plugin at an index with the labels it receives.
+async fn schedule_enrichment<F, Fut>(
+    dependencies: &[Vec<usize>],
+    timeouts: &[Duration],
+    router_labels: &HashMap<String, String>,
+    plugin_semaphore: &Arc<Semaphore>,
+    mut dispatch: F,
+) -> HashMap<String, String>
+where
+    F: FnMut(usize, HashMap<String, String>) -> Fut,
+    Fut: std::future::Future<Output = Result<HashMap<String, String>, PluginExecutionError>>
+        + Send
+        + 'static,
+{
+    let plugin_count = dependencies.len();
+    let mut enrichment_phase_tasks = JoinSet::new();
+    let mut started = vec![false; plugin_count];
+    let mut finished = vec![false; plugin_count];
+    let mut inputs: Vec<HashMap<String, String>> = vec![HashMap::new(); plugin_count];
+    let mut outputs: Vec<HashMap<String, String>> = vec![HashMap::new(); plugin_count];
+    let mut labels = router_labels.clone();
+    loop {
+        for index in 0..plugin_count {
+            if started[index]
+                || !dependencies[index]
+                    .iter()
+                    .all(|dependency| finished[*dependency])
+            {
+                continue;
+            }
+            started[index] = true;
+
+            let mut plugin_labels = router_labels.clone();
+            for dependency in &dependencies[index] {
+                plugin_labels.extend(inputs[*dependency].clone());
+                plugin_labels.extend(outputs[*dependency].clone());
+            }
+            inputs[index] = plugin_labels.clone();
+
+            let enrichment_phase_child_span =
+                tracing::info_span!("execute handle_request_enrichment",);
+            let permit = plugin_semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("semaphore closed");
+            let enrichment = dispatch(index, plugin_labels);
+            let enrichment_timeout = timeouts[index];
+            enrichment_phase_tasks.spawn(
+                async move {
+                    let result = timeout(enrichment_timeout, enrichment).await;
+                    drop(permit);
+                    (index, result)
+                }
+                .instrument(enrichment_phase_child_span.or_current()),
+            );
+        }
+
+        // efficiently hand execution off to the the tasks we're joining
+        tokio::task::yield_now().await;
+
+        match enrichment_phase_tasks.join_next().await {
+            Some(Ok((index, result))) => {
+                finished[index] = true;
+                if let Some(new_labels) = plugin_output(result) {
+                    // Merge labels from each plugin
+                    labels.extend(new_labels.clone());
+                    outputs[index] = new_labels;
+                }
+            }
+            Some(Err(err)) => {
+                warn!(
+                    message = "join error on plugin execution",
+                    error_message = ?err,
+                );
+            }
+            None => {
+                if started.iter().all(|started| *started) {
+                    break;
+                }
+                // A join error means some task never reported back, so unblock anything waiting on it.
+                for (finished, started) in finished.iter_mut().zip(&started) {
+                    *finished |= *started;
+                }
+            }
+        }
+    }
+    labels
 }
 
 /// The `BulwarkProcessor` implements the primary envoy processing service logic via the [`ExternalProcessor`] trait.
 ///
-/// The [`process`](BulwarkProcessor::process) function is the main request handler.
+/// The [`process`](BulwarkProcessor::process) function is the main request handler. The processor also implements
+/// the [`Authorization`](envoy_control_plane::envoy::service::auth::v3::authorization_server::Authorization) trait
+/// for use with Envoy's external authorization filter, and handles auth request subrequests from other proxies via
+/// [`handle_auth_request`](BulwarkProcessor::handle_auth_request). Response phases are unsupported in both cases.
 #[derive(Clone)]
 pub struct BulwarkProcessor {
     // TODO: may need to have a plugin registry at some point
-    router: Arc<RwLock<Router<RouteTarget>>>,
+    router: Arc<RwLock<ResourceRouter<RouteTarget>>>,
     redis_ctx: RedisCtx,
     request_semaphore: Arc<tokio::sync::Semaphore>,
     plugin_semaphore: Arc<tokio::sync::Semaphore>,
     thresholds: bulwark_config::Thresholds,
     proxy_hops: usize,
+    capture: Option<Arc<CaptureWriter>>,
     // TODO: redis circuit breaker for health monitoring
 }
 
@@ -157,12 +380,9 @@ impl ExternalProcessor for BulwarkProcessor {
                     );
 
                     let router = bulwark_processor.router.read().await;
-                    let route_result = router.at(request.uri().path());
-                    // TODO: router needs to point to a struct that bundles the plugin set and associated config like timeout duration
-                    // TODO: put default timeout in a constant somewhere central
-                    let mut timeout_duration = Duration::from_millis(10);
+                    let route_result = router.at(&request);
                     match route_result {
-                        Ok(route_match) => {
+                        Some(route_match) => {
                             // TODO: may want to expose labels to logging after redaction
                             let mut router_labels = HashMap::new();
                             for (key, value) in route_match.params.iter() {
@@ -177,15 +397,14 @@ impl ExternalProcessor for BulwarkProcessor {
                                 .instantiate_plugins(&route_target.plugins)
                                 .await
                                 .unwrap();
-                            if let Some(millis) = route_match.value.timeout {
-                                timeout_duration = Duration::from_millis(millis);
-                            }
 
                             let mut ctx = ProcessorContext {
-                                sender: arc_sender,
-                                stream: arc_stream,
+                                sender: Some(arc_sender),
+                                stream: Some(arc_stream),
                                 plugin_semaphore,
                                 plugin_instances: plugin_instances.clone(),
+                                plugin_timeouts: route_target.timeouts.clone(),
+                                plugin_dependencies: route_target.dependencies.clone(),
                                 router_labels,
                                 request: request.clone(),
                                 response: None,
@@ -193,8 +412,13 @@ impl ExternalProcessor for BulwarkProcessor {
                                 combined_output: HandlerOutput::default(),
                                 plugin_outputs: HashMap::new(),
                                 thresholds,
-                                timeout_duration,
+                                actions: route_target.actions.clone(),
+                                capture: bulwark_processor.capture.clone(),
+                                request_permit: Some(permit),
+                                tarpitted: false,
                             };
+                            // The router is only needed for routing, holding it any longer would block reloads.
+                            drop(router);
 
                             ctx.execute_init_phase().await;
 
@@ -203,7 +427,7 @@ impl ExternalProcessor for BulwarkProcessor {
 
                             ctx.complete_request_phase().await;
                         }
-                        Err(_) => {
+                        None => {
                             // TODO: figure out how best to handle trailing slash errors, silent failure is probably undesirable
                             error!(uri = request.uri().to_string(), message = "match error");
                             // TODO: panic is undesirable, need to figure out if we should be returning a Status or changing the response or doing something else
@@ -211,7 +435,6 @@ impl ExternalProcessor for BulwarkProcessor {
                         }
                     };
                 }
-                drop(permit);
             }
             .instrument(child_span.or_current()),
         );
@@ -228,7 +451,7 @@ impl BulwarkProcessor {
     /// # Arguments
     ///
     /// * `config` - The root of the Bulwark configuration structure to be used to initialize the service.
-    pub async fn new(config: Config) -> Result<Self, PluginLoadError> {
+    pub async fn new(config: Config) -> Result<Self, ProcessorInitError> {
         // Get all outcomes registered even if those outcomes don't happen immediately.
         metrics::register_counter!(
             "combined_decision",
@@ -269,45 +492,132 @@ impl BulwarkProcessor {
             registry: Arc::new(ScriptRegistry::default()),
         };
 
-        let mut router: Router<RouteTarget> = Router::new();
         if config.resources.is_empty() {
-            // TODO: return an init error not a plugin load error
-            return Err(PluginLoadError::ResourceMissing);
+            return Err(ProcessorInitError::ResourceMissing);
         }
+        let mut route_targets = Vec::with_capacity(config.resources.len());
+        // Resources that run the same plugin share its limits.
+        let plugin_registry = PluginRegistry::new();
         for resource in &config.resources {
-            let plugin_configs = resource.resolve_plugins(&config)?;
+            let plugin_configs = resource.resolve_plugins_with_overrides(&config)?;
             let mut plugins: PluginList = Vec::with_capacity(plugin_configs.len());
-            for plugin_config in plugin_configs {
+            let mut timeouts = Vec::with_capacity(plugin_configs.len());
+            let dependencies = plugin_configs
+                .iter()
+                .map(|plugin_config| {
+                    // Dependencies that this resource doesn't run are ignored.
+                    plugin_config
+                        .after
+                        .iter()
+                        .filter_map(|reference| {
+                            plugin_configs
+                                .iter()
+                                .position(|dependency| dependency.reference == *reference)
+                        })
+                        .collect()
+                })
+                .collect();
+            for plugin_config in &plugin_configs {
                 // TODO: pass in the plugin config
                 debug!(
                     message = "load plugin",
-                    path = plugin_config.path,
+                    // Embedded plugins would otherwise dump their entire base64 payload into the log.
+                    path = if plugin_config.path.starts_with("data:") {
+                        "data:"
+                    } else {
+                        plugin_config.path.as_str()
+                    },
                     resource = resource.route
                 );
-                let plugin = Plugin::from_file(plugin_config.path.clone(), &config, plugin_config)?;
+                let plugin = plugin_registry.load(&config, plugin_config)?;
                 plugins.push(Arc::new(plugin));
+                timeouts.push(PhaseTimeouts::from(
+                    plugin_config
+                        .timeout
+                        .or(resource.timeout)
+                        .or(config.runtime.default_timeout),
+                ));
             }
-            router
-                .insert(
-                    resource.route.clone(),
-                    // TODO: the route target will probably need access to the route itself in the future
-                    RouteTarget {
-                        timeout: resource.timeout,
-                        plugins,
-                    },
-                )
-                .ok();
+            route_targets.push((
+                resource,
+                // TODO: the route target will probably need access to the route itself in the future
+                RouteTarget {
+                    plugins,
+                    timeouts,
+                    dependencies,
+                    actions: resource.actions.clone(),
+                },
+            ));
         }
+        let capture = match config.capture.path.as_ref() {
+            Some(path) => Some(Arc::new(
+                CaptureWriter::open(path, &config.capture).map_err(ProcessorInitError::Capture)?,
+            )),
+            None => None,
+        };
         Ok(Self {
-            router: Arc::new(RwLock::new(router)),
+            router: Arc::new(RwLock::new(ResourceRouter::new(route_targets)?)),
             request_semaphore: Arc::new(Semaphore::new(config.runtime.max_concurrent_requests)),
             plugin_semaphore: Arc::new(Semaphore::new(config.runtime.max_plugin_tasks)),
             thresholds: config.thresholds,
             proxy_hops: usize::from(config.service.proxy_hops),
+            capture,
             redis_ctx,
         })
     }
 
+    /// Routes a request and runs every plugin phase up to and including the request decision phase.
+    ///
+    /// Used by the services that only ever see the request, so the returned context has no processing stream. The
+    /// request's concurrency permit is held by the returned context.
+    async fn execute_request_phases(
+        &self,
+        request: Arc<bulwark_sdk::Request>,
+        permit: OwnedSemaphorePermit,
+    ) -> Result<ProcessorContext, HandlerError> {
+        let mut ctx = {
+            let router = self.router.read().await;
+            let route_match = router.at(&request).ok_or_else(|| {
+                error!(uri = request.uri().to_string(), message = "match error");
+                HandlerError::RouteMatch(matchit::MatchError::NotFound)
+            })?;
+            let mut router_labels = HashMap::new();
+            for (key, value) in route_match.params.iter() {
+                router_labels.insert(format!("route.{}", key), value.to_string());
+            }
+
+            let route_target = route_match.value;
+            let plugin_instances = self.instantiate_plugins(&route_target.plugins).await?;
+
+            ProcessorContext {
+                sender: None,
+                stream: None,
+                plugin_semaphore: self.plugin_semaphore.clone(),
+                plugin_instances,
+                plugin_timeouts: route_target.timeouts.clone(),
+                plugin_dependencies: route_target.dependencies.clone(),
+                router_labels,
+                request: request.clone(),
+                response: None,
+                verdict: None,
+                combined_output: HandlerOutput::default(),
+                plugin_outputs: HashMap::new(),
+                thresholds: self.thresholds,
+                actions: route_target.actions.clone(),
+                capture: self.capture.clone(),
+                request_permit: Some(permit),
+                tarpitted: false,
+            }
+        };
+
+        ctx.execute_init_phase().await;
+
+        ctx.execute_request_enrichment_phase().await;
+        ctx.execute_request_decision_phase().await;
+
+        Ok(ctx)
+    }
+
     async fn instantiate_plugins(
         &self,
         plugins: &PluginList,
@@ -456,10 +766,13 @@ impl BulwarkProcessor {
 
 /// The `ProcessorContext` wraps values associated with a single request/response cycle.
 struct ProcessorContext {
-    sender: Arc<Mutex<UnboundedSender<Result<ProcessingResponse, tonic::Status>>>>,
-    stream: Arc<Mutex<Streaming<ProcessingRequest>>>,
+    /// The external processor stream, absent for requests received by the external authorization service.
+    sender: Option<ProcessingSender>,
+    stream: Option<Arc<Mutex<Streaming<ProcessingRequest>>>>,
     plugin_semaphore: Arc<tokio::sync::Semaphore>,
     plugin_instances: Vec<Arc<Mutex<PluginInstance>>>,
+    plugin_timeouts: Vec<PhaseTimeouts>,
+    plugin_dependencies: Vec<Vec<usize>>,
     router_labels: HashMap<String, String>,
     request: Arc<bulwark_sdk::Request>,
     response: Option<Arc<bulwark_sdk::Response>>,
@@ -467,7 +780,13 @@ struct ProcessorContext {
     combined_output: HandlerOutput,
     plugin_outputs: HashMap<String, HandlerOutput>,
     thresholds: bulwark_config::Thresholds,
-    timeout_duration: Duration,
+    actions: Actions,
+    /// The capture file writer, absent if capture is disabled.
+    capture: Option<Arc<CaptureWriter>>,
+    /// Counts this request against the concurrent request limit, released early if the request is tarpitted.
+    request_permit: Option<OwnedSemaphorePermit>,
+    /// True once the request has been tarpitted, so that it's never delayed twice.
+    tarpitted: bool,
 }
 
 impl ProcessorContext {
@@ -489,20 +808,24 @@ impl ProcessorContext {
                 None
             };
 
-            // TODO: currently this information isn't used and isn't accessible to the plugin environment yet
-            // TODO: does this go into a request extension?
             // TODO: :protocol?
-            let _authority = Self::get_header_value(&header_msg.headers, ":authority")
+            let authority = Self::get_header_value(&header_msg.headers, ":authority")
                 .ok_or(RequestError::MissingAuthority)?;
-            let _scheme = Self::get_header_value(&header_msg.headers, ":scheme")
+            let scheme = Self::get_header_value(&header_msg.headers, ":scheme")
                 .ok_or(RequestError::MissingScheme)?;
 
             let method = http::Method::from_str(
                 Self::get_header_value(&header_msg.headers, ":method")
                     .ok_or(RequestError::MissingMethod)?,
             )?;
-            let request_uri = Self::get_header_value(&header_msg.headers, ":path")
+            let path = Self::get_header_value(&header_msg.headers, ":path")
                 .ok_or(RequestError::MissingPath)?;
+            // An absolute URI makes the host available for routing and to plugins.
+            let request_uri = http::Uri::builder()
+                .scheme(scheme)
+                .authority(authority)
+                .path_and_query(path)
+                .build()?;
             let mut request = http::Request::builder();
             let request_chunk = if let Some(body) = body {
                 bytes::Bytes::from(body)
@@ -542,14 +865,26 @@ impl ProcessorContext {
         Err(RequestError::MissingHeaders)
     }
 
+    fn sender(&self) -> ProcessingSender {
+        self.sender
+            .clone()
+            .expect("request was not received on an external processor stream")
+    }
+
+    fn stream(&self) -> Arc<Mutex<Streaming<ProcessingRequest>>> {
+        self.stream
+            .clone()
+            .expect("request was not received on an external processor stream")
+    }
+
     async fn prepare_response(&mut self) -> Result<bulwark_sdk::Response, ResponseError> {
-        if let Some(header_msg) = Self::get_response_headers_message(self.stream.clone()).await? {
+        if let Some(header_msg) = Self::get_response_headers_message(self.stream()).await? {
             // If there is no body, we have to skip these to avoid Envoy errors.
             let body = if !header_msg.end_of_stream {
                 // We have to send a reply back before we can retrieve the response body
-                Self::send_response_headers_message(self.sender.clone()).await?;
+                Self::send_response_headers_message(self.sender()).await?;
 
-                Self::get_response_body_message(self.stream.clone())
+                Self::get_response_body_message(self.stream())
                     .await?
                     .map(|body_msg| body_msg.body)
             } else {
@@ -584,7 +919,12 @@ impl ProcessorContext {
 
     async fn execute_init_phase(&self) {
         let mut init_phase_tasks = JoinSet::new();
-        for plugin_instance in self.plugin_instances.iter().cloned() {
+        for (plugin_instance, plugin_timeouts) in self
+            .plugin_instances
+            .iter()
+            .cloned()
+            .zip(self.plugin_timeouts.iter().copied())
+        {
             let init_phase_child_span = tracing::info_span!("execute handle_init",);
             let permit = self
                 .plugin_semaphore
@@ -593,7 +933,7 @@ impl ProcessorContext {
                 .await
                 .expect("semaphore closed");
             init_phase_tasks.spawn(
-                timeout(self.timeout_duration, async move {
+                timeout(plugin_timeouts.init, async move {
                     let result = BulwarkProcessor::dispatch_init(plugin_instance).await;
                     drop(permit);
                     result
@@ -604,39 +944,28 @@ impl ProcessorContext {
         join_all(init_phase_tasks, |_| {}).await;
     }
 
+    /// Runs the enrichment phase, starting each plugin once every plugin it depends on has finished.
+    ///
+    /// See [`schedule_enrichment`] for the labels each plugin receives.
     async fn execute_request_enrichment_phase(&mut self) {
-        let mut enrichment_phase_tasks = JoinSet::new();
-        for plugin_instance in self.plugin_instances.iter().cloned() {
-            let enrichment_phase_child_span =
-                tracing::info_span!("execute handle_request_enrichment",);
-            let permit = self
-                .plugin_semaphore
-                .clone()
-                .acquire_owned()
-                .await
-                .expect("semaphore closed");
-            let request = self.request.clone();
-            let router_labels = self.router_labels.clone();
-            enrichment_phase_tasks.spawn(
-                timeout(self.timeout_duration, async move {
-                    let result = BulwarkProcessor::dispatch_request_enrichment(
-                        plugin_instance,
-                        request,
-                        router_labels,
-                    )
-                    .await;
-                    drop(permit);
-                    result
-                })
-                .instrument(enrichment_phase_child_span.or_current()),
-            );
-        }
-
-        let mut labels = self.router_labels.clone();
-        join_all(enrichment_phase_tasks, |new_labels| {
-            // Merge labels from each plugin
-            labels.extend(new_labels);
-        })
+        let timeouts: Vec<Duration> = self
+            .plugin_timeouts
+            .iter()
+            .map(|plugin_timeouts| plugin_timeouts.enrichment)
+            .collect();
+        let labels = schedule_enrichment(
+            &self.plugin_dependencies,
+            &timeouts,
+            &self.router_labels,
+            &self.plugin_semaphore,
+            |index, plugin_labels| {
+                BulwarkProcessor::dispatch_request_enrichment(
+                    self.plugin_instances[index].clone(),
+                    self.request.clone(),
+                    plugin_labels,
+                )
+            },
+        )
         .await;
         self.combined_output = HandlerOutput {
             decision: Decision::default(),
@@ -645,6 +974,10 @@ impl ProcessorContext {
         };
     }
 
+    /// Runs the decision phase, combining the decisions of every plugin.
+    ///
+    /// If a decisive plugin returns a decision that reaches the decisive threshold, any plugins that are still
+    /// running are cancelled and the decisive plugin's decision is used in place of the combined decision.
     async fn execute_request_decision_phase(&mut self) {
         let outputs: Arc<Mutex<Vec<HandlerOutput>>> =
             Arc::new(Mutex::new(Vec::with_capacity(self.plugin_instances.len())));
@@ -653,7 +986,12 @@ impl ProcessorContext {
         let mut decision_phase_tasks = JoinSet::new();
         // The .iter().cloned() appears to be necessary
         #[allow(clippy::unnecessary_to_owned)]
-        for plugin_instance in self.plugin_instances.iter().cloned() {
+        for (plugin_instance, plugin_timeouts) in self
+            .plugin_instances
+            .iter()
+            .cloned()
+            .zip(self.plugin_timeouts.iter().copied())
+        {
             let decision_phase_child_span = tracing::info_span!("execute handle_request_decision",);
             let permit = self
                 .plugin_semaphore
@@ -666,14 +1004,16 @@ impl ProcessorContext {
             let request = self.request.clone();
             // Need to be careful that we grab the labels emitted by the request phase and not the labels we started with.
             let labels = self.combined_output.labels.clone();
+            let decisive_threshold = self.thresholds.decisive;
             decision_phase_tasks.spawn(
-                timeout(self.timeout_duration, async move {
+                timeout(plugin_timeouts.decision, async move {
                     let output_result = BulwarkProcessor::dispatch_request_decision(
                         plugin_instance.clone(),
                         request,
                         labels,
                     )
                     .await;
+                    let mut decisive_decision = None;
                     if let Ok(output) = &output_result {
                         // Re-weight the decision based on its weighting value from the configuration
                         let plugin_instance = plugin_instance.lock().await;
@@ -691,6 +1031,13 @@ impl ProcessorContext {
                             weight = format_f64!(plugin_instance.weight()),
                         );
 
+                        if plugin_instance.decisive()
+                            && (decision.accept >= decisive_threshold
+                                || decision.restrict >= decisive_threshold)
+                        {
+                            decisive_decision = Some(*decision);
+                        }
+
                         let mut outputs = outputs.lock().await;
                         outputs.push(output.clone());
                         let mut plugin_outputs = plugin_outputs.lock().await;
@@ -705,18 +1052,14 @@ impl ProcessorContext {
                         });
                     }
                     drop(permit);
-                    output_result.map(|output| output.labels)
+                    output_result.map(|output| (output.labels, decisive_decision))
                 })
                 .instrument(decision_phase_child_span.or_current()),
             );
         }
 
         let mut labels = self.router_labels.clone();
-        join_all(decision_phase_tasks, |new_labels| {
-            // Merge labels from each plugin
-            labels.extend(new_labels);
-        })
-        .await;
+        let decisive_decision = join_decision_tasks(decision_phase_tasks, &mut labels).await;
 
         let decision_vec: Vec<Decision>;
         {
@@ -729,7 +1072,7 @@ impl ProcessorContext {
                     .collect::<HashSet<String>>(),
             );
         }
-        let decision = Decision::combine_murphy(&decision_vec);
+        let decision = decisive_decision.unwrap_or_else(|| Decision::combine_murphy(&decision_vec));
 
         let plugin_outputs = plugin_outputs.lock().await;
         self.combined_output = HandlerOutput {
@@ -748,7 +1091,12 @@ impl ProcessorContext {
         let mut response_phase_tasks = JoinSet::new();
         // The .iter().cloned() appears to be necessary
         #[allow(clippy::unnecessary_to_owned)]
-        for plugin_instance in self.plugin_instances.iter().cloned() {
+        for (plugin_instance, plugin_timeouts) in self
+            .plugin_instances
+            .iter()
+            .cloned()
+            .zip(self.plugin_timeouts.iter().copied())
+        {
             let response_phase_child_span =
                 tracing::info_span!("execute handle_response_decision",);
             let permit = self
@@ -771,7 +1119,7 @@ impl ProcessorContext {
                 .get(&plugin_instance.lock().await.plugin_reference())
                 .cloned();
             response_phase_tasks.spawn(
-                timeout(self.timeout_duration, async move {
+                timeout(plugin_timeouts.decision, async move {
                     let output_result = BulwarkProcessor::dispatch_response_decision(
                         plugin_instance.clone(),
                         request,
@@ -871,7 +1219,12 @@ impl ProcessorContext {
 
         let mut decisions: Vec<Decision> = Vec::with_capacity(self.plugin_instances.len());
         let mut feedback_phase_tasks = JoinSet::new();
-        for plugin_instance in self.plugin_instances.iter().cloned() {
+        for (plugin_instance, plugin_timeouts) in self
+            .plugin_instances
+            .iter()
+            .cloned()
+            .zip(self.plugin_timeouts.iter().copied())
+        {
             let response_phase_child_span =
                 tracing::info_span!("execute handle_decision_feedback",);
             let permit = self
@@ -917,7 +1270,7 @@ impl ProcessorContext {
             let labels = self.combined_output.labels.clone();
             let verdict = verdict.clone();
             feedback_phase_tasks.spawn(
-                timeout(self.timeout_duration, async move {
+                timeout(plugin_timeouts.feedback, async move {
                     let result = BulwarkProcessor::dispatch_decision_feedback(
                         plugin_instance,
                         request,
@@ -950,6 +1303,7 @@ impl ProcessorContext {
                 self.thresholds.restrict,
             )
             .unwrap();
+        let action = self.actions.action(outcome).clone();
 
         info!(
             message = "combine decision",
@@ -958,6 +1312,7 @@ impl ProcessorContext {
             unknown = format_f64!(decision.unknown),
             score = format_f64!(decision.pignistic().restrict),
             outcome = outcome.to_string(),
+            action = action.to_string(),
             observe_only = self.thresholds.observe_only,
             // array values aren't handled well unfortunately, coercing to comma-separated values seems to be the best option
             tags = self
@@ -975,28 +1330,40 @@ impl ProcessorContext {
             "observe_only" => self.thresholds.observe_only.to_string(),
         );
 
-        let mut restricted = false;
+        let mut intercepted = false;
         let end_of_stream = self.request.body().is_empty();
-        match outcome {
-            bulwark_sdk::Outcome::Trusted
-            | bulwark_sdk::Outcome::Accepted
-            // suspected requests are monitored but not rejected
-            | bulwark_sdk::Outcome::Suspected => {
-                let result = Self::send_allow_request_message(self.sender.clone(), end_of_stream).await;
+        match action {
+            Action::Allow | Action::Tarpit { .. } => {
+                if let Action::Tarpit { delay } = action {
+                    self.tarpit(delay).await;
+                }
+                let result = Self::send_allow_request_message(self.sender(), end_of_stream).await;
                 // TODO: must perform proper error handling on sender results, sending can fail
                 if let Err(err) = result {
                     error!(message = format!("send error: {}", err));
                 }
-            },
-            bulwark_sdk::Outcome::Restricted => {
-                restricted = true;
+            }
+            Action::Block | Action::Redirect { .. } | Action::RateLimit { .. } | Action::Drop => {
+                intercepted = true;
+                // The generate function should be infallible here since the action was validated with the config.
+                let response = Self::generate_action_response(&action)
+                    .expect("could not generate action response");
                 if !self.thresholds.observe_only {
-                    info!(message = "process response", status = 403);
-                    match Self::send_block_request_message(self.sender.clone()).await {
-                        Ok(response) => {
-                            // Normally we initiate feedback after the response phase, but if we're blocking the request
-                            // in the request phase, we're also skipping the response phase and we need to do it here
-                            // instead.
+                    info!(
+                        message = "process response",
+                        status = u16::from(response.status())
+                    );
+                    let result = if action == Action::Drop {
+                        Self::send_drop_message(self.sender()).await
+                    } else {
+                        Self::send_immediate_response_message(self.sender(), &action, &response)
+                            .await
+                    };
+                    match result {
+                        Ok(()) => {
+                            // Normally we initiate feedback after the response phase, but if we're intercepting the
+                            // request in the request phase, we're also skipping the response phase and we need to do
+                            // it here instead.
                             let verdict = Verdict {
                                 decision,
                                 outcome,
@@ -1005,14 +1372,15 @@ impl ProcessorContext {
                             self.response = Some(Arc::new(response));
                             self.verdict = Some(verdict);
                             self.execute_decision_feedback().await;
-                        },
+                        }
                         Err(err) => {
                             // TODO: must perform proper error handling on sender results, sending can fail
                             error!(message = format!("send error: {}", err));
-                        },
+                        }
                     }
+                    self.capture_traffic(false).await;
 
-                    // Short-circuit if restricted, we can skip the response phase
+                    // Short-circuit if intercepted, we can skip the response phase
                     return;
                 } else {
                     // In observe-only mode, we still perform decision feedback, but there won't be a response.
@@ -1024,29 +1392,25 @@ impl ProcessorContext {
                     self.verdict = Some(verdict);
 
                     // We need to set a response or decision feedback will panic.
-                    // This response is what would have been sent if we had blocked, rather than what will actually
-                    // be sent, since we're about to call send_allow_request_message and that instructs envoy that
-                    // the processor no longer needs to continue processing the request or response.
-                    let code: i32 = 403;
-                    let body = "Access Denied\n";
-                    // The generate function should be infallible here.
-                    let response = Self::generate_block_response(code, body).expect("could not generate block response");
+                    // This response is what would have been sent if we had intercepted the request, rather than what
+                    // will actually be sent, since we're about to call send_allow_request_message and that instructs
+                    // envoy that the processor no longer needs to continue processing the request or response.
                     self.response = Some(Arc::new(response));
 
                     self.execute_decision_feedback().await;
                 }
 
-                let result = Self::send_allow_request_message(self.sender.clone(), end_of_stream).await;
+                let result = Self::send_allow_request_message(self.sender(), end_of_stream).await;
                 // TODO: must perform proper error handling on sender results, sending can fail
                 if let Err(err) = result {
                     error!(message = format!("send error: {}", err));
                 }
-            },
+            }
         }
 
-        // There's only a response phase if we haven't blocked.
+        // There's only a response phase if we haven't intercepted the request.
         // Observe-only mode should also behave the same way as normal mode here.
-        if !restricted {
+        if !intercepted {
             match self.prepare_response().await {
                 Ok(response) => {
                     self.response = Some(Arc::new(response));
@@ -1057,6 +1421,8 @@ impl ProcessorContext {
                     error!(message = format!("response error: {}", err));
                 }
             }
+        } else {
+            self.capture_traffic(false).await;
         }
     }
 
@@ -1069,6 +1435,7 @@ impl ProcessorContext {
                 self.thresholds.restrict,
             )
             .unwrap();
+        let action = self.actions.action(outcome).clone();
 
         info!(
             message = "combine decision",
@@ -1077,6 +1444,7 @@ impl ProcessorContext {
             unknown = format_f64!(decision.unknown),
             score = format_f64!(decision.pignistic().restrict),
             outcome = outcome.to_string(),
+            action = action.to_string(),
             observe_only = self.thresholds.observe_only,
             // array values aren't handled well unfortunately, coercing to comma-separated values seems to be the best option
             tags = self
@@ -1099,30 +1467,48 @@ impl ProcessorContext {
             .clone()
             .expect("cannot complete response phase without response");
         let end_of_stream = response.body().is_empty();
-        match outcome {
-            bulwark_sdk::Outcome::Trusted
-            | bulwark_sdk::Outcome::Accepted
-            // suspected requests are monitored but not rejected
-            | bulwark_sdk::Outcome::Suspected => {
-                info!(message = "process response", status = u16::from(response.status()));
-                let result = Self::send_allow_response_message(self.sender.clone(), end_of_stream).await;
+        match action {
+            Action::Allow | Action::Tarpit { .. } => {
+                if let Action::Tarpit { delay } = action {
+                    self.tarpit(delay).await;
+                }
+                info!(
+                    message = "process response",
+                    status = u16::from(response.status())
+                );
+                let result = Self::send_allow_response_message(self.sender(), end_of_stream).await;
                 // TODO: must perform proper error handling on sender results, sending can fail
                 if let Err(err) = result {
                     error!(message = format!("send error: {}", err));
                 }
-            },
-            bulwark_sdk::Outcome::Restricted => {
+            }
+            Action::Block | Action::Redirect { .. } | Action::RateLimit { .. } | Action::Drop => {
                 if !self.thresholds.observe_only {
-                    info!(message = "process response", status = 403);
-                    let result = Self::send_block_response_message(self.sender.clone()).await;
+                    // The generate function should be infallible here since the action was validated with the config.
+                    let replacement = Self::generate_action_response(&action)
+                        .expect("could not generate action response");
+                    info!(
+                        message = "process response",
+                        status = u16::from(replacement.status())
+                    );
+                    let result = if action == Action::Drop {
+                        Self::send_drop_message(self.sender()).await
+                    } else {
+                        Self::send_immediate_response_message(self.sender(), &action, &replacement)
+                            .await
+                    };
                     // TODO: must perform proper error handling on sender results, sending can fail
                     if let Err(err) = result {
                         error!(message = format!("send error: {}", err));
                     }
                 } else {
-                    info!(message = "process response", status = u16::from(response.status()));
-                    // Don't receive a body when we would have otherwise blocked if we weren't in monitor-only mode
-                    let result = Self::send_allow_response_message(self.sender.clone(), end_of_stream).await;
+                    info!(
+                        message = "process response",
+                        status = u16::from(response.status())
+                    );
+                    // Don't receive a body when we would have otherwise intercepted if we weren't in monitor-only mode
+                    let result =
+                        Self::send_allow_response_message(self.sender(), end_of_stream).await;
                     // TODO: must perform proper error handling on sender results, sending can fail
                     if let Err(err) = result {
                         error!(message = format!("send error: {}", err));
@@ -1138,6 +1524,102 @@ impl ProcessorContext {
         };
         self.verdict = Some(verdict);
         self.execute_decision_feedback().await;
+        self.capture_traffic(true).await;
+    }
+
+    /// Completes the request decision for services that never see the response, returning the action that
+    /// intercepts the request, if any.
+    ///
+    /// Intercepted requests receive decision feedback using the response generated for their action, which is then
+    /// also the context's response. Allowed requests have no response to give feedback on. In observe-only mode,
+    /// feedback is still performed for requests that would have been intercepted, but no action is returned.
+    async fn complete_request_only_phase(
+        &mut self,
+        generate_response: fn(&Action) -> Result<bulwark_sdk::Response, http::Error>,
+    ) -> Option<Action> {
+        let decision = self.combined_output.decision;
+        let outcome = decision
+            .outcome(
+                self.thresholds.trust,
+                self.thresholds.suspicious,
+                self.thresholds.restrict,
+            )
+            .unwrap();
+        let action = self.actions.action(outcome).clone();
+
+        info!(
+            message = "combine decision",
+            accept = format_f64!(decision.accept),
+            restrict = format_f64!(decision.restrict),
+            unknown = format_f64!(decision.unknown),
+            score = format_f64!(decision.pignistic().restrict),
+            outcome = outcome.to_string(),
+            action = action.to_string(),
+            observe_only = self.thresholds.observe_only,
+            // array values aren't handled well unfortunately, coercing to comma-separated values seems to be the best option
+            tags = self
+                .combined_output
+                .tags
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<&str>>()
+                .join(","),
+        );
+        metrics::increment_counter!(
+            "plugin_request_phase_decision",
+            "outcome" => outcome.to_string(),
+            "observe_only" => self.thresholds.observe_only.to_string(),
+        );
+
+        let mut tags: Vec<String> = self.combined_output.tags.iter().cloned().collect();
+        tags.sort();
+        self.verdict = Some(Verdict {
+            decision,
+            outcome,
+            tags,
+        });
+        self.capture_traffic(false).await;
+
+        match action {
+            Action::Allow | Action::Tarpit { .. } => {
+                if let Action::Tarpit { delay } = action {
+                    self.tarpit(delay).await;
+                }
+                // Without a response there's no decision feedback, but plugin output still needs to be logged.
+                self.capture_stdio().await;
+                None
+            }
+            Action::Block | Action::Redirect { .. } | Action::RateLimit { .. } | Action::Drop => {
+                // The generate function should be infallible here since the action was validated with the config.
+                let response =
+                    generate_response(&action).expect("could not generate action response");
+                if !self.thresholds.observe_only {
+                    info!(
+                        message = "process response",
+                        status = u16::from(response.status())
+                    );
+                }
+                // In observe-only mode, this response is what would have been sent if we had intercepted the request.
+                self.response = Some(Arc::new(response));
+                self.execute_decision_feedback().await;
+
+                (!self.thresholds.observe_only).then_some(action)
+            }
+        }
+    }
+
+    /// Delays the request for a tarpit action, unless it has already been delayed or the service is observe-only.
+    ///
+    /// The request's concurrency permit is released before the delay so that tarpitted requests can't starve other
+    /// requests of permits.
+    async fn tarpit(&mut self, delay: u64) {
+        if self.tarpitted || self.thresholds.observe_only {
+            return;
+        }
+        self.tarpitted = true;
+        self.request_permit = None;
+        info!(message = "tarpit request", delay = delay);
+        tokio::time::sleep(Duration::from_millis(delay)).await;
     }
 
     #[instrument(name = "plugin output", skip(self))]
@@ -1170,14 +1652,26 @@ impl ProcessorContext {
         }
     }
 
-    /// Generates a response indicating the request has been blocked.
-    fn generate_block_response(
-        code: i32,
-        body: &str,
-    ) -> Result<bulwark_sdk::Response, http::Error> {
-        http::response::Builder::new()
-            .status(code as u16)
-            .body(bytes::Bytes::from(body.to_string()))
+    /// Generates the response sent in place of the upstream response when an action intercepts a request.
+    ///
+    /// Dropped requests never receive a response, so they are represented by the non-standard `444` status.
+    fn generate_action_response(action: &Action) -> Result<bulwark_sdk::Response, http::Error> {
+        let builder = http::response::Builder::new();
+        match action {
+            Action::Redirect { location } => builder
+                .status(302)
+                .header(http::header::LOCATION, location)
+                .body(bytes::Bytes::new()),
+            Action::RateLimit { retry_after } => builder
+                .status(429)
+                .header(http::header::RETRY_AFTER, retry_after.to_string())
+                .body(bytes::Bytes::from("Too Many Requests\n")),
+            Action::Drop => builder.status(444).body(bytes::Bytes::new()),
+            // Actions that allow the request never send a response of their own, so they fall back to blocking.
+            Action::Block | Action::Allow | Action::Tarpit { .. } => builder
+                .status(403)
+                .body(bytes::Bytes::from("Access Denied\n")),
+        }
     }
 
     async fn send_allow_request_message(
@@ -1207,32 +1701,56 @@ impl ProcessorContext {
         Ok(sender.send(Ok(processing_reply)).await?)
     }
 
-    async fn send_block_request_message(
+    async fn send_immediate_response_message(
         sender: Arc<Mutex<UnboundedSender<Result<ProcessingResponse, tonic::Status>>>>,
-    ) -> Result<bulwark_sdk::Response, ProcessingMessageError> {
+        action: &Action,
+        response: &bulwark_sdk::Response,
+    ) -> Result<(), ProcessingMessageError> {
         let mut sender = sender.lock().await;
 
-        trace!("send_block_request_message (ProcessingResponse)");
-        // TODO: better default response + customizability
-        let code: i32 = 403;
-        let body = "Access Denied\n";
-        let response = Self::generate_block_response(code, body)?;
-
+        trace!("send_immediate_response_message (ProcessingResponse)");
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| HeaderValueOption {
+                header: Some(HeaderValue {
+                    key: name.to_string(),
+                    value: String::from_utf8_lossy(value.as_bytes()).to_string(),
+                }),
+                ..Default::default()
+            })
+            .collect();
         let processing_reply = ProcessingResponse {
             response: Some(processing_response::Response::ImmediateResponse(
                 ImmediateResponse {
-                    status: Some(HttpStatus { code }),
+                    status: Some(HttpStatus {
+                        code: response.status().as_u16() as i32,
+                    }),
                     // TODO: add decision debug
-                    details: "blocked by bulwark".to_string(),
-                    body: body.to_string(),
-                    headers: None,
+                    details: format!("{} by bulwark", action),
+                    body: String::from_utf8_lossy(response.body()).to_string(),
+                    headers: Some(HeaderMutation {
+                        set_headers: headers,
+                        remove_headers: vec![],
+                    }),
                     grpc_status: None,
                 },
             )),
             ..Default::default()
         };
-        sender.send(Ok(processing_reply)).await?;
-        Ok(response)
+        Ok(sender.send(Ok(processing_reply)).await?)
+    }
+
+    async fn send_drop_message(
+        sender: Arc<Mutex<UnboundedSender<Result<ProcessingResponse, tonic::Status>>>>,
+    ) -> Result<(), ProcessingMessageError> {
+        let mut sender = sender.lock().await;
+
+        trace!("send_drop_message (Status)");
+        // Ending the stream with an error leaves Envoy to fail the request without forwarding it.
+        Ok(sender
+            .send(Err(tonic::Status::aborted("dropped by bulwark")))
+            .await?)
     }
 
     async fn send_allow_response_message(
@@ -1260,36 +1778,6 @@ impl ProcessorContext {
         Ok(sender.send(Ok(processing_reply)).await?)
     }
 
-    async fn send_block_response_message(
-        sender: Arc<Mutex<UnboundedSender<Result<ProcessingResponse, tonic::Status>>>>,
-    ) -> Result<bulwark_sdk::Response, ProcessingMessageError> {
-        let mut sender = sender.lock().await;
-
-        trace!("send_block_response_message (ProcessingResponse)");
-        // Send back a response indicating the request has been blocked.
-        let code: i32 = 403;
-        // TODO: better default response + customizability
-        let body = "Access Denied\n";
-        let response: bulwark_sdk::Response = http::response::Builder::new()
-            .status(code as u16)
-            .body(bytes::Bytes::from(body))?;
-        let processing_reply = ProcessingResponse {
-            response: Some(processing_response::Response::ImmediateResponse(
-                ImmediateResponse {
-                    status: Some(HttpStatus { code }),
-                    // TODO: add decision debug
-                    details: "blocked by bulwark".to_string(),
-                    body: body.to_string(),
-                    headers: None,
-                    grpc_status: None,
-                },
-            )),
-            ..Default::default()
-        };
-        sender.send(Ok(processing_reply)).await?;
-        Ok(response)
-    }
-
     async fn get_request_header_message(
         stream: Arc<Mutex<Streaming<ProcessingRequest>>>,
     ) -> Result<Option<HttpHeaders>, tonic::Status> {
@@ -1430,6 +1918,159 @@ impl ProcessorContext {
 mod tests {
     use super::*;
 
+    /// Builds a config without plugins, routing each path to a resource with its own accepted action.
+    ///
+    /// Without plugins every request is accepted, so the accepted action is the only one that's ever taken.
+    pub(super) fn config_without_plugins(routes: Vec<(&str, Action)>) -> Config {
+        Config {
+            service: Default::default(),
+            runtime: Default::default(),
+            state: Default::default(),
+            thresholds: Default::default(),
+            actions: Default::default(),
+            metrics: Default::default(),
+            capture: Default::default(),
+            plugins: vec![],
+            presets: vec![],
+            resources: routes
+                .into_iter()
+                .map(|(route, accepted)| bulwark_config::Resource {
+                    route: route.to_string(),
+                    hosts: vec![],
+                    methods: vec![],
+                    headers: HashMap::new(),
+                    plugins: vec![],
+                    overrides: HashMap::new(),
+                    timeout: bulwark_config::Timeout::default(),
+                    actions: Actions {
+                        accepted,
+                        ..Default::default()
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_schedule_enrichment() -> Result<(), Box<dyn std::error::Error>> {
+        const DELAY: Duration = Duration::from_millis(100);
+        // Plugins 0 and 1 are independent, plugin 2 depends on plugin 0.
+        let dependencies = vec![vec![], vec![], vec![0]];
+        let timeouts = vec![Duration::from_secs(1); 3];
+        let router_labels = HashMap::from([("route".to_string(), "/".to_string())]);
+        let received = Arc::new(std::sync::Mutex::new(HashMap::new()));
+
+        let start = tokio::time::Instant::now();
+        let labels = schedule_enrichment(
+            &dependencies,
+            &timeouts,
+            &router_labels,
+            &Arc::new(Semaphore::new(3)),
+            |index, plugin_labels| {
+                received
+                    .lock()
+                    .unwrap()
+                    .insert(index, plugin_labels.clone());
+                async move {
+                    tokio::time::sleep(DELAY).await;
+                    Ok(HashMap::from([(
+                        format!("plugin_{index}"),
+                        plugin_labels.len().to_string(),
+                    )]))
+                }
+            },
+        )
+        .await;
+
+        // The independent plugins ran side by side, so the whole phase took two delays rather than three.
+        assert_eq!(start.elapsed(), DELAY * 2);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received[&0], router_labels);
+        assert_eq!(received[&1], router_labels);
+        // The dependent plugin sees its dependency's labels, but not those of the unrelated plugin.
+        assert_eq!(
+            received[&2],
+            HashMap::from([
+                ("route".to_string(), "/".to_string()),
+                ("plugin_0".to_string(), "1".to_string()),
+            ])
+        );
+        assert_eq!(
+            labels,
+            HashMap::from([
+                ("route".to_string(), "/".to_string()),
+                ("plugin_0".to_string(), "1".to_string()),
+                ("plugin_1".to_string(), "1".to_string()),
+                ("plugin_2".to_string(), "2".to_string()),
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_join_decision_tasks() -> Result<(), Box<dyn std::error::Error>> {
+        /// Flags the task it belongs to as cancelled if it is dropped before it finishes.
+        struct CancelGuard(Arc<std::sync::atomic::AtomicBool>);
+
+        impl Drop for CancelGuard {
+            fn drop(&mut self) {
+                self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+
+        let decisive = Decision {
+            accept: 0.0,
+            restrict: 1.0,
+            unknown: 0.0,
+        };
+        let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let mut join_set = JoinSet::new();
+        let guard = CancelGuard(cancelled.clone());
+        join_set.spawn(timeout(Duration::from_secs(10), async move {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            std::mem::forget(guard);
+            Ok((HashMap::from([("slow".to_string(), "1".to_string())]), None))
+        }));
+        join_set.spawn(timeout(Duration::from_secs(10), async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok((
+                HashMap::from([("decisive".to_string(), "1".to_string())]),
+                Some(decisive),
+            ))
+        }));
+
+        let start = tokio::time::Instant::now();
+        let mut labels = HashMap::new();
+        let decision = join_decision_tasks(join_set, &mut labels).await;
+
+        // The phase ends as soon as the decisive plugin finishes and the slow plugin never gets to finish.
+        assert_eq!(start.elapsed(), Duration::from_millis(10));
+        assert_eq!(decision, Some(decisive));
+        assert!(cancelled.load(std::sync::atomic::Ordering::SeqCst));
+        assert_eq!(
+            labels,
+            HashMap::from([("decisive".to_string(), "1".to_string())])
+        );
+
+        // Without a decisive decision every plugin gets to finish.
+        let mut join_set = JoinSet::new();
+        for (name, delay) in [("fast", 10), ("slow", 50)] {
+            join_set.spawn(timeout(Duration::from_secs(10), async move {
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                Ok((HashMap::from([(name.to_string(), "1".to_string())]), None))
+            }));
+        }
+        let start = tokio::time::Instant::now();
+        let mut labels = HashMap::new();
+        assert_eq!(join_decision_tasks(join_set, &mut labels).await, None);
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+        assert_eq!(labels.len(), 2);
+
+        Ok(())
+    }
+
     #[test]
     fn test_parse_forwarded() -> Result<(), Box<dyn std::error::Error>> {
         let test_cases = [
@@ -1509,4 +2150,28 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_generate_action_response() -> Result<(), Box<dyn std::error::Error>> {
+        let response = ProcessorContext::generate_action_response(&Action::Block)?;
+        assert_eq!(response.status(), 403);
+        assert_eq!(response.body(), "Access Denied\n");
+
+        let response = ProcessorContext::generate_action_response(&Action::Redirect {
+            location: "/challenge".to_string(),
+        })?;
+        assert_eq!(response.status(), 302);
+        assert_eq!(response.headers()[http::header::LOCATION], "/challenge");
+
+        let response =
+            ProcessorContext::generate_action_response(&Action::RateLimit { retry_after: 30 })?;
+        assert_eq!(response.status(), 429);
+        assert_eq!(response.headers()[http::header::RETRY_AFTER], "30");
+
+        let response = ProcessorContext::generate_action_response(&Action::Drop)?;
+        assert_eq!(response.status(), 444);
+        assert!(response.body().is_empty());
+
+        Ok(())
+    }
 }
@@ -0,0 +1,340 @@
+//! The auth request module contains a plain HTTP authorization service implementation, compatible with nginx's
+//! `auth_request`, Traefik's `forwardAuth` and Caddy's `forward_auth`.
+//!
+//! These proxies send a subrequest without a body, describing the original request with `X-Original-*` or
+//! `X-Forwarded-*` headers. As with the external authorization service, only the request phases are run. Every action
+//! that intercepts the request is answered with a `403`, since `auth_request` only distinguishes allowed requests from
+//! denied ones.
+
+use super::{BulwarkProcessor, ProcessorContext};
+use crate::headers::serialize_verdict_headers;
+use crate::{HandlerError, RequestError};
+use bulwark_config::Action;
+use bulwark_host::ForwardedIP;
+use std::{net::IpAddr, str::FromStr, sync::Arc};
+use tracing::{error, info, instrument, warn};
+
+/// The absolute URL of the original request, as sent by ingress-nginx.
+const ORIGINAL_URL_HEADER: &str = "x-original-url";
+/// The path and query of the original request, as conventionally configured for nginx.
+const ORIGINAL_URI_HEADER: &str = "x-original-uri";
+/// The method of the original request, as conventionally configured for nginx.
+const ORIGINAL_METHOD_HEADER: &str = "x-original-method";
+/// The path and query of the original request, as sent by Traefik and Caddy.
+const FORWARDED_URI_HEADER: &str = "x-forwarded-uri";
+/// The method of the original request, as sent by Traefik and Caddy.
+const FORWARDED_METHOD_HEADER: &str = "x-forwarded-method";
+const FORWARDED_PROTO_HEADER: &str = "x-forwarded-proto";
+const FORWARDED_HOST_HEADER: &str = "x-forwarded-host";
+
+/// Headers that describe the original request rather than being part of it.
+const ORIGINAL_REQUEST_HEADERS: [&str; 7] = [
+    ORIGINAL_URL_HEADER,
+    ORIGINAL_URI_HEADER,
+    ORIGINAL_METHOD_HEADER,
+    FORWARDED_URI_HEADER,
+    FORWARDED_METHOD_HEADER,
+    FORWARDED_PROTO_HEADER,
+    FORWARDED_HOST_HEADER,
+];
+
+impl BulwarkProcessor {
+    /// Handles an auth request subrequest, answering `200` if the original request is allowed and `403` if it isn't.
+    ///
+    /// Both responses carry the `bulwark-decision` and `bulwark-tags` verdict headers. Subrequests that don't describe
+    /// a valid original request are answered with a `400`.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - The headers of the subrequest.
+    /// * `remote_ip` - The address of the proxy that sent the subrequest, used as the client address when the
+    ///     forwarding headers don't provide one.
+    #[instrument(name = "handle auth request", skip(self, headers))]
+    pub async fn handle_auth_request(
+        &self,
+        headers: &http::HeaderMap,
+        remote_ip: Option<IpAddr>,
+    ) -> bulwark_sdk::Response {
+        let permit = self
+            .request_semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore closed");
+
+        let request =
+            match ProcessorContext::prepare_auth_request(headers, remote_ip, self.proxy_hops) {
+                Ok(request) => Arc::new(request),
+                Err(err) => {
+                    warn!(message = format!("request error: {}", err));
+                    return ProcessorContext::auth_request_error(http::StatusCode::BAD_REQUEST);
+                }
+            };
+
+        info!(
+            message = "auth request",
+            method = request.method().to_string(),
+            uri = request.uri().to_string(),
+            user_agent = request
+                .headers()
+                .get("User-Agent")
+                .map(|ua: &http::HeaderValue| ua.to_str().unwrap_or_default())
+        );
+
+        let mut ctx = match self.execute_request_phases(request, permit).await {
+            Ok(ctx) => ctx,
+            Err(err) => {
+                error!(message = format!("handler error: {}", err));
+                return ProcessorContext::auth_request_error(match err {
+                    HandlerError::RouteMatch(_) => http::StatusCode::NOT_FOUND,
+                    _ => http::StatusCode::INTERNAL_SERVER_ERROR,
+                });
+            }
+        };
+        ctx.complete_auth_request_phase().await
+    }
+}
+
+impl ProcessorContext {
+    /// Assembles a [`Request`](bulwark_sdk::Request) from the headers of an auth request subrequest.
+    // The error type is shared with the external processor, where it also needs to carry stream errors.
+    #[allow(clippy::result_large_err)]
+    fn prepare_auth_request(
+        headers: &http::HeaderMap,
+        remote_ip: Option<IpAddr>,
+        proxy_hops: usize,
+    ) -> Result<bulwark_sdk::Request, RequestError> {
+        let header_value = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        let method = http::Method::from_str(
+            header_value(ORIGINAL_METHOD_HEADER)
+                .or_else(|| header_value(FORWARDED_METHOD_HEADER))
+                .ok_or(RequestError::MissingMethod)?,
+        )?;
+        let request_uri = if let Some(url) = header_value(ORIGINAL_URL_HEADER) {
+            http::Uri::from_str(url).map_err(http::Error::from)?
+        } else {
+            let path = header_value(ORIGINAL_URI_HEADER)
+                .or_else(|| header_value(FORWARDED_URI_HEADER))
+                .ok_or(RequestError::MissingPath)?;
+            let authority = header_value(FORWARDED_HOST_HEADER)
+                .or_else(|| header_value(http::header::HOST.as_str()))
+                .ok_or(RequestError::MissingAuthority)?;
+            // An absolute URI makes the host available for routing and to plugins.
+            http::Uri::builder()
+                .scheme(header_value(FORWARDED_PROTO_HEADER).unwrap_or("http"))
+                .authority(authority)
+                .path_and_query(path)
+                .build()?
+        };
+
+        let mut request = http::Request::builder().method(method);
+        for (name, value) in headers {
+            // The subrequest's host is the authorization service, not the original host.
+            if *name != http::header::HOST && !ORIGINAL_REQUEST_HEADERS.contains(&name.as_str()) {
+                request = request.header(name, value);
+            }
+        }
+        if let Some(authority) = request_uri.authority() {
+            request = request.header(http::header::HOST, authority.as_str());
+        }
+        request = request.uri(request_uri);
+
+        let forwarded_ip = if let Some(forwarded) = header_value("forwarded") {
+            Self::parse_forwarded_ip(forwarded, proxy_hops)
+        } else if let Some(forwarded) = header_value("x-forwarded-for") {
+            Self::parse_x_forwarded_for_ip(forwarded, proxy_hops)
+        } else {
+            None
+        };
+        if let Some(ip_addr) = forwarded_ip.or(remote_ip) {
+            request = request.extension(ForwardedIP(ip_addr));
+        }
+
+        // Subrequests never carry the original request body.
+        Ok(request.body(bytes::Bytes::new())?)
+    }
+
+    /// Maps the combined decision onto an auth request response.
+    async fn complete_auth_request_phase(&mut self) -> bulwark_sdk::Response {
+        let interception = self
+            .complete_request_only_phase(|_| Self::generate_action_response(&Action::Block))
+            .await;
+        let verdict = self
+            .verdict
+            .clone()
+            .expect("request decision completed without verdict");
+
+        let mut response = if interception.is_some() {
+            Self::generate_action_response(&Action::Block).expect("could not generate response")
+        } else {
+            http::Response::new(bytes::Bytes::new())
+        };
+        match serialize_verdict_headers(&verdict) {
+            Ok(verdict_headers) => {
+                for (name, value) in verdict_headers {
+                    if let Ok(value) = http::HeaderValue::from_str(&value) {
+                        response.headers_mut().insert(name, value);
+                    }
+                }
+            }
+            Err(err) => {
+                error!(message = format!("verdict header error: {}", err));
+            }
+        }
+        response
+    }
+
+    fn auth_request_error(status: http::StatusCode) -> bulwark_sdk::Response {
+        let mut response = http::Response::new(bytes::Bytes::new());
+        *response.status_mut() = status;
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::config_without_plugins;
+    use super::*;
+    use std::time::Duration;
+
+    fn header_map(headers: Vec<(&'static str, &'static str)>) -> http::HeaderMap {
+        headers
+            .into_iter()
+            .map(|(name, value)| {
+                (
+                    http::HeaderName::from_static(name),
+                    http::HeaderValue::from_static(value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_prepare_auth_request() -> Result<(), Box<dyn std::error::Error>> {
+        // nginx
+        let request = ProcessorContext::prepare_auth_request(
+            &header_map(vec![
+                ("host", "bulwark.internal"),
+                ("x-forwarded-host", "example.com"),
+                ("x-original-method", "POST"),
+                ("x-original-uri", "/login?next=%2F"),
+                ("user-agent", "curl/8.0"),
+            ]),
+            Some(IpAddr::from_str("192.0.2.10")?),
+            1,
+        )?;
+        assert_eq!(request.method(), http::Method::POST);
+        assert_eq!(request.uri(), "http://example.com/login?next=%2F");
+        assert_eq!(request.headers().get("host").unwrap(), "example.com");
+        assert_eq!(request.headers().get("user-agent").unwrap(), "curl/8.0");
+        assert!(request.headers().get("x-original-uri").is_none());
+        assert_eq!(
+            request.extensions().get::<ForwardedIP>().unwrap().0,
+            IpAddr::from_str("192.0.2.10")?
+        );
+
+        // Traefik and Caddy
+        let request = ProcessorContext::prepare_auth_request(
+            &header_map(vec![
+                ("x-forwarded-method", "GET"),
+                ("x-forwarded-proto", "https"),
+                ("x-forwarded-host", "example.com"),
+                ("x-forwarded-uri", "/"),
+                ("x-forwarded-for", "203.0.113.7, 198.51.100.1"),
+            ]),
+            Some(IpAddr::from_str("192.0.2.10")?),
+            1,
+        )?;
+        assert_eq!(request.method(), http::Method::GET);
+        assert_eq!(request.uri(), "https://example.com/");
+        assert_eq!(
+            request.extensions().get::<ForwardedIP>().unwrap().0,
+            IpAddr::from_str("198.51.100.1")?
+        );
+
+        // ingress-nginx
+        let request = ProcessorContext::prepare_auth_request(
+            &header_map(vec![
+                ("x-original-method", "GET"),
+                ("x-original-url", "https://example.com/search?q=1"),
+            ]),
+            None,
+            1,
+        )?;
+        assert_eq!(request.uri(), "https://example.com/search?q=1");
+        assert!(request.extensions().get::<ForwardedIP>().is_none());
+
+        assert!(matches!(
+            ProcessorContext::prepare_auth_request(
+                &header_map(vec![("x-original-uri", "/")]),
+                None,
+                1
+            ),
+            Err(RequestError::MissingMethod)
+        ));
+        assert!(matches!(
+            ProcessorContext::prepare_auth_request(
+                &header_map(vec![("x-original-method", "GET"), ("x-original-uri", "/")]),
+                None,
+                1
+            ),
+            Err(RequestError::MissingAuthority)
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tarpit_releases_permit() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = config_without_plugins(vec![
+            ("/slow", Action::Tarpit { delay: 1000 }),
+            ("/*path", Action::Allow),
+        ]);
+        config.runtime.max_concurrent_requests = 1;
+        let processor = BulwarkProcessor::new(config).await?;
+        let request_headers = |uri: &'static str| {
+            header_map(vec![
+                ("x-forwarded-method", "GET"),
+                ("x-forwarded-host", "example.com"),
+                ("x-forwarded-uri", uri),
+            ])
+        };
+
+        let slow = tokio::spawn({
+            let processor = processor.clone();
+            async move {
+                processor
+                    .handle_auth_request(&request_headers("/slow"), None)
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // The only permit is free again while the first request is tarpitted, so the second one isn't held up.
+        let start = tokio::time::Instant::now();
+        let response = processor
+            .handle_auth_request(&request_headers("/fast"), None)
+            .await;
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert!(!slow.is_finished());
+        assert_eq!(slow.await?.status(), http::StatusCode::OK);
+
+        // A request is only ever tarpitted once.
+        let request = Arc::new(ProcessorContext::prepare_auth_request(
+            &request_headers("/slow"),
+            None,
+            0,
+        )?);
+        let permit = processor.request_semaphore.clone().acquire_owned().await?;
+        let mut ctx = processor.execute_request_phases(request, permit).await?;
+        let start = tokio::time::Instant::now();
+        ctx.tarpit(1000).await;
+        assert_eq!(processor.request_semaphore.available_permits(), 1);
+        ctx.tarpit(1000).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(1000));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+//! The authorization module contains the Envoy external authorization service implementation.
+//!
+//! Envoy's external authorization filter only asks for a decision about the request, so only the init, enrichment
+//! and request decision phases are run. The response is never seen by the authorization service, which means plugins
+//! that make response decisions are not supported in this mode. Decision feedback is only performed when a request
+//! is intercepted, using the response that was sent in place of the upstream response.
+
+use super::{BulwarkProcessor, ProcessorContext};
+use crate::headers::serialize_verdict_headers;
+use crate::{HandlerError, RequestError, SfvError};
+use bulwark_config::Action;
+use bulwark_host::ForwardedIP;
+use bulwark_sdk::Verdict;
+use envoy_control_plane::envoy::{
+    config::core::v3::{address, HeaderValue, HeaderValueOption},
+    r#type::v3::HttpStatus,
+    service::auth::v3::{
+        attribute_context, authorization_server::Authorization, check_response, CheckRequest,
+        CheckResponse, DeniedHttpResponse, OkHttpResponse,
+    },
+};
+use envoy_control_plane::google::rpc;
+use std::{net::IpAddr, str::FromStr, sync::Arc};
+use tracing::{info, instrument};
+
+/// The `google.rpc.Code` values used in check responses.
+const CODE_OK: i32 = 0;
+const CODE_PERMISSION_DENIED: i32 = 7;
+
+#[tonic::async_trait]
+impl Authorization for BulwarkProcessor {
+    /// Checks an incoming request, running every plugin phase up to and including the request decision phase.
+    #[instrument(name = "check request", skip(self, tonic_request))]
+    async fn check(
+        &self,
+        tonic_request: tonic::Request<CheckRequest>,
+    ) -> Result<tonic::Response<CheckResponse>, tonic::Status> {
+        let permit = self
+            .request_semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore closed");
+
+        let request = Arc::new(
+            ProcessorContext::prepare_check_request(tonic_request.into_inner(), self.proxy_hops)
+                .map_err(|err| tonic::Status::invalid_argument(err.to_string()))?,
+        );
+
+        info!(
+            message = "check request",
+            method = request.method().to_string(),
+            uri = request.uri().to_string(),
+            user_agent = request
+                .headers()
+                .get("User-Agent")
+                .map(|ua: &http::HeaderValue| ua.to_str().unwrap_or_default())
+        );
+
+        let mut ctx =
+            self.execute_request_phases(request, permit)
+                .await
+                .map_err(|err| match err {
+                    HandlerError::RouteMatch(_) => tonic::Status::not_found(err.to_string()),
+                    _ => tonic::Status::internal(err.to_string()),
+                })?;
+
+        ctx.complete_check_phase().await.map(tonic::Response::new)
+    }
+}
+
+impl ProcessorContext {
+    /// Assembles a [`Request`](bulwark_sdk::Request) from the attributes of an external authorization check.
+    // The error type is shared with the external processor, where it also needs to carry stream errors.
+    #[allow(clippy::result_large_err)]
+    fn prepare_check_request(
+        check_request: CheckRequest,
+        proxy_hops: usize,
+    ) -> Result<bulwark_sdk::Request, RequestError> {
+        let attributes = check_request
+            .attributes
+            .ok_or(RequestError::MissingHeaders)?;
+        let http_request = attributes
+            .request
+            .and_then(|request| request.http)
+            .ok_or(RequestError::MissingHeaders)?;
+
+        if http_request.scheme.is_empty() {
+            return Err(RequestError::MissingScheme);
+        }
+        if http_request.host.is_empty() {
+            return Err(RequestError::MissingAuthority);
+        }
+        if http_request.path.is_empty() {
+            return Err(RequestError::MissingPath);
+        }
+        let method = http::Method::from_str(&http_request.method)?;
+        // The path includes the query string.
+        let request_uri = http::Uri::builder()
+            .scheme(http_request.scheme.as_str())
+            .authority(http_request.host.as_str())
+            .path_and_query(http_request.path.as_str())
+            .build()?;
+
+        let mut request = http::Request::builder().method(method).uri(request_uri);
+        for (key, value) in &http_request.headers {
+            // must not pass through Envoy pseudo headers here, http module treats them as invalid
+            if !key.starts_with(':') {
+                request = request.header(key, value);
+            }
+        }
+
+        // NOTE: header keys are sent in lower case
+        let forwarded_ip = if let Some(forwarded) = http_request.headers.get("forwarded") {
+            Self::parse_forwarded_ip(forwarded, proxy_hops)
+        } else if let Some(forwarded) = http_request.headers.get("x-forwarded-for") {
+            Self::parse_x_forwarded_for_ip(forwarded, proxy_hops)
+        } else {
+            None
+        };
+        // Unlike the external processor, the peer address is available, so fall back to it.
+        if let Some(ip_addr) = forwarded_ip.or_else(|| Self::source_ip(attributes.source)) {
+            request = request.extension(ForwardedIP(ip_addr));
+        }
+
+        // The body is only present when the filter is configured with `with_request_body`.
+        let request_chunk = if !http_request.raw_body.is_empty() {
+            bytes::Bytes::from(http_request.raw_body)
+        } else {
+            bytes::Bytes::from(http_request.body)
+        };
+        Ok(request.body(request_chunk)?)
+    }
+
+    fn source_ip(source: Option<attribute_context::Peer>) -> Option<IpAddr> {
+        match source?.address?.address? {
+            address::Address::SocketAddress(socket_address) => {
+                IpAddr::from_str(&socket_address.address).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Maps the combined decision onto a [`CheckResponse`] using the action configured for its outcome.
+    async fn complete_check_phase(&mut self) -> Result<CheckResponse, tonic::Status> {
+        let interception = self
+            .complete_request_only_phase(Self::generate_action_response)
+            .await;
+        let verdict = self
+            .verdict
+            .clone()
+            .expect("request decision completed without verdict");
+        match interception {
+            None => Self::ok_check_response(&verdict)
+                .map_err(|err| tonic::Status::internal(err.to_string())),
+            // Envoy fails the request according to the filter's `status_on_error` setting.
+            Some(Action::Drop) => Err(tonic::Status::aborted("dropped by bulwark")),
+            Some(action) => {
+                let response = self
+                    .response
+                    .as_ref()
+                    .expect("intercepted request has no response");
+                Ok(Self::denied_check_response(&action, response))
+            }
+        }
+    }
+
+    /// Builds a [`CheckResponse`] that allows the request, passing the decision and tags to the interior service.
+    fn ok_check_response(verdict: &Verdict) -> Result<CheckResponse, SfvError> {
+        let headers = serialize_verdict_headers(verdict)?
+            .into_iter()
+            .map(|(key, value)| Self::header_value_option(key, value))
+            .collect();
+        Ok(CheckResponse {
+            status: Some(rpc::Status {
+                code: CODE_OK,
+                ..Default::default()
+            }),
+            http_response: Some(check_response::HttpResponse::OkResponse(OkHttpResponse {
+                headers,
+                ..Default::default()
+            })),
+            ..Default::default()
+        })
+    }
+
+    /// Builds a [`CheckResponse`] that denies the request, sending the generated response to the client instead.
+    fn denied_check_response(action: &Action, response: &bulwark_sdk::Response) -> CheckResponse {
+        let headers = response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                Self::header_value_option(
+                    name.as_str(),
+                    String::from_utf8_lossy(value.as_bytes()).to_string(),
+                )
+            })
+            .collect();
+        CheckResponse {
+            status: Some(rpc::Status {
+                code: CODE_PERMISSION_DENIED,
+                message: format!("{} by bulwark", action),
+                ..Default::default()
+            }),
+            http_response: Some(check_response::HttpResponse::DeniedResponse(
+                DeniedHttpResponse {
+                    status: Some(HttpStatus {
+                        code: response.status().as_u16() as i32,
+                    }),
+                    headers,
+                    body: String::from_utf8_lossy(response.body()).to_string(),
+                },
+            )),
+            ..Default::default()
+        }
+    }
+
+    fn header_value_option(key: &str, value: String) -> HeaderValueOption {
+        HeaderValueOption {
+            header: Some(HeaderValue {
+                key: key.to_string(),
+                value,
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::{DECISION_HEADER, TAGS_HEADER};
+    use bulwark_sdk::{Decision, Outcome};
+    use envoy_control_plane::envoy::{
+        config::core::v3::{Address, SocketAddress},
+        service::auth::v3::AttributeContext,
+    };
+
+    fn check_request(headers: Vec<(&str, &str)>, source: Option<&str>) -> CheckRequest {
+        CheckRequest {
+            attributes: Some(AttributeContext {
+                source: source.map(|address| attribute_context::Peer {
+                    address: Some(Address {
+                        address: Some(address::Address::SocketAddress(SocketAddress {
+                            address: address.to_string(),
+                            ..Default::default()
+                        })),
+                    }),
+                    ..Default::default()
+                }),
+                request: Some(attribute_context::Request {
+                    http: Some(attribute_context::HttpRequest {
+                        method: "POST".to_string(),
+                        scheme: "https".to_string(),
+                        host: "example.com".to_string(),
+                        path: "/login?next=%2F".to_string(),
+                        headers: headers
+                            .into_iter()
+                            .map(|(key, value)| (key.to_string(), value.to_string()))
+                            .collect(),
+                        body: "user=alice".to_string(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn test_prepare_check_request() -> Result<(), Box<dyn std::error::Error>> {
+        let request = ProcessorContext::prepare_check_request(
+            check_request(
+                vec![(":authority", "example.com"), ("user-agent", "curl/8.0")],
+                Some("192.0.2.10"),
+            ),
+            1,
+        )?;
+        assert_eq!(request.method(), http::Method::POST);
+        assert_eq!(request.uri(), "https://example.com/login?next=%2F");
+        assert_eq!(request.headers().get("user-agent").unwrap(), "curl/8.0");
+        assert!(request.headers().get(":authority").is_none());
+        assert_eq!(request.body(), "user=alice");
+        assert_eq!(
+            request.extensions().get::<ForwardedIP>().unwrap().0,
+            IpAddr::from_str("192.0.2.10")?
+        );
+
+        // Forwarding headers take precedence over the peer address.
+        let request = ProcessorContext::prepare_check_request(
+            check_request(
+                vec![("x-forwarded-for", "203.0.113.7, 198.51.100.1")],
+                Some("192.0.2.10"),
+            ),
+            1,
+        )?;
+        assert_eq!(
+            request.extensions().get::<ForwardedIP>().unwrap().0,
+            IpAddr::from_str("198.51.100.1")?
+        );
+
+        assert!(matches!(
+            ProcessorContext::prepare_check_request(CheckRequest { attributes: None }, 1),
+            Err(RequestError::MissingHeaders)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_responses() -> Result<(), Box<dyn std::error::Error>> {
+        let check_response = ProcessorContext::ok_check_response(&Verdict {
+            decision: Decision {
+                accept: 0.0,
+                restrict: 0.0,
+                unknown: 1.0,
+            },
+            outcome: Outcome::Accepted,
+            tags: vec!["bot".to_string()],
+        })?;
+        assert_eq!(check_response.status.unwrap().code, CODE_OK);
+        let Some(check_response::HttpResponse::OkResponse(ok_response)) =
+            check_response.http_response
+        else {
+            panic!("expected ok response");
+        };
+        let headers: Vec<(String, String)> = ok_response
+            .headers
+            .into_iter()
+            .map(|option| {
+                let header = option.header.unwrap();
+                (header.key, header.value)
+            })
+            .collect();
+        assert_eq!(
+            headers,
+            vec![
+                (
+                    DECISION_HEADER.to_string(),
+                    "accept=0.0, restrict=0.0, unknown=1.0, score=0.5, outcome=\"accepted\""
+                        .to_string()
+                ),
+                (TAGS_HEADER.to_string(), "bot".to_string()),
+            ]
+        );
+
+        let action = Action::RateLimit { retry_after: 30 };
+        let response = ProcessorContext::generate_action_response(&action)?;
+        let check_response = ProcessorContext::denied_check_response(&action, &response);
+        let status = check_response.status.unwrap();
+        assert_eq!(status.code, CODE_PERMISSION_DENIED);
+        assert_eq!(status.message, "rate_limit by bulwark");
+        let Some(check_response::HttpResponse::DeniedResponse(denied_response)) =
+            check_response.http_response
+        else {
+            panic!("expected denied response");
+        };
+        assert_eq!(denied_response.status.unwrap().code, 429);
+        assert_eq!(denied_response.body, "Too Many Requests\n");
+        let header = denied_response.headers[0].header.clone().unwrap();
+        assert_eq!(header.key, "retry-after");
+        assert_eq!(header.value, "30");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,451 @@
+//! The capture module records processed traffic to a rotating NDJSON file so that it can be replayed later.
+//!
+//! Each line is a [`CaptureRecord`], holding the request in the same form the [`decide`](super::decide) API accepts,
+//! along with the verdict it received. Bodies are truncated to the configured size, so a replay only sees what was
+//! kept. The values of credential headers, or whichever headers the config lists, are redacted before they're
+//! written.
+
+use super::{DecideRequest, DecisionValues, ObservedResponse, ProcessorContext};
+use base64::Engine as _;
+use bulwark_host::ForwardedIP;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::error;
+
+/// The value written in place of a redacted header value.
+const REDACTED: &str = "[redacted]";
+
+/// A single processed request, as written to a capture file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    /// The time the verdict was reached.
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// The request along with its upstream response, if one was received.
+    pub request: DecideRequest,
+    /// Whether the request or response body was truncated.
+    pub truncated: bool,
+    /// The labels extracted from the route.
+    pub labels: BTreeMap<String, String>,
+    pub verdict: CapturedVerdict,
+}
+
+/// The verdict recorded for a [`CaptureRecord`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturedVerdict {
+    pub decision: DecisionValues,
+    pub outcome: String,
+    /// The action configured for the outcome, which may not have been taken in observe-only mode.
+    pub action: String,
+    pub tags: Vec<String>,
+}
+
+/// Appends capture records to a file, rotating it once it exceeds the configured size.
+pub(crate) struct CaptureWriter {
+    path: PathBuf,
+    max_body_size: usize,
+    max_file_size: u64,
+    max_files: usize,
+    /// The lowercase names of the headers whose values are redacted.
+    redact_headers: HashSet<String>,
+    /// The open capture file and the number of bytes it holds.
+    file: Mutex<(File, u64)>,
+}
+
+impl CaptureWriter {
+    /// Opens the capture file, appending to it if it already exists.
+    pub(crate) fn open(path: &str, capture: &bulwark_config::Capture) -> std::io::Result<Self> {
+        let path = PathBuf::from(path);
+        let file = Self::open_file(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_body_size: capture.max_body_size,
+            max_file_size: capture.max_file_size,
+            max_files: capture.max_files,
+            redact_headers: capture.redact_headers.iter().cloned().collect(),
+            file: Mutex::new((file, size)),
+        })
+    }
+
+    fn open_file(path: &Path) -> std::io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// Writes a single record as one line, rotating first if the line would exceed the maximum file size.
+    fn write(&self, record: &CaptureRecord) -> std::io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut guard = self.file.lock().expect("capture file lock poisoned");
+        let (file, size) = &mut *guard;
+        if *size > 0 && *size + line.len() as u64 > self.max_file_size {
+            self.rotate()?;
+            *file = Self::open_file(&self.path)?;
+            *size = 0;
+        }
+        file.write_all(&line)?;
+        *size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts each rotated file up by one suffix, discarding the oldest, and moves the current file into `.1`.
+    fn rotate(&self) -> std::io::Result<()> {
+        let rotated = |index: usize| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(format!(".{}", index));
+            PathBuf::from(path)
+        };
+        if self.max_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        for index in (1..self.max_files).rev() {
+            match std::fs::rename(rotated(index), rotated(index + 1)) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        std::fs::rename(&self.path, rotated(1))
+    }
+
+    /// Truncates a body to the maximum capture size and encodes it, returning whether it was truncated.
+    fn encode_body(&self, body: &bytes::Bytes) -> (Option<String>, bool) {
+        if body.is_empty() {
+            return (None, false);
+        }
+        let truncated = body.len() > self.max_body_size;
+        let body = &body[..body.len().min(self.max_body_size)];
+        (
+            Some(base64::engine::general_purpose::STANDARD.encode(body)),
+            truncated,
+        )
+    }
+}
+
+/// Joins repeated headers with commas, as expected by a [`DecideRequest`], redacting the values of the `redact`
+/// headers.
+fn join_headers(headers: &http::HeaderMap, redact: &HashSet<String>) -> BTreeMap<String, String> {
+    let mut joined: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in headers {
+        if redact.contains(name.as_str()) {
+            joined.insert(name.to_string(), REDACTED.to_string());
+            continue;
+        }
+        let value = String::from_utf8_lossy(value.as_bytes());
+        joined
+            .entry(name.to_string())
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(&value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    joined
+}
+
+impl ProcessorContext {
+    /// Writes the request and its verdict to the capture file, if capture is enabled.
+    ///
+    /// The response is only recorded when `upstream_response` is set, since a response generated for an intercepting
+    /// action was never seen by the response phase.
+    pub(super) async fn capture_traffic(&self, upstream_response: bool) {
+        let Some(capture) = self.capture.clone() else {
+            return;
+        };
+        let verdict = self
+            .verdict
+            .as_ref()
+            .expect("cannot capture request without verdict");
+        let record = self.capture_record(&capture, upstream_response, verdict);
+
+        // File writes block, so they happen off the async runtime.
+        let result = tokio::task::spawn_blocking(move || capture.write(&record)).await;
+        match result {
+            Ok(Err(err)) => error!(message = format!("capture error: {}", err)),
+            Err(err) => error!(message = format!("capture error: {}", err)),
+            Ok(Ok(())) => {}
+        }
+    }
+
+    fn capture_record(
+        &self,
+        capture: &CaptureWriter,
+        upstream_response: bool,
+        verdict: &bulwark_sdk::Verdict,
+    ) -> CaptureRecord {
+        let (body, mut truncated) = capture.encode_body(self.request.body());
+        let response = if upstream_response {
+            self.response.as_ref().map(|response| {
+                let (body, response_truncated) = capture.encode_body(response.body());
+                truncated |= response_truncated;
+                ObservedResponse {
+                    status: response.status().as_u16(),
+                    headers: join_headers(response.headers(), &capture.redact_headers),
+                    body,
+                }
+            })
+        } else {
+            None
+        };
+        let mut tags = verdict.tags.clone();
+        tags.sort();
+
+        CaptureRecord {
+            timestamp: chrono::Utc::now(),
+            request: DecideRequest {
+                method: self.request.method().to_string(),
+                uri: self.request.uri().to_string(),
+                headers: join_headers(self.request.headers(), &capture.redact_headers),
+                body,
+                client_ip: self
+                    .request
+                    .extensions()
+                    .get::<ForwardedIP>()
+                    .map(|forwarded_ip| forwarded_ip.0),
+                response,
+            },
+            truncated,
+            labels: self.router_labels.clone().into_iter().collect(),
+            verdict: CapturedVerdict {
+                decision: verdict.decision.into(),
+                outcome: verdict.outcome.to_string(),
+                action: self.actions.action(verdict.outcome).to_string(),
+                tags,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::config_without_plugins;
+    use super::*;
+    use crate::BulwarkProcessor;
+    use bulwark_config::Action;
+    use bulwark_host::HandlerOutput;
+    use bulwark_sdk::Decision;
+    use std::{collections::HashMap, sync::Arc};
+
+    fn record(uri: &str) -> CaptureRecord {
+        CaptureRecord {
+            timestamp: chrono::Utc::now(),
+            request: DecideRequest {
+                method: "GET".to_string(),
+                uri: uri.to_string(),
+                headers: BTreeMap::new(),
+                body: None,
+                client_ip: None,
+                response: None,
+            },
+            truncated: false,
+            labels: BTreeMap::new(),
+            verdict: CapturedVerdict {
+                decision: Decision::default().into(),
+                outcome: "accepted".to_string(),
+                action: "allow".to_string(),
+                tags: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_capture_rotation() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("bulwark-capture-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("capture.ndjson");
+        let line_size = serde_json::to_vec(&record("https://example.com/0"))?.len() as u64 + 1;
+
+        // Room for two records per file, keeping one rotated file.
+        let writer = CaptureWriter::open(
+            path.to_str().unwrap(),
+            &bulwark_config::Capture {
+                path: None,
+                max_body_size: 4,
+                max_file_size: line_size * 2,
+                max_files: 1,
+                redact_headers: vec![],
+            },
+        )?;
+        for index in 0..5 {
+            writer.write(&record(&format!("https://example.com/{}", index)))?;
+        }
+
+        let read_uris = |path: &Path| -> Result<Vec<String>, Box<dyn std::error::Error>> {
+            std::fs::read_to_string(path)?
+                .lines()
+                .map(|line| Ok(serde_json::from_str::<CaptureRecord>(line)?.request.uri))
+                .collect()
+        };
+        assert_eq!(read_uris(&path)?, vec!["https://example.com/4"]);
+        assert_eq!(
+            read_uris(&dir.join("capture.ndjson.1"))?,
+            vec!["https://example.com/2", "https://example.com/3"]
+        );
+        assert!(!dir.join("capture.ndjson.2").exists());
+
+        assert_eq!(
+            writer.encode_body(&bytes::Bytes::from_static(b"abcdef")),
+            (Some("YWJjZA==".to_string()), true)
+        );
+        assert_eq!(writer.encode_body(&bytes::Bytes::new()), (None, false));
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_capture_record() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("bulwark-record-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let writer = CaptureWriter::open(
+            dir.join("capture.ndjson").to_str().unwrap(),
+            &bulwark_config::Capture {
+                path: None,
+                max_body_size: 4,
+                max_file_size: 1024,
+                max_files: 1,
+                redact_headers: vec!["authorization".to_string(), "set-cookie".to_string()],
+            },
+        )?;
+        let ctx = ProcessorContext {
+            sender: None,
+            stream: None,
+            plugin_semaphore: Arc::new(tokio::sync::Semaphore::new(1)),
+            plugin_instances: vec![],
+            plugin_timeouts: vec![],
+            plugin_dependencies: vec![],
+            router_labels: HashMap::from([("route.id".to_string(), "7".to_string())]),
+            request: Arc::new(
+                http::Request::builder()
+                    .method("POST")
+                    .uri("https://example.com/users/7")
+                    .header("authorization", "Bearer secret")
+                    .header("accept", "text/html")
+                    .header("accept", "application/json")
+                    .body(bytes::Bytes::from_static(b"abcdef"))?,
+            ),
+            response: Some(Arc::new(
+                http::Response::builder()
+                    .status(200)
+                    .header("set-cookie", "session=secret")
+                    .header("set-cookie", "theme=dark")
+                    .header("content-type", "text/plain")
+                    .body(bytes::Bytes::from_static(b"ok"))?,
+            )),
+            verdict: None,
+            combined_output: HandlerOutput::default(),
+            plugin_outputs: HashMap::new(),
+            thresholds: Default::default(),
+            actions: Default::default(),
+            capture: None,
+            request_permit: None,
+            tarpitted: false,
+        };
+        let verdict = bulwark_sdk::Verdict {
+            decision: Decision::default(),
+            outcome: bulwark_sdk::Outcome::Accepted,
+            tags: vec!["b".to_string(), "a".to_string()],
+        };
+
+        let record = ctx.capture_record(&writer, true, &verdict);
+        assert_eq!(record.request.method, "POST");
+        assert_eq!(
+            record.request.headers,
+            BTreeMap::from([
+                (
+                    "accept".to_string(),
+                    "text/html, application/json".to_string()
+                ),
+                ("authorization".to_string(), REDACTED.to_string()),
+            ])
+        );
+        assert_eq!(record.request.body.as_deref(), Some("YWJjZA=="));
+        assert!(record.truncated);
+        let response = record.request.response.expect("response not recorded");
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.headers,
+            BTreeMap::from([
+                ("content-type".to_string(), "text/plain".to_string()),
+                ("set-cookie".to_string(), REDACTED.to_string()),
+            ])
+        );
+        assert_eq!(record.labels.get("route.id").map(String::as_str), Some("7"));
+        assert_eq!(record.verdict.action, "allow");
+        assert_eq!(record.verdict.tags, vec!["a", "b"]);
+
+        // A response that didn't come from upstream is left out.
+        let record = ctx.capture_record(&writer, false, &verdict);
+        assert!(record.request.response.is_none());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_capture_paths() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("bulwark-paths-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("capture.ndjson");
+        let mut config =
+            config_without_plugins(vec![("/blocked", Action::Block), ("/*path", Action::Allow)]);
+        config.capture.path = Some(path.to_str().unwrap().to_string());
+        let processor = BulwarkProcessor::new(config).await?;
+
+        for uri in ["/allowed", "/blocked"] {
+            let headers: http::HeaderMap = [
+                ("x-forwarded-method", "GET"),
+                ("x-forwarded-host", "example.com"),
+                ("x-forwarded-uri", uri),
+                ("authorization", "Basic c2VjcmV0"),
+                ("cookie", "session=secret"),
+            ]
+            .into_iter()
+            .map(|(name, value)| {
+                (
+                    http::HeaderName::from_static(name),
+                    http::HeaderValue::from_static(value),
+                )
+            })
+            .collect();
+            processor.handle_auth_request(&headers, None).await;
+        }
+        // Out-of-band decisions are never captured.
+        processor
+            .decide(serde_json::from_value(serde_json::json!({
+                "method": "GET",
+                "uri": "https://example.com/decided",
+                "response": {"status": 200},
+            }))?)
+            .await?;
+
+        let records = std::fs::read_to_string(&path)?
+            .lines()
+            .map(serde_json::from_str::<CaptureRecord>)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (record.request.uri.as_str(), record.verdict.action.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("http://example.com/allowed", "allow"),
+                ("http://example.com/blocked", "block")
+            ]
+        );
+        for record in &records {
+            // The auth request services never see the upstream response, and the blocked request's 403 was generated.
+            assert!(record.request.response.is_none());
+            assert_eq!(record.request.headers["authorization"], REDACTED);
+            assert_eq!(record.request.headers["cookie"], REDACTED);
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
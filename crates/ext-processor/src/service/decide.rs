@@ -0,0 +1,325 @@
+//! The decide module allows requests to be evaluated out-of-band, without a proxy in the path.
+//!
+//! A [`DecideRequest`] describes a request, and optionally the response that was already sent for it. Every plugin
+//! phase that applies is run and the combined decision is returned along with each plugin's output. No action is
+//! taken on the outcome, although the action that would have been taken is reported.
+//!
+//! The decide API is only served as JSON over HTTP. Unlike the external processor and external authorization
+//! services, it has no gRPC counterpart.
+
+use super::{BulwarkProcessor, ProcessorContext};
+use crate::{DecideError, RequestError, ResponseError};
+use base64::Engine as _;
+use bulwark_config::Action;
+use bulwark_host::{ForwardedIP, HandlerOutput};
+use bulwark_sdk::{Decision, Verdict};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc};
+use tracing::{info, instrument};
+
+/// A request to be decided on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DecideRequest {
+    pub method: String,
+    /// The absolute URI of the request, the host is used for routing.
+    pub uri: String,
+    /// Multiple values for the same header should be joined with commas.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// The base64-encoded request body.
+    #[serde(default)]
+    pub body: Option<String>,
+    /// The address of the client that sent the request.
+    #[serde(default)]
+    pub client_ip: Option<IpAddr>,
+    /// The response that was sent for the request, if any. The response phase is only run when this is present.
+    #[serde(default)]
+    pub response: Option<ObservedResponse>,
+}
+
+/// A response that was already sent for a [`DecideRequest`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ObservedResponse {
+    pub status: u16,
+    /// Multiple values for the same header should be joined with commas.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// The base64-encoded response body.
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+/// The combined decision for a [`DecideRequest`], along with the output of each plugin.
+#[derive(Debug, Clone, Serialize)]
+pub struct DecideResponse {
+    pub decision: DecisionValues,
+    pub outcome: String,
+    /// The action configured for the outcome. It is reported, but not taken.
+    pub action: Action,
+    pub tags: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+    /// The output of each plugin, keyed by plugin reference.
+    pub plugins: BTreeMap<String, PluginOutput>,
+}
+
+/// The output of a single plugin in a [`DecideResponse`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginOutput {
+    pub decision: DecisionValues,
+    pub tags: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+}
+
+/// A [`Decision`] along with its score.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DecisionValues {
+    pub accept: f64,
+    pub restrict: f64,
+    pub unknown: f64,
+    /// The pignistic restrict value, as used to determine the outcome.
+    pub score: f64,
+}
+
+impl From<Decision> for DecisionValues {
+    fn from(decision: Decision) -> Self {
+        Self {
+            accept: decision.accept,
+            restrict: decision.restrict,
+            unknown: decision.unknown,
+            score: decision.pignistic().restrict,
+        }
+    }
+}
+
+impl From<&HandlerOutput> for PluginOutput {
+    fn from(output: &HandlerOutput) -> Self {
+        let mut tags: Vec<String> = output.tags.iter().cloned().collect();
+        tags.sort();
+        Self {
+            decision: output.decision.into(),
+            tags,
+            labels: output.labels.clone().into_iter().collect(),
+        }
+    }
+}
+
+impl BulwarkProcessor {
+    /// Decides on a request out-of-band, running every plugin phase that applies.
+    ///
+    /// Decision feedback is only performed when the response is provided.
+    #[instrument(name = "decide request", skip(self, decide_request))]
+    pub async fn decide(
+        &self,
+        decide_request: DecideRequest,
+    ) -> Result<DecideResponse, DecideError> {
+        let permit = self
+            .request_semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore closed");
+
+        let response = decide_request
+            .response
+            .clone()
+            .map(ProcessorContext::prepare_observed_response)
+            .transpose()?;
+        let request = Arc::new(ProcessorContext::prepare_decide_request(decide_request)?);
+
+        info!(
+            message = "decide request",
+            method = request.method().to_string(),
+            uri = request.uri().to_string(),
+        );
+
+        let mut ctx = self.execute_request_phases(request, permit).await?;
+        if let Some(response) = response {
+            ctx.response = Some(Arc::new(response));
+            ctx.execute_response_phase().await;
+        }
+        Ok(ctx.complete_decide_phase().await)
+    }
+}
+
+// The request and response errors are shared with the external processor, where they also carry stream errors.
+#[allow(clippy::result_large_err)]
+impl ProcessorContext {
+    /// Assembles a [`Request`](bulwark_sdk::Request) from a [`DecideRequest`].
+    fn prepare_decide_request(
+        decide_request: DecideRequest,
+    ) -> Result<bulwark_sdk::Request, DecideError> {
+        let mut request = http::Request::builder()
+            .method(decide_request.method.as_str())
+            .uri(decide_request.uri.as_str());
+        for (name, value) in &decide_request.headers {
+            request = request.header(name, value);
+        }
+        if let Some(client_ip) = decide_request.client_ip {
+            request = request.extension(ForwardedIP(client_ip));
+        }
+        let body = Self::decode_body(decide_request.body)?;
+        Ok(request.body(body).map_err(RequestError::from)?)
+    }
+
+    /// Assembles a [`Response`](bulwark_sdk::Response) from an [`ObservedResponse`].
+    fn prepare_observed_response(
+        observed_response: ObservedResponse,
+    ) -> Result<bulwark_sdk::Response, DecideError> {
+        let mut response = http::Response::builder().status(observed_response.status);
+        for (name, value) in &observed_response.headers {
+            response = response.header(name, value);
+        }
+        let body = Self::decode_body(observed_response.body)?;
+        Ok(response.body(body).map_err(ResponseError::from)?)
+    }
+
+    fn decode_body(body: Option<String>) -> Result<bytes::Bytes, DecideError> {
+        match body {
+            Some(body) => Ok(bytes::Bytes::from(
+                base64::engine::general_purpose::STANDARD.decode(body)?,
+            )),
+            None => Ok(bytes::Bytes::new()),
+        }
+    }
+
+    /// Reports the combined decision without taking any action on it.
+    async fn complete_decide_phase(&mut self) -> DecideResponse {
+        let decision = self.combined_output.decision;
+        let outcome = decision
+            .outcome(
+                self.thresholds.trust,
+                self.thresholds.suspicious,
+                self.thresholds.restrict,
+            )
+            .unwrap();
+        let action = self.actions.action(outcome).clone();
+
+        info!(
+            message = "combine decision",
+            accept = format_f64!(decision.accept),
+            restrict = format_f64!(decision.restrict),
+            unknown = format_f64!(decision.unknown),
+            score = format_f64!(decision.pignistic().restrict),
+            outcome = outcome.to_string(),
+            action = action.to_string(),
+        );
+
+        let mut tags: Vec<String> = self.combined_output.tags.iter().cloned().collect();
+        tags.sort();
+        self.verdict = Some(Verdict {
+            decision,
+            outcome,
+            tags: tags.clone(),
+        });
+        if self.response.is_some() {
+            self.execute_decision_feedback().await;
+        } else {
+            // Without a response there's no decision feedback, but plugin output still needs to be logged.
+            self.capture_stdio().await;
+        }
+
+        DecideResponse {
+            decision: decision.into(),
+            outcome: outcome.to_string(),
+            action,
+            tags,
+            labels: self.combined_output.labels.clone().into_iter().collect(),
+            plugins: self
+                .plugin_outputs
+                .iter()
+                .map(|(reference, output)| (reference.clone(), PluginOutput::from(output)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prepare_decide_request() -> Result<(), Box<dyn std::error::Error>> {
+        let decide_request: DecideRequest = serde_json::from_str(
+            r#"{
+                "method": "POST",
+                "uri": "https://example.com/webhook",
+                "headers": {"content-type": "application/json"},
+                "body": "eyJvayI6dHJ1ZX0=",
+                "client_ip": "192.0.2.10",
+                "response": {"status": 204}
+            }"#,
+        )?;
+        let response =
+            ProcessorContext::prepare_observed_response(decide_request.response.clone().unwrap())?;
+        assert_eq!(response.status(), 204);
+        assert!(response.body().is_empty());
+
+        let request = ProcessorContext::prepare_decide_request(decide_request)?;
+        assert_eq!(request.method(), http::Method::POST);
+        assert_eq!(request.uri(), "https://example.com/webhook");
+        assert_eq!(
+            request.headers().get("content-type").unwrap(),
+            "application/json"
+        );
+        assert_eq!(request.body(), r#"{"ok":true}"#);
+        assert_eq!(
+            request.extensions().get::<ForwardedIP>().unwrap().0,
+            "192.0.2.10".parse::<IpAddr>()?
+        );
+
+        let decide_request: DecideRequest = serde_json::from_str(
+            r#"{"method": "GET", "uri": "https://example.com/", "body": "not base64!"}"#,
+        )?;
+        assert!(matches!(
+            ProcessorContext::prepare_decide_request(decide_request),
+            Err(DecideError::Body(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_decide_response() -> Result<(), Box<dyn std::error::Error>> {
+        let decision = Decision {
+            accept: 0.0,
+            restrict: 0.5,
+            unknown: 0.5,
+        };
+        let decide_response = DecideResponse {
+            decision: decision.into(),
+            outcome: "suspected".to_string(),
+            action: Action::Allow,
+            tags: vec!["bot".to_string()],
+            labels: BTreeMap::new(),
+            plugins: BTreeMap::from([(
+                "evil_bit".to_string(),
+                PluginOutput {
+                    decision: decision.into(),
+                    tags: vec!["bot".to_string()],
+                    labels: BTreeMap::new(),
+                },
+            )]),
+        };
+        assert_eq!(
+            serde_json::to_value(&decide_response)?,
+            serde_json::json!({
+                "decision": {"accept": 0.0, "restrict": 0.5, "unknown": 0.5, "score": 0.75},
+                "outcome": "suspected",
+                "action": {"type": "allow"},
+                "tags": ["bot"],
+                "labels": {},
+                "plugins": {
+                    "evil_bit": {
+                        "decision": {"accept": 0.0, "restrict": 0.5, "unknown": 0.5, "score": 0.75},
+                        "tags": ["bot"],
+                        "labels": {}
+                    }
+                }
+            })
+        );
+
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{ContextInstantiationError, Plugin, PluginStdio};
+use crate::{
+    http_cache::HttpCache, http_client::HttpClient, plugin_metrics::PluginMetrics,
+    ContextInstantiationError, Plugin, PluginStdio,
+};
 
 use chrono::Utc;
 use core::{future::Future, marker::Send, pin::Pin};
@@ -8,6 +11,7 @@ use url::Url;
 use wasmtime::component::Resource;
 use wasmtime_wasi::{ResourceTable, WasiCtx, WasiCtxBuilder, WasiView};
 use wasmtime_wasi_http::{
+    bindings::http::types::ErrorCode,
     types::{HostFutureIncomingResponse, HostIncomingResponse, OutgoingRequest},
     WasiHttpCtx, WasiHttpView,
 };
@@ -22,6 +26,8 @@ pub struct PluginCtx {
     wasi_table: ResourceTable,
     /// The standard I/O buffers used by WASI and captured for logging.
     pub(crate) stdio: PluginStdio,
+    /// The reference of the plugin, attached to the events it logs.
+    reference: String,
     /// All host configuration.
     host_config: Arc<bulwark_config::Config>,
     /// Plugin-specific configuration. Stored as bytes and deserialized as JSON values by the SDK.
@@ -32,6 +38,12 @@ pub struct PluginCtx {
     guest_config: Arc<serde_json::Map<String, serde_json::Value>>,
     /// The set of permissions granted to a plugin.
     permissions: bulwark_config::Permissions,
+    /// The client that sends the plugin's outbound HTTP requests.
+    http_client: Arc<HttpClient>,
+    /// The cache for responses to the plugin's outbound requests, if enabled.
+    http_cache: Option<Arc<HttpCache>>,
+    /// The metrics recorded by the plugin, shared with its other instances for cardinality limits.
+    metrics: Arc<PluginMetrics>,
     /// The Redis connection pool and its associated Lua scripts.
     redis_ctx: RedisCtx,
 }
@@ -176,7 +188,6 @@ impl PluginCtx {
     ///
     /// * `plugin` - The [`Plugin`] and its associated configuration.
     /// * `redis_ctx` - The Redis connection pool.
-    /// * `http_client` - The HTTP client used for outbound requests.
     pub fn new(
         plugin: Arc<Plugin>,
         environment: HashMap<String, String>,
@@ -200,9 +211,13 @@ impl PluginCtx {
             wasi_http: WasiHttpCtx,
             wasi_table: ResourceTable::new(),
             stdio,
+            reference: plugin.reference().to_string(),
             host_config: Arc::new(plugin.host_config().clone()),
             guest_config: Arc::new(plugin.guest_config().clone()),
             permissions: plugin.permissions().clone(),
+            http_client: plugin.http_client(),
+            http_cache: plugin.http_cache(),
+            metrics: plugin.metrics(),
             redis_ctx,
         })
     }
@@ -242,8 +257,20 @@ impl WasiHttpView for PluginCtx {
     where
         Self: Sized,
     {
+        // Error codes are passed back to the plugin, where other errors would trap it.
         verify_http_domains(&self.permissions.http, &request.authority)?;
-        wasmtime_wasi_http::types::default_send_request(self, request)
+        let http_client = self.http_client.clone();
+        let http_cache = self.http_cache.clone();
+        let redis = self.redis_ctx.pool.clone();
+        let handle = wasmtime_wasi::spawn(async move {
+            Ok(match http_cache {
+                Some(http_cache) => http_cache.send(http_client, redis, request).await,
+                None => http_client.send(request).await,
+            })
+        });
+        Ok(self
+            .wasi_table
+            .push(HostFutureIncomingResponse::new(handle))?)
     }
 }
 
@@ -280,18 +307,34 @@ impl crate::bindings::bulwark::plugin::config::Host for PluginCtx {
         Self: 'async_trait,
     {
         Box::pin(async move {
-            let result = self
-                .guest_config
+            self.guest_config
                 .get(key.as_str())
                 .map_or(Ok(None), |value| {
                     // Invert, we need Result<Option<V>, E> rather than Option<Result<V, E>>.
                     // This is also why the map_or default above is Ok(None).
                     value.clone().try_into().map(Some)
-                });
-            Ok(result.expect("config should already be validated"))
+                })
+                // Nested values can't be represented, this traps the plugin rather than hiding the value.
+                .map_err(|err| {
+                    wasmtime::Error::msg(format!(
+                        "config key '{}' must be read with config-json: {}",
+                        key, err
+                    ))
+                })
         })
     }
 
+    /// Returns the entire config as a JSON-encoded object.
+    fn config_json<'ctx, 'async_trait>(
+        &'ctx mut self,
+    ) -> Pin<Box<dyn Future<Output = wasmtime::Result<String>> + Send + 'async_trait>>
+    where
+        'ctx: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move { Ok(serde_json::to_string(&*self.guest_config)?) })
+    }
+
     /// Returns the number of proxy hops expected exterior to Bulwark.
     fn proxy_hops<'ctx, 'async_trait>(
         &'ctx mut self,
@@ -304,6 +347,113 @@ impl crate::bindings::bulwark::plugin::config::Host for PluginCtx {
     }
 }
 
+impl crate::bindings::bulwark::plugin::log::Host for PluginCtx {
+    /// Emits a log event within the span of the plugin execution that produced it.
+    fn emit<'ctx, 'async_trait>(
+        &'ctx mut self,
+        level: crate::bindings::bulwark::plugin::log::Level,
+        message: String,
+        fields: Vec<(String, String)>,
+    ) -> Pin<Box<dyn Future<Output = wasmtime::Result<()>> + Send + 'async_trait>>
+    where
+        'ctx: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            use crate::bindings::bulwark::plugin::log::Level;
+
+            // Tracing field names are static, so the plugin's own fields are carried as a single JSON object.
+            let fields = serde_json::to_string(
+                &fields
+                    .into_iter()
+                    .collect::<std::collections::BTreeMap<_, _>>(),
+            )?;
+            let plugin = self.reference.as_str();
+            let content = message.as_str();
+            let fields = fields.as_str();
+            match level {
+                Level::Trace => tracing::trace!(message = "plugin log", plugin, content, fields),
+                Level::Debug => tracing::debug!(message = "plugin log", plugin, content, fields),
+                Level::Info => tracing::info!(message = "plugin log", plugin, content, fields),
+                Level::Warn => tracing::warn!(message = "plugin log", plugin, content, fields),
+                Level::Error => tracing::error!(message = "plugin log", plugin, content, fields),
+            }
+            Ok(())
+        })
+    }
+}
+
+impl crate::bindings::bulwark::plugin::metrics::Host for PluginCtx {
+    /// Increments the named counter by the given value.
+    fn increment_counter<'ctx, 'async_trait>(
+        &'ctx mut self,
+        name: String,
+        value: u64,
+        labels: Vec<(String, String)>,
+    ) -> Pin<
+        Box<
+            dyn Future<
+                    Output = wasmtime::Result<
+                        Result<(), crate::bindings::bulwark::plugin::metrics::Error>,
+                    >,
+                > + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'ctx: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move { Ok(self.metrics.increment_counter(&name, value, labels)) })
+    }
+
+    /// Sets the named gauge to the given value.
+    fn set_gauge<'ctx, 'async_trait>(
+        &'ctx mut self,
+        name: String,
+        value: f64,
+        labels: Vec<(String, String)>,
+    ) -> Pin<
+        Box<
+            dyn Future<
+                    Output = wasmtime::Result<
+                        Result<(), crate::bindings::bulwark::plugin::metrics::Error>,
+                    >,
+                > + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'ctx: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move { Ok(self.metrics.set_gauge(&name, value, labels)) })
+    }
+
+    /// Records the given value in the named histogram.
+    fn record_histogram<'ctx, 'async_trait>(
+        &'ctx mut self,
+        name: String,
+        value: f64,
+        labels: Vec<(String, String)>,
+    ) -> Pin<
+        Box<
+            dyn Future<
+                    Output = wasmtime::Result<
+                        Result<(), crate::bindings::bulwark::plugin::metrics::Error>,
+                    >,
+                > + Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'ctx: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move { Ok(self.metrics.record_histogram(&name, value, labels)) })
+    }
+}
+
 impl crate::bindings::bulwark::plugin::redis::Host for PluginCtx {
     /// Retrieves the value associated with the given key.
     fn get<'ctx, 'async_trait>(
@@ -1004,17 +1154,15 @@ fn verify_http_domains(
     // TODO: BTreeSet<String> instead, all the way up
     allowed_http_domains: &[String],
     authority: &str,
-) -> Result<(), bulwark_sdk::Error> {
-    let parsed_uri = Url::parse(format!("//{}/", authority).as_str())
-        .map_err(|e| bulwark_sdk::error!("invalid request authority <{}>: {}", authority, e))?;
-    let requested_domain = parsed_uri.domain().ok_or_else(|| {
-        bulwark_sdk::error!("request authority must be a valid dns name <{}>", authority)
-    })?;
+) -> Result<(), ErrorCode> {
+    // The authority has no scheme of its own, but one is needed to parse it as a URL.
+    let parsed_uri = Url::parse(format!("http://{}/", authority).as_str())
+        .map_err(|_| ErrorCode::HttpRequestUriInvalid)?;
+    let requested_domain = parsed_uri
+        .domain()
+        .ok_or(ErrorCode::HttpRequestUriInvalid)?;
     if !allowed_http_domains.contains(&requested_domain.to_string()) {
-        return Err(bulwark_sdk::error!(
-            "missing http permissions <{}>",
-            authority
-        ));
+        return Err(ErrorCode::HttpRequestDenied);
     }
     Ok(())
 }
@@ -1036,3 +1184,27 @@ fn verify_redis_prefixes(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_http_domains() {
+        let allowed = vec!["api.example.com".to_string()];
+        assert!(verify_http_domains(&allowed, "api.example.com").is_ok());
+        assert!(verify_http_domains(&allowed, "api.example.com:8443").is_ok());
+        assert!(matches!(
+            verify_http_domains(&allowed, "example.com"),
+            Err(ErrorCode::HttpRequestDenied)
+        ));
+        assert!(matches!(
+            verify_http_domains(&allowed, "192.0.2.1"),
+            Err(ErrorCode::HttpRequestUriInvalid)
+        ));
+        assert!(matches!(
+            verify_http_domains(&allowed, "api example.com"),
+            Err(ErrorCode::HttpRequestUriInvalid)
+        ));
+    }
+}
@@ -9,12 +9,38 @@ pub enum PluginLoadError {
     CreatePool(#[from] deadpool_redis::CreatePoolError),
     #[error(transparent)]
     Resolution(#[from] bulwark_config::ResolutionError),
-    #[error("at least one resource required")]
-    ResourceMissing,
+    #[error(transparent)]
+    Source(#[from] bulwark_config::PluginSourceError),
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error("digest mismatch for plugin '{reference}': expected '{expected}', found '{actual}'")]
+    DigestMismatch {
+        reference: String,
+        expected: String,
+        actual: String,
+    },
+    #[error(transparent)]
+    ConfigSchema(#[from] ConfigSchemaError),
     #[error(transparent)]
     AnyError(#[from] anyhow::Error),
 }
 
+/// Returned when a plugin's config schema cannot be read or the plugin's config does not satisfy it.
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigSchemaError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error("invalid config schema: {0}")]
+    Schema(String),
+    #[error("invalid config for plugin '{reference}': {}", violations.join(", "))]
+    Violation {
+        reference: String,
+        violations: Vec<String>,
+    },
+}
+
 /// Returned when an attempt to instantiate a plugin fails.
 #[derive(thiserror::Error, Debug)]
 pub enum PluginInstantiationError {
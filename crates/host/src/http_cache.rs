@@ -0,0 +1,588 @@
+use crate::http_client::HttpClient;
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use redis::AsyncCommands;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::time::timeout;
+use wasmtime_wasi_http::{
+    bindings::http::types::ErrorCode,
+    types::{IncomingResponseInternal, OutgoingRequest},
+};
+
+/// Caches responses to a plugin's outbound `GET` requests.
+///
+/// A single `HttpCache` is shared by every instance of a plugin, including the copies loaded for each resource
+/// through a [`PluginRegistry`](crate::PluginRegistry), so responses fetched on one route answer requests made on
+/// another and the memory limit applies to the plugin as a whole. Responses are only cached when the remote host
+/// gives them a `max-age`, capped by the plugin's configured maximum.
+///
+/// The cache key doesn't include the `Authorization` header unless it's configured to, so responses to authorized
+/// requests are only stored when they're marked as shareable, following RFC 9111 section 3.5.
+pub(crate) struct HttpCache {
+    /// The reference of the plugin making requests, used to namespace keys and label metrics.
+    reference: String,
+    /// The plugin's cache configuration.
+    config: bulwark_config::HttpCache,
+    /// Cached responses, for the in-memory backend.
+    memory: Mutex<MemoryCache>,
+}
+
+/// The responses held by the in-memory backend, bounded by their total size.
+///
+/// Once the size limit is reached, the least recently used responses are evicted to make room for new ones.
+/// Expired responses are dropped when they're next looked up, or once they become the least recently used.
+#[derive(Default)]
+struct MemoryCache {
+    /// Cached responses by key.
+    entries: HashMap<String, MemoryEntry>,
+    /// The keys of cached responses, ordered from least to most recently used.
+    recency: BTreeMap<u64, String>,
+    /// Incremented each time a response is used, to order them by recency.
+    clock: u64,
+    /// The total size in bytes of the cached responses.
+    size: u64,
+}
+
+/// A response held by the in-memory backend.
+struct MemoryEntry {
+    cached: CachedResponse,
+    expires_at: Instant,
+    /// The size in bytes counted against the cache's limit.
+    size: u64,
+    /// When the response was last used, as a key into [`MemoryCache::recency`].
+    used: u64,
+}
+
+/// A response held in the cache.
+#[derive(Clone, Debug, PartialEq)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, Vec<u8>)>,
+    body: Bytes,
+}
+
+impl HttpCache {
+    /// Creates a new [`HttpCache`] for a plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The plugin's reference.
+    /// * `config` - The cache configuration from the plugin's configuration.
+    pub(crate) fn new(reference: String, config: bulwark_config::HttpCache) -> Self {
+        Self {
+            reference,
+            config,
+            memory: Mutex::new(MemoryCache::default()),
+        }
+    }
+
+    /// Answers a request from the cache if possible, otherwise sending it with the plugin's [`HttpClient`] and
+    /// caching the response if it allows it.
+    ///
+    /// Cache failures are treated as misses, so an unavailable Redis server never fails a request.
+    ///
+    /// # Arguments
+    ///
+    /// * `http_client` - The plugin's client, used when the cache can't answer the request.
+    /// * `redis` - The Redis connection pool, required by the Redis backend.
+    /// * `request` - The outbound request.
+    pub(crate) async fn send(
+        &self,
+        http_client: Arc<HttpClient>,
+        redis: Option<Arc<deadpool_redis::Pool>>,
+        request: OutgoingRequest,
+    ) -> Result<IncomingResponseInternal, ErrorCode> {
+        if request.request.method() != http::Method::GET {
+            return http_client.send(request).await;
+        }
+
+        let key = self.key(&request.request);
+        let authorized = request
+            .request
+            .headers()
+            .contains_key(http::header::AUTHORIZATION);
+        let bypass = directives(request.request.headers())
+            .any(|directive| directive == "no-cache" || directive == "no-store");
+        if !bypass {
+            if let Some(cached) = self.get(&key, redis.as_deref()).await {
+                metrics::increment_counter!(
+                    "plugin_http_cache",
+                    "ref" => self.reference.clone(), "result" => "hit"
+                );
+                return Ok(cached.into_response(request.between_bytes_timeout));
+            }
+        }
+        metrics::increment_counter!(
+            "plugin_http_cache",
+            "ref" => self.reference.clone(), "result" => "miss"
+        );
+
+        let response = http_client.send(request).await?;
+        let Some(ttl) = cache_ttl(&response.resp, authorized, &self.config) else {
+            return Ok(response);
+        };
+
+        // The body has to be read in full before it can be cached.
+        let between_bytes_timeout = response.between_bytes_timeout;
+        let (parts, mut body) = response.resp.into_parts();
+        let mut buffer = Vec::new();
+        while let Some(frame) = timeout(between_bytes_timeout, body.frame())
+            .await
+            .map_err(|_| ErrorCode::ConnectionReadTimeout)?
+        {
+            if let Ok(data) = frame?.into_data() {
+                buffer.extend_from_slice(&data);
+            }
+        }
+        let cached = CachedResponse {
+            status: parts.status.as_u16(),
+            headers: parts
+                .headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+                .collect(),
+            body: Bytes::from(buffer),
+        };
+        self.put(&key, &cached, ttl, redis.as_deref()).await;
+        Ok(cached.into_response(between_bytes_timeout))
+    }
+
+    /// Derives the cache key from the request's method, URL and any configured headers.
+    fn key<B>(&self, request: &http::Request<B>) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(request.method().as_str());
+        hasher.update(b"\n");
+        hasher.update(request.uri().to_string());
+        for name in &self.config.headers {
+            hasher.update(b"\n");
+            hasher.update(name);
+            hasher.update(b":");
+            for value in request.headers().get_all(name) {
+                hasher.update(value.as_bytes());
+                hasher.update(b",");
+            }
+        }
+        format!("bulwark:http:{}:{:x}", self.reference, hasher.finalize())
+    }
+
+    async fn get(&self, key: &str, redis: Option<&deadpool_redis::Pool>) -> Option<CachedResponse> {
+        match self.config.backend {
+            bulwark_config::HttpCacheBackend::Memory => {
+                self.memory.lock().expect("poisoned mutex").get(key)
+            }
+            bulwark_config::HttpCacheBackend::Redis => {
+                let mut conn = redis?.get().await.ok()?;
+                let mut fields: HashMap<String, Vec<u8>> = conn.hgetall(key).await.ok()?;
+                CachedResponse::decode(&mut fields)
+            }
+        }
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        cached: &CachedResponse,
+        ttl: Duration,
+        redis: Option<&deadpool_redis::Pool>,
+    ) {
+        match self.config.backend {
+            bulwark_config::HttpCacheBackend::Memory => {
+                self.memory.lock().expect("poisoned mutex").insert(
+                    key,
+                    cached,
+                    Instant::now() + ttl,
+                    self.config.max_memory_size,
+                );
+            }
+            bulwark_config::HttpCacheBackend::Redis => {
+                let Some(pool) = redis else {
+                    return;
+                };
+                if let Ok(mut conn) = pool.get().await {
+                    // Failing to cache a response doesn't affect the request.
+                    let _: Result<(), _> = redis::pipe()
+                        .atomic()
+                        .del(key)
+                        .ignore()
+                        .hset_multiple(key, &cached.encode())
+                        .ignore()
+                        .expire(key, ttl.as_secs() as i64)
+                        .ignore()
+                        .query_async(&mut *conn)
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+impl MemoryCache {
+    /// Returns an unexpired response, marking it as the most recently used.
+    fn get(&mut self, key: &str) -> Option<CachedResponse> {
+        let entry = self.entries.get_mut(key)?;
+        if entry.expires_at <= Instant::now() {
+            self.remove(key);
+            return None;
+        }
+        self.recency.remove(&entry.used);
+        self.clock += 1;
+        entry.used = self.clock;
+        self.recency.insert(self.clock, key.to_string());
+        Some(entry.cached.clone())
+    }
+
+    /// Stores a response, evicting the least recently used responses until it fits within `max_size` bytes.
+    ///
+    /// Responses larger than `max_size` on their own aren't stored.
+    fn insert(&mut self, key: &str, cached: &CachedResponse, expires_at: Instant, max_size: u64) {
+        self.remove(key);
+        let size = (key.len() + cached.size()) as u64;
+        if size > max_size {
+            return;
+        }
+        while self.size + size > max_size {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.size -= entry.size;
+            }
+        }
+        self.clock += 1;
+        self.size += size;
+        self.recency.insert(self.clock, key.to_string());
+        self.entries.insert(
+            key.to_string(),
+            MemoryEntry {
+                cached: cached.clone(),
+                expires_at,
+                size,
+                used: self.clock,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.used);
+            self.size -= entry.size;
+        }
+    }
+}
+
+impl CachedResponse {
+    /// The size in bytes of the response's headers and body.
+    fn size(&self) -> usize {
+        self.headers
+            .iter()
+            .map(|(name, value)| name.len() + value.len())
+            .sum::<usize>()
+            + self.body.len()
+    }
+
+    /// Encodes the response as the fields of a Redis hash.
+    fn encode(&self) -> [(&'static str, Vec<u8>); 3] {
+        let mut headers = Vec::new();
+        for (name, value) in &self.headers {
+            headers.extend_from_slice(name.as_bytes());
+            headers.extend_from_slice(b": ");
+            headers.extend_from_slice(value);
+            headers.extend_from_slice(b"\r\n");
+        }
+        [
+            ("status", self.status.to_string().into_bytes()),
+            ("headers", headers),
+            ("body", self.body.to_vec()),
+        ]
+    }
+
+    /// Decodes a response from the fields of a Redis hash, returning `None` if the hash is missing or malformed.
+    fn decode(fields: &mut HashMap<String, Vec<u8>>) -> Option<Self> {
+        let status = std::str::from_utf8(fields.get("status")?)
+            .ok()?
+            .parse()
+            .ok()?;
+        let mut headers = Vec::new();
+        for line in fields.get("headers")?.split(|byte| *byte == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+            let separator = line.windows(2).position(|window| window == b": ")?;
+            headers.push((
+                String::from_utf8(line[..separator].to_vec()).ok()?,
+                line[separator + 2..].to_vec(),
+            ));
+        }
+        Some(Self {
+            status,
+            headers,
+            body: Bytes::from(fields.remove("body")?),
+        })
+    }
+
+    /// Converts the cached response into the form returned by an outgoing request.
+    fn into_response(self, between_bytes_timeout: Duration) -> IncomingResponseInternal {
+        let mut builder = http::Response::builder().status(self.status);
+        for (name, value) in self.headers {
+            builder = builder.header(name, value);
+        }
+        let resp = builder
+            .body(Full::new(self.body).map_err(|_| unreachable!()).boxed())
+            .expect("cached responses are valid");
+        IncomingResponseInternal {
+            resp,
+            // There's no connection behind a cached response.
+            worker: Arc::new(wasmtime_wasi::spawn(async {})),
+            between_bytes_timeout,
+        }
+    }
+}
+
+/// Returns the lowercased `Cache-Control` directives in a set of headers.
+fn directives(headers: &http::HeaderMap) -> impl Iterator<Item = String> + '_ {
+    headers
+        .get_all(http::header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.trim().to_ascii_lowercase())
+}
+
+/// Determines how long a response may be cached for, if at all.
+///
+/// Responses need a `max-age` or `s-maxage` directive to be cached, less any `Age` they already have, and are
+/// never cached for longer than the configured maximum. Responses that vary on headers outside the cache key aren't
+/// cached. When the request was `authorized` and its `Authorization` header isn't part of the cache key, the
+/// response also has to be `public` or have an `s-maxage`.
+fn cache_ttl<B>(
+    response: &http::Response<B>,
+    authorized: bool,
+    config: &bulwark_config::HttpCache,
+) -> Option<Duration> {
+    let mut max_age = None;
+    let mut shared_max_age = None;
+    let mut public = false;
+    for directive in directives(response.headers()) {
+        match directive.split_once('=') {
+            Some(("max-age", value)) => max_age = value.trim_matches('"').parse::<u64>().ok(),
+            Some(("s-maxage", value)) => {
+                shared_max_age = value.trim_matches('"').parse::<u64>().ok()
+            }
+            None if matches!(directive.as_str(), "no-store" | "no-cache" | "private") => {
+                return None
+            }
+            None if directive == "public" => public = true,
+            _ => {}
+        }
+    }
+    let keyed_on_authorization = config
+        .headers
+        .iter()
+        .any(|name| name == http::header::AUTHORIZATION.as_str());
+    if authorized && !keyed_on_authorization && !public && shared_max_age.is_none() {
+        return None;
+    }
+    let varies_outside_key = response
+        .headers()
+        .get_all(http::header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .any(|name| !config.headers.contains(&name));
+    if varies_outside_key {
+        return None;
+    }
+    let age = response
+        .headers()
+        .get(http::header::AGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(0);
+    let ttl = shared_max_age
+        .or(max_age)?
+        .saturating_sub(age)
+        .min(config.max_ttl);
+    (ttl > 0).then(|| Duration::from_secs(ttl))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_client::tests::{request, serve};
+    use std::sync::atomic::Ordering;
+
+    fn config(headers: &[&str]) -> bulwark_config::HttpCache {
+        bulwark_config::HttpCache {
+            max_ttl: 60,
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            backend: bulwark_config::HttpCacheBackend::Memory,
+            max_memory_size: bulwark_config::DEFAULT_HTTP_CACHE_MAX_MEMORY_SIZE,
+        }
+    }
+
+    fn response(headers: &[(&str, &str)]) -> http::Response<()> {
+        let mut builder = http::Response::builder();
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_cache_ttl() {
+        let config = config(&["accept"]);
+        let ttl = |headers| cache_ttl(&response(headers), false, &config).map(|ttl| ttl.as_secs());
+
+        assert_eq!(ttl(&[]), None);
+        assert_eq!(ttl(&[("cache-control", "public, max-age=30")]), Some(30));
+        assert_eq!(
+            ttl(&[("cache-control", "max-age=30, s-maxage=10")]),
+            Some(10)
+        );
+        assert_eq!(ttl(&[("cache-control", "max-age=3600")]), Some(60));
+        assert_eq!(
+            ttl(&[("cache-control", "max-age=30"), ("age", "20")]),
+            Some(10)
+        );
+        assert_eq!(ttl(&[("cache-control", "max-age=30"), ("age", "40")]), None);
+        assert_eq!(ttl(&[("cache-control", "max-age=30, no-store")]), None);
+        assert_eq!(ttl(&[("cache-control", "private, max-age=30")]), None);
+        assert_eq!(
+            ttl(&[("cache-control", "max-age=30"), ("vary", "Accept")]),
+            Some(30)
+        );
+        assert_eq!(
+            ttl(&[("cache-control", "max-age=30"), ("vary", "Cookie")]),
+            None
+        );
+        assert_eq!(ttl(&[("cache-control", "max-age=30"), ("vary", "*")]), None);
+    }
+
+    #[test]
+    fn test_cache_ttl_authorized() {
+        let config = config(&[]);
+        let ttl = |headers| cache_ttl(&response(headers), true, &config).map(|ttl| ttl.as_secs());
+
+        assert_eq!(ttl(&[("cache-control", "max-age=30")]), None);
+        assert_eq!(ttl(&[("cache-control", "public, max-age=30")]), Some(30));
+        assert_eq!(ttl(&[("cache-control", "s-maxage=30")]), Some(30));
+
+        // Responses may be cached per credential when the header is part of the key.
+        let config = self::config(&["authorization"]);
+        assert_eq!(
+            cache_ttl(&response(&[("cache-control", "max-age=30")]), true, &config),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn test_memory_cache_eviction() {
+        let cached = |body: &'static [u8]| CachedResponse {
+            status: 200,
+            headers: vec![],
+            body: Bytes::from_static(body),
+        };
+        let expires_at = Instant::now() + Duration::from_secs(60);
+        let mut memory = MemoryCache::default();
+
+        // Each entry takes up 10 bytes: a 1 byte key and a 9 byte body.
+        memory.insert("a", &cached(b"aaaaaaaaa"), expires_at, 25);
+        memory.insert("b", &cached(b"bbbbbbbbb"), expires_at, 25);
+        assert_eq!(memory.size, 20);
+        // Using `a` leaves `b` as the least recently used.
+        assert!(memory.get("a").is_some());
+        memory.insert("c", &cached(b"ccccccccc"), expires_at, 25);
+        assert!(memory.get("b").is_none());
+        assert!(memory.get("a").is_some());
+        assert!(memory.get("c").is_some());
+        assert_eq!(memory.size, 20);
+
+        // Replacing an entry doesn't count it twice.
+        memory.insert("c", &cached(b"ccccccccc"), expires_at, 25);
+        assert_eq!(memory.size, 20);
+        assert_eq!(memory.entries.len(), memory.recency.len());
+
+        // Responses that could never fit aren't stored, and don't evict anything.
+        memory.insert("d", &cached(&[0; 25]), expires_at, 25);
+        assert!(memory.get("d").is_none());
+        assert_eq!(memory.size, 20);
+
+        // Expired responses are dropped when they're looked up.
+        memory.insert("e", &cached(b"e"), Instant::now(), 25);
+        assert!(memory.get("e").is_none());
+        assert!(!memory.entries.contains_key("e"));
+    }
+
+    #[test]
+    fn test_cached_response_encoding() {
+        let cached = CachedResponse {
+            status: 200,
+            headers: vec![
+                ("content-type".to_string(), b"application/json".to_vec()),
+                ("x-empty".to_string(), vec![]),
+            ],
+            body: Bytes::from_static(b"{\"score\":\r\n0.5}"),
+        };
+        let mut fields = cached
+            .encode()
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        assert_eq!(CachedResponse::decode(&mut fields), Some(cached));
+        assert_eq!(CachedResponse::decode(&mut HashMap::new()), None);
+    }
+
+    #[tokio::test]
+    async fn test_http_cache() -> Result<(), ErrorCode> {
+        let server =
+            serve("HTTP/1.1 200 OK\r\ncache-control: max-age=60\r\ncontent-length: 5\r\n\r\nhello")
+                .await;
+        let http_client = Arc::new(HttpClient::new(
+            "example".to_string(),
+            bulwark_config::HttpClient::default(),
+        ));
+        let http_cache = HttpCache::new("example".to_string(), config(&[]));
+        let send = |request| http_cache.send(http_client.clone(), None, request);
+
+        for _ in 0..2 {
+            let response = send(request(&server.authority, Duration::from_secs(5))).await?;
+            assert_eq!(response.resp.status(), 200);
+            assert_eq!(response.resp.headers()["cache-control"], "max-age=60");
+            let body = response.resp.into_body().collect().await?.to_bytes();
+            assert_eq!(body, "hello");
+        }
+        assert_eq!(server.requests.load(Ordering::SeqCst), 1);
+
+        // Plugins may ask for a fresh response.
+        let mut fresh = request(&server.authority, Duration::from_secs(5));
+        fresh.request.headers_mut().insert(
+            http::header::CACHE_CONTROL,
+            http::HeaderValue::from_static("no-cache"),
+        );
+        send(fresh).await?;
+        assert_eq!(server.requests.load(Ordering::SeqCst), 2);
+
+        // Responses to authorized requests aren't shared unless they're public.
+        for expected in [3, 4] {
+            let mut authorized = request(&server.authority, Duration::from_secs(5));
+            *authorized.request.uri_mut() = format!("http://{}/private", server.authority)
+                .parse()
+                .unwrap();
+            authorized.request.headers_mut().insert(
+                http::header::AUTHORIZATION,
+                http::HeaderValue::from_static("Bearer secret"),
+            );
+            send(authorized).await?;
+            assert_eq!(server.requests.load(Ordering::SeqCst), expected);
+        }
+        Ok(())
+    }
+}
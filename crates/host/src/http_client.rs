@@ -0,0 +1,588 @@
+use bytes::Bytes;
+use http_body::{Body, Frame, SizeHint};
+use http_body_util::BodyExt;
+use hyper::client::conn::http1::SendRequest;
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tokio::{
+    net::{lookup_host, TcpStream},
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::timeout,
+};
+use tokio_rustls::{rustls, TlsConnector};
+use wasmtime_wasi::AbortOnDropJoinHandle;
+use wasmtime_wasi_http::{
+    bindings::http::types::{DnsErrorPayload, ErrorCode},
+    body::{HyperIncomingBody, HyperOutgoingBody},
+    hyper_request_error,
+    io::TokioIo,
+    types::{IncomingResponseInternal, OutgoingRequest},
+};
+
+/// How long a connection may sit idle in the pool before it's closed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// The largest number of idle connections kept open to any one host. Connections beyond it are closed once their
+/// response has been read.
+const MAX_IDLE_PER_HOST: usize = 8;
+
+/// Sends a plugin's outbound HTTP requests, enforcing the limits in its configuration.
+///
+/// A single `HttpClient` is shared by every instance of a plugin, including the copies loaded for each resource
+/// through a [`PluginRegistry`](crate::PluginRegistry), so that connections to the same host may be reused across
+/// requests and so that the concurrency limit applies to the plugin as a whole.
+///
+/// Connections are pooled here rather than through `hyper_util`'s legacy `Client` because each response body has
+/// to hold on to the plugin's concurrency permit and enforce its size limit, and `wasmtime_wasi_http` needs the
+/// task driving the connection to hand back to the guest. Only HTTP/1.1 is spoken: plugins make small requests to
+/// a handful of hosts, so multiplexing over HTTP/2 would gain little over a pool of keep-alive connections.
+pub(crate) struct HttpClient {
+    /// The reference of the plugin making requests, used to label metrics.
+    reference: String,
+    /// The limits applied to the plugin's requests.
+    limits: bulwark_config::HttpClient,
+    /// Bounds the number of requests in flight. A permit is held until the response body is complete.
+    permits: Arc<Semaphore>,
+    /// Idle connections, keyed by whether they use TLS and the authority they're connected to, oldest first.
+    idle: Mutex<HashMap<(bool, String), Vec<IdleConnection>>>,
+    /// How long a connection may sit idle in the pool before it's closed.
+    idle_timeout: Duration,
+    /// The largest number of idle connections kept for each key in `idle`.
+    max_idle_per_host: usize,
+    /// Wraps connections to `https` hosts in TLS.
+    tls: TlsConnector,
+}
+
+/// A connection waiting in the pool for its next request.
+struct IdleConnection {
+    connection: Connection,
+    /// When the connection was returned to the pool.
+    since: Instant,
+}
+
+/// An open HTTP/1.1 connection.
+struct Connection {
+    /// Sends requests over the connection.
+    sender: SendRequest<HyperOutgoingBody>,
+    /// The task driving the connection. The connection is closed once every reference to it is dropped.
+    worker: Arc<AbortOnDropJoinHandle<()>>,
+}
+
+impl HttpClient {
+    /// Creates a new [`HttpClient`] for a plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The plugin's reference.
+    /// * `limits` - The outbound HTTP limits from the plugin's configuration.
+    pub(crate) fn new(reference: String, limits: bulwark_config::HttpClient) -> Self {
+        let mut root_store = rustls::RootCertStore::empty();
+        root_store.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        let tls_config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+
+        Self {
+            reference,
+            permits: Arc::new(Semaphore::new(limits.max_concurrent_requests)),
+            limits,
+            idle: Mutex::new(HashMap::new()),
+            idle_timeout: IDLE_TIMEOUT,
+            max_idle_per_host: MAX_IDLE_PER_HOST,
+            tls: TlsConnector::from(Arc::new(tls_config)),
+        }
+    }
+
+    /// Sends a request, returning once the response headers have been received.
+    ///
+    /// The timeouts requested by the plugin are capped by the configured limits. Waiting for the response body is
+    /// bounded by the first byte timeout as well. Responses larger than the configured maximum fail with
+    /// [`ErrorCode::HttpResponseBodySize`].
+    pub(crate) async fn send(
+        self: Arc<Self>,
+        request: OutgoingRequest,
+    ) -> Result<IncomingResponseInternal, ErrorCode> {
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| ErrorCode::InternalError(Some("http client closed".to_string())))?;
+
+        let host = request
+            .request
+            .uri()
+            .host()
+            .unwrap_or(&request.authority)
+            .to_string();
+        let start = Instant::now();
+        let result = self.clone().send_permitted(request, permit).await;
+        metrics::histogram!(
+            "plugin_http_request_duration",
+            start.elapsed(),
+            "ref" => self.reference.clone(),
+            "host" => host,
+            "result" => if result.is_ok() { "ok" } else { "error" }
+        );
+        result
+    }
+
+    /// Sends a request once a concurrency permit has been acquired.
+    async fn send_permitted(
+        self: Arc<Self>,
+        OutgoingRequest {
+            use_tls,
+            authority,
+            mut request,
+            connect_timeout,
+            first_byte_timeout,
+            between_bytes_timeout,
+        }: OutgoingRequest,
+        permit: OwnedSemaphorePermit,
+    ) -> Result<IncomingResponseInternal, ErrorCode> {
+        let max_first_byte_timeout = Duration::from_millis(self.limits.first_byte_timeout);
+        let connect_timeout =
+            connect_timeout.min(Duration::from_millis(self.limits.connect_timeout));
+        let first_byte_timeout = first_byte_timeout.min(max_first_byte_timeout);
+        let between_bytes_timeout = between_bytes_timeout.min(max_first_byte_timeout);
+
+        let key = (use_tls, authority);
+        let mut connection = timeout(connect_timeout, self.connection(&key))
+            .await
+            .map_err(|_| ErrorCode::ConnectionTimeout)??;
+
+        // The request only includes the scheme and authority when addressing a proxy.
+        *request.uri_mut() = http::Uri::builder()
+            .path_and_query(
+                request
+                    .uri()
+                    .path_and_query()
+                    .map(|path| path.as_str())
+                    .unwrap_or("/"),
+            )
+            .build()
+            .map_err(|_| ErrorCode::HttpRequestUriInvalid)?;
+
+        let response = timeout(first_byte_timeout, connection.sender.send_request(request))
+            .await
+            .map_err(|_| ErrorCode::ConnectionReadTimeout)?
+            .map_err(hyper_request_error)?;
+
+        let max_size = self.limits.max_response_size;
+        let content_length = response
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if let Some(content_length) = content_length.filter(|length| *length > max_size) {
+            return Err(ErrorCode::HttpResponseBodySize(Some(content_length)));
+        }
+
+        let worker = connection.worker.clone();
+        let resp = response.map(|body| {
+            ResponseBody {
+                inner: body.map_err(hyper_request_error).boxed(),
+                received: 0,
+                max_size,
+                finished: false,
+                connection: Some(connection),
+                key,
+                client: self,
+                _permit: permit,
+            }
+            .boxed()
+        });
+        Ok(IncomingResponseInternal {
+            resp,
+            worker,
+            between_bytes_timeout,
+        })
+    }
+
+    /// Takes an idle connection from the pool, or opens a new one if there are none.
+    async fn connection(&self, key: &(bool, String)) -> Result<Connection, ErrorCode> {
+        loop {
+            let idle = self
+                .idle
+                .lock()
+                .expect("poisoned mutex")
+                .get_mut(key)
+                .and_then(Vec::pop);
+            let Some(IdleConnection {
+                mut connection,
+                since,
+            }) = idle
+            else {
+                break;
+            };
+            // Connections closed by the remote host since they were pooled are discarded, as are any that have
+            // outlived the idle timeout but haven't been closed yet.
+            if since.elapsed() < self.idle_timeout && connection.sender.ready().await.is_ok() {
+                return Ok(connection);
+            }
+        }
+
+        let (use_tls, authority) = key;
+        // Resolving separately from connecting distinguishes lookup failures from refused connections.
+        let addrs = lookup_host(authority.as_str())
+            .await
+            .map_err(|_| dns_error())?
+            .collect::<Vec<_>>();
+        if addrs.is_empty() {
+            return Err(dns_error());
+        }
+        let stream = TcpStream::connect(addrs.as_slice())
+            .await
+            .map_err(|_| ErrorCode::ConnectionRefused)?;
+        if *use_tls {
+            let host = authority
+                .parse::<http::uri::Authority>()
+                .map_err(|_| ErrorCode::HttpRequestUriInvalid)?;
+            let server_name =
+                rustls::ServerName::try_from(host.host().trim_matches(|c| c == '[' || c == ']'))
+                    .map_err(|_| dns_error())?;
+            let stream = self
+                .tls
+                .connect(server_name, stream)
+                .await
+                .map_err(|_| ErrorCode::TlsProtocolError)?;
+            handshake(TokioIo::new(stream)).await
+        } else {
+            handshake(TokioIo::new(stream)).await
+        }
+    }
+
+    /// Returns a connection to the pool once its response has been fully read.
+    ///
+    /// The connection is closed instead if the pool already holds as many connections to the host as it may, and
+    /// is closed later if it's still idle once the idle timeout has passed.
+    fn release(self: &Arc<Self>, key: (bool, String), connection: Connection) {
+        if connection.sender.is_closed() {
+            return;
+        }
+        let mut idle = self.idle.lock().expect("poisoned mutex");
+        let pooled = idle.entry(key.clone()).or_default();
+        if pooled.len() >= self.max_idle_per_host {
+            return;
+        }
+        pooled.push(IdleConnection {
+            connection,
+            since: Instant::now(),
+        });
+        drop(idle);
+
+        // The client isn't kept alive just to close its connections.
+        let client = Arc::downgrade(self);
+        let idle_timeout = self.idle_timeout;
+        tokio::spawn(async move {
+            tokio::time::sleep(idle_timeout).await;
+            if let Some(client) = Weak::upgrade(&client) {
+                client.expire(&key);
+            }
+        });
+    }
+
+    /// Closes the connections to a host that have been idle for longer than the idle timeout.
+    fn expire(&self, key: &(bool, String)) {
+        let mut idle = self.idle.lock().expect("poisoned mutex");
+        if let Some(pooled) = idle.get_mut(key) {
+            pooled.retain(|connection| connection.since.elapsed() < self.idle_timeout);
+            if pooled.is_empty() {
+                idle.remove(key);
+            }
+        }
+    }
+}
+
+/// Performs the HTTP/1.1 handshake over a newly opened connection.
+async fn handshake<T>(io: T) -> Result<Connection, ErrorCode>
+where
+    T: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+{
+    let (sender, conn) = hyper::client::conn::http1::handshake(io)
+        .await
+        .map_err(hyper_request_error)?;
+    let worker = wasmtime_wasi::spawn(async move {
+        // Errors on the connection surface through the requests made over it.
+        let _ = conn.await;
+    });
+    Ok(Connection {
+        sender,
+        worker: Arc::new(worker),
+    })
+}
+
+fn dns_error() -> ErrorCode {
+    ErrorCode::DnsError(DnsErrorPayload {
+        rcode: Some("address not available".to_string()),
+        info_code: Some(0),
+    })
+}
+
+/// A response body that enforces the maximum response size.
+///
+/// Holds on to the request's concurrency permit and its connection, returning the connection to the pool if the
+/// body was read to completion.
+struct ResponseBody {
+    inner: HyperIncomingBody,
+    received: u64,
+    max_size: u64,
+    finished: bool,
+    connection: Option<Connection>,
+    key: (bool, String),
+    client: Arc<HttpClient>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl Body for ResponseBody {
+    type Data = Bytes;
+    type Error = ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let frame = match Pin::new(&mut self.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => frame,
+            Poll::Ready(None) => {
+                self.finished = true;
+                return Poll::Ready(None);
+            }
+            other => return other,
+        };
+        if let Some(data) = frame.data_ref() {
+            self.received += data.len() as u64;
+            if self.received > self.max_size {
+                // The rest of the body is never read, so the connection can't be reused.
+                self.connection = None;
+                return Poll::Ready(Some(Err(ErrorCode::HttpResponseBodySize(Some(
+                    self.received,
+                )))));
+            }
+        }
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for ResponseBody {
+    fn drop(&mut self) {
+        if self.finished || self.inner.is_end_stream() {
+            if let Some(connection) = self.connection.take() {
+                self.client.release(self.key.clone(), connection);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use http_body_util::Empty;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A test server, along with counts of the connections and requests it has accepted.
+    pub(crate) struct Server {
+        pub(crate) authority: String,
+        pub(crate) connections: Arc<AtomicUsize>,
+        pub(crate) requests: Arc<AtomicUsize>,
+    }
+
+    /// Serves `response` to every request on every connection. An empty response is never sent.
+    pub(crate) async fn serve(response: &'static str) -> Server {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = Server {
+            authority: listener.local_addr().unwrap().to_string(),
+            connections: Arc::new(AtomicUsize::new(0)),
+            requests: Arc::new(AtomicUsize::new(0)),
+        };
+        let (connections, requests) = (server.connections.clone(), server.requests.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                connections.fetch_add(1, Ordering::SeqCst);
+                let requests = requests.clone();
+                tokio::spawn(async move {
+                    let mut buffer = Vec::new();
+                    let mut chunk = [0; 1024];
+                    loop {
+                        match stream.read(&mut chunk).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+                        }
+                        // Requests in these tests have no body, so each ends with a blank line.
+                        while let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                            buffer.drain(..end + 4);
+                            requests.fetch_add(1, Ordering::SeqCst);
+                            if !response.is_empty() {
+                                stream.write_all(response.as_bytes()).await.unwrap();
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        server
+    }
+
+    pub(crate) fn request(authority: &str, timeout: Duration) -> OutgoingRequest {
+        OutgoingRequest {
+            use_tls: false,
+            authority: authority.to_string(),
+            request: http::Request::get(format!("http://{}/", authority))
+                .header(http::header::HOST, authority)
+                .body(Empty::new().map_err(|_| unreachable!()).boxed())
+                .unwrap(),
+            connect_timeout: timeout,
+            first_byte_timeout: timeout,
+            between_bytes_timeout: timeout,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_client_pooling() -> Result<(), ErrorCode> {
+        let server = serve("HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello").await;
+        let client = Arc::new(HttpClient::new(
+            "example".to_string(),
+            bulwark_config::HttpClient::default(),
+        ));
+
+        for _ in 0..3 {
+            let response = client
+                .clone()
+                .send(request(&server.authority, Duration::from_secs(5)))
+                .await?;
+            assert_eq!(response.resp.status(), 200);
+            let body = response.resp.into_body().collect().await?.to_bytes();
+            assert_eq!(body, "hello");
+        }
+        assert_eq!(server.requests.load(Ordering::SeqCst), 3);
+        assert_eq!(server.connections.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_http_client_idle_connections() -> Result<(), ErrorCode> {
+        let server = serve("HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello").await;
+        let mut client =
+            HttpClient::new("example".to_string(), bulwark_config::HttpClient::default());
+        client.idle_timeout = Duration::from_millis(100);
+        client.max_idle_per_host = 2;
+        let client = Arc::new(client);
+        let idle = |client: &HttpClient| {
+            client
+                .idle
+                .lock()
+                .unwrap()
+                .values()
+                .map(Vec::len)
+                .sum::<usize>()
+        };
+
+        // Holding on to every response forces each request onto its own connection.
+        let mut responses = Vec::new();
+        for _ in 0..3 {
+            responses.push(
+                client
+                    .clone()
+                    .send(request(&server.authority, Duration::from_secs(5)))
+                    .await?,
+            );
+        }
+        for response in responses {
+            response.resp.into_body().collect().await?;
+        }
+        assert_eq!(server.connections.load(Ordering::SeqCst), 3);
+        assert_eq!(idle(&client), 2);
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(idle(&client), 0);
+        assert!(client.idle.lock().unwrap().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_http_client_connect_errors() {
+        let client = Arc::new(HttpClient::new(
+            "example".to_string(),
+            bulwark_config::HttpClient::default(),
+        ));
+        assert!(matches!(
+            client
+                .clone()
+                .send(request("bulwark.invalid:80", Duration::from_secs(5)))
+                .await,
+            Err(ErrorCode::DnsError(_))
+        ));
+
+        // Nothing is listening on the port once the listener has been dropped.
+        let authority = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+        assert!(matches!(
+            client
+                .send(request(&authority, Duration::from_secs(5)))
+                .await,
+            Err(ErrorCode::ConnectionRefused)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_client_limits() -> Result<(), ErrorCode> {
+        let server = serve("HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\nhello").await;
+        let client = Arc::new(HttpClient::new(
+            "example".to_string(),
+            bulwark_config::HttpClient {
+                max_response_size: 4,
+                ..Default::default()
+            },
+        ));
+        assert!(matches!(
+            client
+                .send(request(&server.authority, Duration::from_secs(5)))
+                .await,
+            Err(ErrorCode::HttpResponseBodySize(Some(5)))
+        ));
+
+        // The configured timeout applies even when the plugin asks for a longer one.
+        let server = serve("").await;
+        let client = Arc::new(HttpClient::new(
+            "example".to_string(),
+            bulwark_config::HttpClient {
+                first_byte_timeout: 50,
+                ..Default::default()
+            },
+        ));
+        assert!(matches!(
+            client
+                .send(request(&server.authority, Duration::from_secs(60)))
+                .await,
+            Err(ErrorCode::ConnectionReadTimeout)
+        ));
+        Ok(())
+    }
+}
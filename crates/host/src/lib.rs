@@ -1,8 +1,15 @@
 mod context;
 mod errors;
 mod from;
+mod http_cache;
+mod http_client;
 mod plugin;
+mod plugin_metrics;
+mod registry;
+mod schema;
 
 pub use context::*;
 pub use errors::*;
 pub use plugin::*;
+pub use registry::*;
+pub use schema::*;
@@ -28,10 +28,14 @@ pub(crate) mod bindings {
 }
 
 use {
-    crate::PluginCtx,
+    crate::{
+        http_cache::HttpCache, http_client::HttpClient, plugin_metrics::PluginMetrics,
+        ConfigSchema, ConfigSchemaError, PluginCtx, PluginRegistry,
+    },
     crate::{PluginExecutionError, PluginInstantiationError, PluginLoadError},
     bulwark_sdk::Decision,
     http_body_util::{combinators::BoxBody, BodyExt, Empty, Full},
+    sha2::{Digest, Sha256},
     std::{
         collections::{HashMap, HashSet},
         net::IpAddr,
@@ -95,6 +99,9 @@ pub struct Plugin {
     guest_config: Arc<bulwark_config::Plugin>,
     engine: Engine,
     component: Component,
+    http_client: Arc<HttpClient>,
+    http_cache: Option<Arc<HttpCache>>,
+    metrics: Arc<PluginMetrics>,
 }
 
 impl Plugin {
@@ -110,6 +117,7 @@ impl Plugin {
             name,
             host_config,
             guest_config,
+            &PluginRegistry::default(),
             |engine| -> Result<Component, PluginLoadError> {
                 Ok(Component::new(engine, wat.as_bytes())?)
             },
@@ -119,6 +127,8 @@ impl Plugin {
     /// Creates and compiles a new [`Plugin`] from a byte slice of WASM.
     ///
     /// The bytes it expects are what you'd get if you read in a `*.wasm` file.
+    /// Fails if the bytes do not match the [`sha256`](bulwark_config::Plugin::sha256) digest in the plugin's
+    /// configuration, or if the plugin embeds a [`ConfigSchema`] that its configuration does not satisfy.
     /// See [`Component::from_binary`].
     pub fn from_bytes(
         name: String,
@@ -126,10 +136,30 @@ impl Plugin {
         host_config: &bulwark_config::Config,
         guest_config: &bulwark_config::Plugin,
     ) -> Result<Self, PluginLoadError> {
+        Self::from_bytes_in(
+            name,
+            bytes,
+            host_config,
+            guest_config,
+            &PluginRegistry::default(),
+        )
+    }
+
+    /// Helper for [`Plugin::from_bytes`] that shares host state through a [`PluginRegistry`].
+    fn from_bytes_in(
+        name: String,
+        bytes: &[u8],
+        host_config: &bulwark_config::Config,
+        guest_config: &bulwark_config::Plugin,
+        registry: &PluginRegistry,
+    ) -> Result<Self, PluginLoadError> {
+        verify_digest(bytes, guest_config)?;
+        verify_config(ConfigSchema::from_wasm(bytes)?, guest_config)?;
         Self::from_component(
             name,
             host_config,
             guest_config,
+            registry,
             |engine| -> Result<Component, PluginLoadError> {
                 Ok(Component::from_binary(engine, bytes)?)
             },
@@ -138,28 +168,79 @@ impl Plugin {
 
     /// Creates and compiles a new [`Plugin`] by reading in a file in either `*.wasm` or `*.wat` format.
     ///
-    /// See [`Component::from_file`].
+    /// Fails if the file contents do not match the [`sha256`](bulwark_config::Plugin::sha256) digest in the
+    /// plugin's configuration, or if the plugin's configuration does not satisfy its [`ConfigSchema`]. A sidecar
+    /// schema file takes priority over a schema embedded in the WASM. See [`Component::new`].
     pub fn from_file(
         path: impl AsRef<Path>,
         host_config: &bulwark_config::Config,
         guest_config: &bulwark_config::Plugin,
+    ) -> Result<Self, PluginLoadError> {
+        Self::from_file_in(path, host_config, guest_config, &PluginRegistry::default())
+    }
+
+    /// Helper for [`Plugin::from_file`] that shares host state through a [`PluginRegistry`].
+    fn from_file_in(
+        path: impl AsRef<Path>,
+        host_config: &bulwark_config::Config,
+        guest_config: &bulwark_config::Plugin,
+        registry: &PluginRegistry,
     ) -> Result<Self, PluginLoadError> {
         let name = guest_config.reference.clone();
+        let bytes = std::fs::read(&path)?;
+        verify_digest(&bytes, guest_config)?;
+        let schema = match ConfigSchema::from_sidecar(&path)? {
+            Some(schema) => Some(schema),
+            None => ConfigSchema::from_wasm(&bytes)?,
+        };
+        verify_config(schema, guest_config)?;
         Self::from_component(
             name,
             host_config,
             guest_config,
-            |engine| -> Result<Component, PluginLoadError> {
-                Ok(Component::from_file(engine, &path)?)
-            },
+            registry,
+            |engine| -> Result<Component, PluginLoadError> { Ok(Component::new(engine, &bytes)?) },
         )
     }
 
+    /// Creates and compiles a new [`Plugin`] from the location given by the plugin's configuration.
+    ///
+    /// The location may be a file or WASM embedded in a `data:` URI. See [`bulwark_config::Plugin::source`].
+    ///
+    /// Use [`PluginRegistry::load`] to load several copies of a plugin that share host state.
+    pub fn from_config(
+        host_config: &bulwark_config::Config,
+        guest_config: &bulwark_config::Plugin,
+    ) -> Result<Self, PluginLoadError> {
+        Self::from_config_in(host_config, guest_config, &PluginRegistry::default())
+    }
+
+    /// Helper for [`Plugin::from_config`] that shares host state through a [`PluginRegistry`].
+    pub(crate) fn from_config_in(
+        host_config: &bulwark_config::Config,
+        guest_config: &bulwark_config::Plugin,
+        registry: &PluginRegistry,
+    ) -> Result<Self, PluginLoadError> {
+        match guest_config.source()? {
+            bulwark_config::PluginSource::File(path) => {
+                Self::from_file_in(path, host_config, guest_config, registry)
+            }
+            bulwark_config::PluginSource::Data(bytes) => Self::from_bytes_in(
+                guest_config.reference.clone(),
+                &bytes,
+                host_config,
+                guest_config,
+                registry,
+            ),
+        }
+    }
+
     /// Helper method for the other `from_*` functions.
     fn from_component<F>(
         reference: String,
         host_config: &bulwark_config::Config,
         guest_config: &bulwark_config::Plugin,
+        registry: &PluginRegistry,
         mut get_component: F,
     ) -> Result<Self, PluginLoadError>
     where
@@ -174,7 +255,11 @@ impl Plugin {
         let engine = Engine::new(&wasm_config)?;
         let component = get_component(&engine)?;
 
+        let shared = registry.shared(guest_config);
         Ok(Plugin {
+            http_client: shared.http_client,
+            http_cache: shared.http_cache,
+            metrics: shared.metrics,
             reference,
             host_config: Arc::new(host_config.clone()),
             guest_config: Arc::new(guest_config.clone()),
@@ -183,6 +268,11 @@ impl Plugin {
         })
     }
 
+    /// Makes the plugin's reference available to host functions.
+    pub(crate) fn reference(&self) -> &str {
+        &self.reference
+    }
+
     /// Makes the host's configuration available to host functions.
     pub(crate) fn host_config(&self) -> &bulwark_config::Config {
         &self.host_config
@@ -197,6 +287,55 @@ impl Plugin {
     pub fn permissions(&self) -> &bulwark_config::Permissions {
         &self.guest_config.permissions
     }
+
+    /// Makes the client shared by every instance of the plugin available to host functions.
+    pub(crate) fn http_client(&self) -> Arc<HttpClient> {
+        self.http_client.clone()
+    }
+
+    /// Makes the response cache shared by every instance of the plugin available to host functions, if enabled.
+    pub(crate) fn http_cache(&self) -> Option<Arc<HttpCache>> {
+        self.http_cache.clone()
+    }
+
+    /// Makes the metrics shared by every instance of the plugin available to host functions.
+    pub(crate) fn metrics(&self) -> Arc<PluginMetrics> {
+        self.metrics.clone()
+    }
+}
+
+/// Ensures that plugin WASM matches the digest pinned in the plugin's configuration, if any.
+fn verify_digest(
+    bytes: &[u8],
+    guest_config: &bulwark_config::Plugin,
+) -> Result<(), PluginLoadError> {
+    if let Some(expected) = &guest_config.sha256 {
+        let actual = format!("{:x}", Sha256::digest(bytes));
+        if actual != *expected {
+            return Err(PluginLoadError::DigestMismatch {
+                reference: guest_config.reference.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Ensures that the plugin's configuration satisfies the plugin's config schema, if it has one.
+fn verify_config(
+    schema: Option<ConfigSchema>,
+    guest_config: &bulwark_config::Plugin,
+) -> Result<(), PluginLoadError> {
+    if let Some(schema) = schema {
+        schema
+            .validate(&guest_config.config)
+            .map_err(|violations| ConfigSchemaError::Violation {
+                reference: guest_config.reference.clone(),
+                violations,
+            })?;
+    }
+    Ok(())
 }
 
 /// Allows the host to capture plugin standard IO and record it to the log.
@@ -285,6 +424,10 @@ impl PluginInstance {
         .context("failed to link `wasi:http/outgoing-handler` interface")?;
         bindings::bulwark::plugin::config::add_to_linker(&mut linker, |t| t)
             .context("failed to link `bulwark:plugin/config` interface")?;
+        bindings::bulwark::plugin::log::add_to_linker(&mut linker, |t| t)
+            .context("failed to link `bulwark:plugin/log` interface")?;
+        bindings::bulwark::plugin::metrics::add_to_linker(&mut linker, |t| t)
+            .context("failed to link `bulwark:plugin/metrics` interface")?;
         bindings::bulwark::plugin::redis::add_to_linker(&mut linker, |t| t)
             .context("failed to link `bulwark:plugin/redis` interface")?;
         bindings::bulwark::plugin::types::add_to_linker(&mut linker, |t| t)
@@ -314,6 +457,11 @@ impl PluginInstance {
         self.plugin.guest_config.weight
     }
 
+    /// Returns true if a certain decision from this plugin finalizes the combined decision.
+    pub fn decisive(&self) -> bool {
+        self.plugin.guest_config.decisive
+    }
+
     /// Returns the plugin's identifier.
     pub fn plugin_reference(&self) -> String {
         self.plugin.reference.clone()
@@ -571,3 +719,123 @@ impl PluginInstance {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty WASM component, embedded in a `data:` URI.
+    const EMPTY_COMPONENT: &str = "data:application/wasm;base64,AGFzbQ0AAQA=";
+    /// The sha256 digest of [`EMPTY_COMPONENT`].
+    const EMPTY_COMPONENT_SHA256: &str =
+        "24eaf3439a60c96764c4f2c92a0a81b52bd4fe5d5b91f090184a0f6486b1bf29";
+
+    fn host_config() -> bulwark_config::Config {
+        bulwark_config::Config {
+            service: bulwark_config::Service::default(),
+            runtime: bulwark_config::Runtime::default(),
+            state: bulwark_config::State::default(),
+            thresholds: bulwark_config::Thresholds::default(),
+            actions: bulwark_config::Actions::default(),
+            metrics: bulwark_config::Metrics::default(),
+            capture: bulwark_config::Capture::default(),
+            plugins: vec![],
+            presets: vec![],
+            resources: vec![],
+        }
+    }
+
+    fn guest_config(path: &str, sha256: Option<&str>) -> bulwark_config::Plugin {
+        bulwark_config::Plugin {
+            reference: "pinned".to_string(),
+            path: path.to_string(),
+            sha256: sha256.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_from_config_digest() -> Result<(), PluginLoadError> {
+        let host_config = host_config();
+
+        let plugin = Plugin::from_config(
+            &host_config,
+            &guest_config(EMPTY_COMPONENT, Some(EMPTY_COMPONENT_SHA256)),
+        )?;
+        assert_eq!(plugin.reference(), "pinned");
+        Plugin::from_config(&host_config, &guest_config(EMPTY_COMPONENT, None))?;
+
+        let tampered = format!("{}0", &EMPTY_COMPONENT_SHA256[..63]);
+        let result = Plugin::from_config(
+            &host_config,
+            &guest_config(EMPTY_COMPONENT, Some(&tampered)),
+        );
+        assert!(matches!(
+            result,
+            Err(PluginLoadError::DigestMismatch { reference, expected, actual })
+                if reference == "pinned" && expected == tampered && actual == EMPTY_COMPONENT_SHA256
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_registry_shares_state() -> Result<(), PluginLoadError> {
+        let host_config = host_config();
+        let registry = PluginRegistry::new();
+
+        let cached = bulwark_config::Plugin {
+            http_client: bulwark_config::HttpClient {
+                cache: Some(bulwark_config::HttpCache {
+                    max_ttl: 60,
+                    headers: vec![],
+                    backend: bulwark_config::HttpCacheBackend::Memory,
+                    max_memory_size: bulwark_config::DEFAULT_HTTP_CACHE_MAX_MEMORY_SIZE,
+                }),
+                ..Default::default()
+            },
+            ..guest_config(EMPTY_COMPONENT, None)
+        };
+        let first = registry.load(&host_config, &cached)?;
+        let second = registry.load(&host_config, &cached)?;
+        assert!(Arc::ptr_eq(&first.metrics(), &second.metrics()));
+        assert!(Arc::ptr_eq(&first.http_client(), &second.http_client()));
+        assert!(Arc::ptr_eq(
+            &first.http_cache().unwrap(),
+            &second.http_cache().unwrap()
+        ));
+
+        let other = registry.load(
+            &host_config,
+            &bulwark_config::Plugin {
+                reference: "other".to_string(),
+                ..guest_config(EMPTY_COMPONENT, None)
+            },
+        )?;
+        assert!(!Arc::ptr_eq(&first.metrics(), &other.metrics()));
+        assert!(!Arc::ptr_eq(&first.http_client(), &other.http_client()));
+
+        // Plugins loaded on their own don't share anything.
+        let standalone = Plugin::from_config(&host_config, &guest_config(EMPTY_COMPONENT, None))?;
+        assert!(!Arc::ptr_eq(&first.metrics(), &standalone.metrics()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_file_digest_mismatch() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join(format!("bulwark-digest-{}.wat", std::process::id()));
+        std::fs::write(&path, "(component)")?;
+
+        let host_config = host_config();
+        let guest_config = guest_config(
+            path.to_str().unwrap(),
+            Some(&format!("{:x}", Sha256::digest(b"(module)"))),
+        );
+        let result = Plugin::from_file(&path, &host_config, &guest_config);
+        std::fs::remove_file(&path)?;
+        assert!(matches!(
+            result,
+            Err(PluginLoadError::DigestMismatch { reference, .. }) if reference == "pinned"
+        ));
+        Ok(())
+    }
+}
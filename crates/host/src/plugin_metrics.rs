@@ -0,0 +1,314 @@
+use crate::bindings::bulwark::plugin::metrics::Error;
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+/// The largest number of distinct metric names a plugin may record.
+const MAX_METRICS: usize = 100;
+
+/// The largest number of labels a plugin may attach to a single metric.
+const MAX_LABELS: usize = 8;
+
+/// The largest number of distinct label combinations a plugin may record for a single metric name.
+const MAX_SERIES: usize = 1000;
+
+/// The label holding the reference of the plugin that recorded a metric.
+const PLUGIN_LABEL: &str = "plugin";
+
+/// The kinds of metric a plugin may record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+/// The kind of every metric name recorded by the plugins loaded through a [`PluginRegistry`](crate::PluginRegistry).
+///
+/// Every plugin exports a metric under the same name, distinguished only by its `plugin` label, so each name may only
+/// be recorded as one kind of metric, whichever plugin records it first.
+#[derive(Default)]
+pub(crate) struct MetricKinds(Mutex<HashMap<String, MetricKind>>);
+
+/// The label combinations already recorded for one of a plugin's metrics.
+struct Metric {
+    kind: MetricKind,
+    series: HashSet<Vec<(String, String)>>,
+}
+
+/// Forwards a plugin's metrics to the installed [`metrics`] recorder.
+///
+/// A single `PluginMetrics` is shared by every instance of a plugin, including the copies loaded for each resource
+/// through a [`PluginRegistry`](crate::PluginRegistry), so that cardinality limits apply to the plugin as a whole.
+/// Metric names are prefixed with `plugin_metric_` and labeled with the plugin's reference, so a metric named `score`
+/// recorded by the `bot_detection` plugin is reported as `plugin_metric_score{plugin="bot_detection"}`. Keeping the
+/// reference out of the name means two plugins can never record the same series, but a metric name is then shared
+/// across plugins, so its kind is checked against the [`MetricKinds`] of every plugin.
+pub(crate) struct PluginMetrics {
+    /// The reference of the plugin recording metrics, used to label its metrics.
+    reference: String,
+    /// The kinds of the metrics recorded by every plugin.
+    kinds: Arc<MetricKinds>,
+    /// The metrics recorded by the plugin so far, by the name the plugin gave them.
+    metrics: Mutex<HashMap<String, Metric>>,
+}
+
+impl PluginMetrics {
+    /// Creates a new [`PluginMetrics`] for a plugin.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The plugin's reference.
+    /// * `kinds` - The kinds of the metrics recorded by every plugin.
+    pub(crate) fn new(reference: String, kinds: Arc<MetricKinds>) -> Self {
+        Self {
+            reference,
+            kinds,
+            metrics: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Increments the named counter by the given value.
+    pub(crate) fn increment_counter(
+        &self,
+        name: &str,
+        value: u64,
+        labels: Vec<(String, String)>,
+    ) -> Result<(), Error> {
+        let key = self.key(MetricKind::Counter, name, labels)?;
+        metrics::recorder().register_counter(&key).increment(value);
+        Ok(())
+    }
+
+    /// Sets the named gauge to the given value.
+    pub(crate) fn set_gauge(
+        &self,
+        name: &str,
+        value: f64,
+        labels: Vec<(String, String)>,
+    ) -> Result<(), Error> {
+        let key = self.key(MetricKind::Gauge, name, labels)?;
+        metrics::recorder().register_gauge(&key).set(value);
+        Ok(())
+    }
+
+    /// Records the given value in the named histogram.
+    pub(crate) fn record_histogram(
+        &self,
+        name: &str,
+        value: f64,
+        labels: Vec<(String, String)>,
+    ) -> Result<(), Error> {
+        let key = self.key(MetricKind::Histogram, name, labels)?;
+        metrics::recorder().register_histogram(&key).record(value);
+        Ok(())
+    }
+
+    /// Validates a metric and its labels against the plugin's limits, returning the key to record it under.
+    fn key(
+        &self,
+        kind: MetricKind,
+        name: &str,
+        mut labels: Vec<(String, String)>,
+    ) -> Result<metrics::Key, Error> {
+        if !is_valid_name(name) {
+            return Err(Error::InvalidName(name.to_string()));
+        }
+        if let Some((label, _)) = labels
+            .iter()
+            .find(|(label, _)| !is_valid_name(label) || label == PLUGIN_LABEL)
+        {
+            return Err(Error::InvalidName(label.clone()));
+        }
+        if labels.len() > MAX_LABELS {
+            return Err(Error::Cardinality(format!(
+                "metric '{}' has more than {} labels",
+                name, MAX_LABELS
+            )));
+        }
+        // The same labels given in a different order are the same series.
+        labels.sort();
+        if let Some(pair) = labels.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(Error::DuplicateLabel(pair[0].0.clone()));
+        }
+
+        let mut metrics = self.metrics.lock().expect("metrics lock poisoned");
+        let metric_count = metrics.len();
+        let metric = match metrics.get_mut(name) {
+            Some(metric) => metric,
+            None if metric_count >= MAX_METRICS => {
+                return Err(Error::Cardinality(format!(
+                    "plugin has recorded more than {} metrics",
+                    MAX_METRICS
+                )));
+            }
+            None => {
+                let mut kinds = self.kinds.0.lock().expect("metrics lock poisoned");
+                if *kinds.entry(name.to_string()).or_insert(kind) != kind {
+                    return Err(Error::KindMismatch(name.to_string()));
+                }
+                metrics.entry(name.to_string()).or_insert(Metric {
+                    kind,
+                    series: HashSet::new(),
+                })
+            }
+        };
+        if metric.kind != kind {
+            return Err(Error::KindMismatch(name.to_string()));
+        }
+        if !metric.series.contains(&labels) {
+            if metric.series.len() >= MAX_SERIES {
+                return Err(Error::Cardinality(format!(
+                    "metric '{}' has more than {} label combinations",
+                    name, MAX_SERIES
+                )));
+            }
+            metric.series.insert(labels.clone());
+        }
+
+        Ok(metrics::Key::from_parts(
+            format!("plugin_metric_{}", name),
+            std::iter::once(metrics::Label::new(PLUGIN_LABEL, self.reference.clone()))
+                .chain(
+                    labels
+                        .into_iter()
+                        .map(|(label, value)| metrics::Label::new(label, value)),
+                )
+                .collect::<Vec<_>>(),
+        ))
+    }
+}
+
+/// Returns true if the name is valid for both metrics and labels across Prometheus and StatsD.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.len() <= 96
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(labels: &[(&str, &str)]) -> Vec<(String, String)> {
+        labels
+            .iter()
+            .map(|(label, value)| (label.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_plugin_metrics_key() {
+        let metrics = PluginMetrics::new("bot_detection".to_string(), Arc::default());
+
+        let key = metrics
+            .key(
+                MetricKind::Histogram,
+                "feature_score",
+                labels(&[("model", "v2"), ("feature", "mouse")]),
+            )
+            .unwrap();
+        assert_eq!(key.name(), "plugin_metric_feature_score");
+        assert_eq!(
+            key.labels()
+                .map(|label| (label.key(), label.value()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("plugin", "bot_detection"),
+                ("feature", "mouse"),
+                ("model", "v2")
+            ]
+        );
+
+        assert!(matches!(
+            metrics.key(MetricKind::Counter, "feature_score", vec![]),
+            Err(Error::KindMismatch(name)) if name == "feature_score"
+        ));
+        assert!(matches!(
+            metrics.key(MetricKind::Counter, "feature-score", vec![]),
+            Err(Error::InvalidName(name)) if name == "feature-score"
+        ));
+        assert!(matches!(
+            metrics.key(MetricKind::Counter, "requests", labels(&[("__name__", "x")])),
+            Err(Error::InvalidName(name)) if name == "__name__"
+        ));
+        assert!(matches!(
+            metrics.key(MetricKind::Counter, "requests", labels(&[("plugin", "other")])),
+            Err(Error::InvalidName(name)) if name == "plugin"
+        ));
+        assert!(matches!(
+            metrics.key(
+                MetricKind::Counter,
+                "requests",
+                labels(&[("ip", "192.0.2.1"), ("ip", "192.0.2.2")])
+            ),
+            Err(Error::DuplicateLabel(label)) if label == "ip"
+        ));
+
+        // Plugins whose references share a prefix record distinct series of the same metric.
+        let bot = PluginMetrics::new("bot".to_string(), Arc::default())
+            .key(MetricKind::Counter, "detection_score", vec![])
+            .unwrap();
+        let bot_detection = metrics.key(MetricKind::Counter, "score", vec![]).unwrap();
+        assert_ne!(bot, bot_detection);
+    }
+
+    #[test]
+    fn test_plugin_metrics_shared_kinds() {
+        let kinds = Arc::new(MetricKinds::default());
+        let first = PluginMetrics::new("first".to_string(), kinds.clone());
+        let second = PluginMetrics::new("second".to_string(), kinds);
+
+        first.key(MetricKind::Histogram, "score", vec![]).unwrap();
+        assert!(second.key(MetricKind::Histogram, "score", vec![]).is_ok());
+        // Both would be exported as `plugin_metric_score`, which can only have one type.
+        assert!(matches!(
+            second.key(MetricKind::Counter, "score", vec![]),
+            Err(Error::KindMismatch(name)) if name == "score"
+        ));
+        assert!(PluginMetrics::new("third".to_string(), Arc::default())
+            .key(MetricKind::Counter, "score", vec![])
+            .is_ok());
+    }
+
+    #[test]
+    fn test_plugin_metrics_cardinality() {
+        let metrics = PluginMetrics::new("bot_detection".to_string(), Arc::default());
+
+        for i in 0..MAX_SERIES {
+            let value = i.to_string();
+            metrics
+                .key(MetricKind::Counter, "requests", labels(&[("ip", &value)]))
+                .unwrap();
+        }
+        // Series that were already recorded may still be recorded again.
+        assert!(metrics
+            .key(MetricKind::Counter, "requests", labels(&[("ip", "0")]))
+            .is_ok());
+        assert!(matches!(
+            metrics.key(MetricKind::Counter, "requests", labels(&[("ip", "new")])),
+            Err(Error::Cardinality(_))
+        ));
+
+        let too_many_labels = (0..=MAX_LABELS)
+            .map(|i| (format!("label{}", i), String::new()))
+            .collect();
+        assert!(matches!(
+            metrics.key(MetricKind::Gauge, "features", too_many_labels),
+            Err(Error::Cardinality(_))
+        ));
+
+        for i in 1..MAX_METRICS {
+            metrics
+                .key(MetricKind::Gauge, &format!("gauge{}", i), vec![])
+                .unwrap();
+        }
+        assert!(matches!(
+            metrics.key(MetricKind::Gauge, "one_too_many", vec![]),
+            Err(Error::Cardinality(_))
+        ));
+    }
+}
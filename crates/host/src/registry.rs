@@ -0,0 +1,75 @@
+use crate::{
+    http_cache::HttpCache,
+    http_client::HttpClient,
+    plugin_metrics::{MetricKinds, PluginMetrics},
+    Plugin, PluginLoadError,
+};
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// Loads [`Plugin`]s so that every copy of a plugin shares the same host state.
+///
+/// Each resource that runs a plugin loads its own copy of it, since resources may override the plugin's
+/// configuration. Loading the copies through one `PluginRegistry` shares the host state that enforces the plugin's
+/// limits between them, so the limits apply to the plugin as a whole rather than once per resource.
+#[derive(Default)]
+pub struct PluginRegistry {
+    /// The state shared by each plugin, by reference.
+    shared: Mutex<HashMap<String, SharedState>>,
+    /// The kinds of the metrics recorded by every plugin, which export metrics under the same names.
+    metric_kinds: Arc<MetricKinds>,
+}
+
+/// The host state shared by every copy of a plugin.
+#[derive(Clone)]
+pub(crate) struct SharedState {
+    pub(crate) http_client: Arc<HttpClient>,
+    pub(crate) http_cache: Option<Arc<HttpCache>>,
+    pub(crate) metrics: Arc<PluginMetrics>,
+}
+
+impl PluginRegistry {
+    /// Creates a new, empty [`PluginRegistry`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates and compiles a [`Plugin`] from the location given by the plugin's configuration, sharing host state
+    /// with any plugin already loaded under the same reference.
+    ///
+    /// See [`Plugin::from_config`].
+    pub fn load(
+        &self,
+        host_config: &bulwark_config::Config,
+        guest_config: &bulwark_config::Plugin,
+    ) -> Result<Plugin, PluginLoadError> {
+        Plugin::from_config_in(host_config, guest_config, self)
+    }
+
+    /// Returns the state shared by plugins with the given reference, creating it the first time it's needed.
+    pub(crate) fn shared(&self, guest_config: &bulwark_config::Plugin) -> SharedState {
+        self.shared
+            .lock()
+            .expect("poisoned mutex")
+            .entry(guest_config.reference.clone())
+            .or_insert_with(|| SharedState {
+                http_client: Arc::new(HttpClient::new(
+                    guest_config.reference.clone(),
+                    guest_config.http_client.clone(),
+                )),
+                http_cache: guest_config
+                    .http_client
+                    .cache
+                    .clone()
+                    .map(|cache| Arc::new(HttpCache::new(guest_config.reference.clone(), cache))),
+                metrics: Arc::new(PluginMetrics::new(
+                    guest_config.reference.clone(),
+                    self.metric_kinds.clone(),
+                )),
+            })
+            .clone()
+    }
+}
@@ -0,0 +1,265 @@
+//! The schema module validates plugin configuration against a JSON Schema supplied by the plugin.
+//!
+//! Schemas are compiled with [`jsonschema`], which supports every keyword of drafts 4 through 2020-12. Schemas
+//! without a `$schema` keyword are treated as draft 2020-12. References are only resolved within the schema itself;
+//! a schema that refers to a remote document is rejected rather than fetched when the plugin is loaded.
+
+use crate::ConfigSchemaError;
+use bulwark_build::CONFIG_SCHEMA_SECTION;
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// A JSON Schema describing the configuration a plugin accepts.
+#[derive(Debug, Clone)]
+pub struct ConfigSchema {
+    validator: Arc<jsonschema::Validator>,
+}
+
+impl ConfigSchema {
+    /// Parses and compiles a JSON Schema document.
+    ///
+    /// Schemas that are not valid against their draft's meta-schema, use invalid regular expressions, or refer to
+    /// documents outside of the schema are rejected, so that mistakes surface at load time rather than as
+    /// confusing violations.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, ConfigSchemaError> {
+        let schema: Value = serde_json::from_slice(bytes)?;
+        let validator = jsonschema::validator_for(&schema)
+            .map_err(|error| ConfigSchemaError::Schema(error.to_string()))?;
+        Ok(Self {
+            validator: Arc::new(validator),
+        })
+    }
+
+    /// Reads the schema embedded in a plugin's WASM, if any.
+    ///
+    /// Bytes that are not binary WASM, such as WAT, never contain an embedded schema.
+    pub fn from_wasm(bytes: &[u8]) -> Result<Option<Self>, ConfigSchemaError> {
+        if !wasmparser::Parser::is_component(bytes) && !wasmparser::Parser::is_core_wasm(bytes) {
+            return Ok(None);
+        }
+        for payload in wasmparser::Parser::new(0).parse_all(bytes) {
+            // Malformed WASM will be reported when the component is compiled.
+            let Ok(payload) = payload else {
+                return Ok(None);
+            };
+            if let wasmparser::Payload::CustomSection(reader) = payload {
+                if reader.name() == CONFIG_SCHEMA_SECTION {
+                    return Self::from_slice(reader.data()).map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the sidecar schema file for a plugin file, if one exists.
+    ///
+    /// The sidecar for `example.wasm` is `example.schema.json` in the same directory.
+    pub fn from_sidecar(path: impl AsRef<Path>) -> Result<Option<Self>, ConfigSchemaError> {
+        let sidecar_path = Self::sidecar_path(path);
+        if !sidecar_path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(Self::from_slice(&std::fs::read(sidecar_path)?)?))
+    }
+
+    /// Returns the location of the sidecar schema file for a plugin file.
+    pub fn sidecar_path(path: impl AsRef<Path>) -> PathBuf {
+        path.as_ref().with_extension("schema.json")
+    }
+
+    /// Validates a plugin's configuration against the schema.
+    ///
+    /// All violations are reported together, each prefixed by the JSON pointer of the offending value.
+    pub fn validate(&self, config: &serde_json::Map<String, Value>) -> Result<(), Vec<String>> {
+        let config = Value::Object(config.clone());
+        let violations: Vec<String> = self
+            .validator
+            .iter_errors(&config)
+            .map(|error| {
+                let pointer = error.instance_path.to_string();
+                let location = if pointer.is_empty() { "/" } else { &pointer };
+                format!("'{location}': {error}")
+            })
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(value: Value) -> serde_json::Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    /// Returns violations in a stable order regardless of how the config map orders its keys.
+    fn violations(schema: &ConfigSchema, value: Value) -> Vec<String> {
+        let mut violations = schema.validate(&config(value)).err().unwrap_or_default();
+        violations.sort();
+        violations
+    }
+
+    #[test]
+    fn test_validate() -> Result<(), Box<dyn std::error::Error>> {
+        let schema = ConfigSchema::from_slice(
+            json!({
+                "type": "object",
+                "properties": {
+                    "threshold": { "type": "number", "minimum": 0.0, "maximum": 1.0 },
+                    "mode": { "enum": ["block", "observe"] },
+                    "paths": { "type": "array", "items": { "type": "string", "pattern": "^/" } },
+                    "limit": { "type": "integer", "exclusiveMinimum": 0 }
+                },
+                "required": ["mode"],
+                "additionalProperties": false
+            })
+            .to_string()
+            .as_bytes(),
+        )?;
+
+        assert!(schema
+            .validate(&config(json!({
+                "threshold": 0.5,
+                "mode": "block",
+                "paths": ["/login", "/signup"],
+                "limit": 10
+            })))
+            .is_ok());
+
+        assert_eq!(
+            violations(&schema, json!({ "mode": "block", "treshold": 0.5 })),
+            vec!["'/': Additional properties are not allowed ('treshold' was unexpected)"]
+        );
+        assert_eq!(
+            violations(&schema, json!({ "threshold": 2 })),
+            vec![
+                "'/': \"mode\" is a required property",
+                "'/threshold': 2 is greater than the maximum of 1.0"
+            ]
+        );
+        assert_eq!(
+            violations(
+                &schema,
+                json!({
+                    "mode": "deny",
+                    "paths": ["/login", "signup"],
+                    "limit": 1.5
+                })
+            ),
+            vec![
+                "'/limit': 1.5 is not of type \"integer\"",
+                "'/mode': \"deny\" is not one of [\"block\",\"observe\"]",
+                "'/paths/1': \"signup\" does not match \"^/\"",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_applicators() -> Result<(), Box<dyn std::error::Error>> {
+        let schema = ConfigSchema::from_slice(
+            json!({
+                "$defs": {
+                    "path": { "type": "string", "pattern": "^/" }
+                },
+                "type": "object",
+                "properties": {
+                    "paths": { "type": "array", "items": { "$ref": "#/$defs/path" } },
+                    "limit": { "anyOf": [{ "type": "integer" }, { "const": "unlimited" }] },
+                    "mode": { "enum": ["block", "observe"] }
+                },
+                "patternProperties": {
+                    "^weight_": { "type": "number" }
+                },
+                "if": { "properties": { "mode": { "const": "block" } } },
+                "then": { "required": ["limit"] }
+            })
+            .to_string()
+            .as_bytes(),
+        )?;
+
+        assert!(schema
+            .validate(&config(json!({
+                "paths": ["/login"],
+                "limit": "unlimited",
+                "mode": "block",
+                "weight_ip": 0.5
+            })))
+            .is_ok());
+        assert!(schema
+            .validate(&config(json!({ "mode": "observe" })))
+            .is_ok());
+
+        let found = violations(
+            &schema,
+            json!({
+                "paths": ["login"],
+                "limit": "none",
+                "mode": "block",
+                "weight_ip": "high"
+            }),
+        );
+        assert_eq!(found.len(), 3);
+        assert!(found[0].starts_with("'/limit': "));
+        assert!(found[1].starts_with("'/paths/0': "));
+        assert!(found[2].starts_with("'/weight_ip': "));
+
+        assert_eq!(
+            violations(&schema, json!({ "mode": "block" })),
+            vec!["'/': \"limit\" is a required property"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        assert!(matches!(
+            ConfigSchema::from_slice(b"[]"),
+            Err(ConfigSchemaError::Schema(_))
+        ));
+        assert!(matches!(
+            ConfigSchema::from_slice(br#"{"properties": {"key": {"pattern": "("}}}"#),
+            Err(ConfigSchemaError::Schema(_))
+        ));
+        assert!(matches!(
+            ConfigSchema::from_slice(b"{"),
+            Err(ConfigSchemaError::Json(_))
+        ));
+        // Remote references are never fetched.
+        assert!(matches!(
+            ConfigSchema::from_slice(br#"{"$ref": "https://example.com/schema.json"}"#),
+            Err(ConfigSchemaError::Schema(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_wasm() -> Result<(), Box<dyn std::error::Error>> {
+        let mut module = wat::parse_str("(module)")?;
+        assert!(ConfigSchema::from_wasm(&module)?.is_none());
+
+        let schema = br#"{"additionalProperties": false}"#;
+        // Custom section: id, size, name length, name, data.
+        module.push(0);
+        module.push((1 + CONFIG_SCHEMA_SECTION.len() + schema.len()) as u8);
+        module.push(CONFIG_SCHEMA_SECTION.len() as u8);
+        module.extend_from_slice(CONFIG_SCHEMA_SECTION.as_bytes());
+        module.extend_from_slice(schema);
+
+        let schema = ConfigSchema::from_wasm(&module)?.unwrap();
+        assert!(schema.validate(&config(json!({}))).is_ok());
+        assert!(schema.validate(&config(json!({ "key": 1 }))).is_err());
+
+        // WAT is never inspected for an embedded schema.
+        assert!(ConfigSchema::from_wasm(b"(module)")?.is_none());
+        Ok(())
+    }
+}
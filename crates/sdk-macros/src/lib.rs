@@ -133,7 +133,6 @@ pub fn bulwark_plugin(_: TokenStream, input: TokenStream) -> TokenStream {
                 "handle_init" => {
                     // handle-init: func() -> result<_, error>;
                     quote! {
-                        #[handler]
                         fn handle_init() -> Result<(), ::bulwark_sdk::Error> {
                             Ok(())
                         }
@@ -141,7 +140,6 @@ pub fn bulwark_plugin(_: TokenStream, input: TokenStream) -> TokenStream {
                 }
                 "handle_request_enrichment" => {
                     quote! {
-                        #[handler]
                         fn handle_request_enrichment(
                             _: ::bulwark_sdk::Request,
                             _: ::std::collections::HashMap<String, String>
@@ -152,7 +150,6 @@ pub fn bulwark_plugin(_: TokenStream, input: TokenStream) -> TokenStream {
                 }
                 "handle_request_decision" => {
                     quote! {
-                        #[handler]
                         fn handle_request_decision(
                             _: ::bulwark_sdk::Request,
                             _: ::std::collections::HashMap<String, String>
@@ -167,7 +164,6 @@ pub fn bulwark_plugin(_: TokenStream, input: TokenStream) -> TokenStream {
                 }
                 "handle_response_decision" => {
                     quote! {
-                        #[handler]
                         fn handle_response_decision(
                             _: ::bulwark_sdk::Request,
                             _: ::bulwark_sdk::Response,
@@ -183,7 +179,6 @@ pub fn bulwark_plugin(_: TokenStream, input: TokenStream) -> TokenStream {
                 }
                 "handle_decision_feedback" => {
                     quote! {
-                        #[handler]
                         fn handle_decision_feedback(
                             _: ::bulwark_sdk::Request,
                             _: ::bulwark_sdk::Response,
@@ -205,6 +200,19 @@ pub fn bulwark_plugin(_: TokenStream, input: TokenStream) -> TokenStream {
         })
         .collect::<Vec<proc_macro2::TokenStream>>();
 
+    // Test builds get the same handlers as inherent functions, without the `handler` attribute, so that unit tests
+    // can call them natively. Inherent functions take precedence over the trait's when called through the type.
+    let test_items = new_items
+        .iter()
+        .map(|item| {
+            let mut item = item.clone();
+            if let syn::ImplItem::Fn(iifn) = &mut item {
+                iifn.attrs.retain(|attr| !attr.path().is_ident("handler"));
+            }
+            item
+        })
+        .collect::<Vec<syn::ImplItem>>();
+
     let output = quote! {
         mod handlers {
             use super::#struct_type;
@@ -414,6 +422,16 @@ pub fn bulwark_plugin(_: TokenStream, input: TokenStream) -> TokenStream {
         use crate::handlers::exports::bulwark::plugin::http_handlers::Guest as HttpHandlers;
         impl HttpHandlers for #struct_type {
             #(#new_items)*
+            #(
+                #[handler]
+                #noop_handlers
+            )*
+        }
+
+        #[cfg(test)]
+        #[allow(dead_code)]
+        impl #struct_type {
+            #(#test_items)*
             #(#noop_handlers)*
         }
 
@@ -11,6 +11,34 @@ pub enum ParseCounterError {
     Utf8(#[from] std::str::Utf8Error),
 }
 
+/// Returned when the plugin's configuration cannot be deserialized into the requested type.
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("invalid plugin config: {0}")]
+    Deserialize(#[from] serde_json::Error),
+}
+
+/// Returned when an outbound HTTP request made by the plugin fails.
+#[derive(thiserror::Error, Debug)]
+pub enum HttpError {
+    #[error("access to http host '{authority}' denied")]
+    Permission { authority: String },
+    #[error("invalid http request: {message}")]
+    InvalidRequest { message: String },
+    #[error("http request timed out")]
+    Timeout,
+    #[error("http response body too large")]
+    BodyTooLarge,
+    #[error("error sending http request: {message}")]
+    Remote { message: String },
+    #[error("unexpected http status: {status}")]
+    Status { status: u16 },
+    #[error(transparent)]
+    Http(#[from] http::Error),
+    #[error("invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
 /// Returned when there is an issue with the remote state requested by the plugin.
 #[derive(thiserror::Error, Debug)]
 pub enum RemoteStateError {
@@ -65,3 +93,35 @@ impl From<ParseCounterError> for RemoteStateError {
         }
     }
 }
+
+/// Returned when a metric recorded by the plugin is rejected by the host.
+#[derive(thiserror::Error, Debug)]
+pub enum MetricsError {
+    #[error("invalid metric name: {message}")]
+    InvalidName { message: String },
+    #[error("label '{label}' was given more than once")]
+    DuplicateLabel { label: String },
+    #[error("metric '{name}' was previously recorded as a different kind of metric")]
+    KindMismatch { name: String },
+    #[error("metric cardinality limit exceeded: {message}")]
+    Cardinality { message: String },
+}
+
+impl From<crate::wit::bulwark::plugin::metrics::Error> for MetricsError {
+    fn from(error: crate::wit::bulwark::plugin::metrics::Error) -> Self {
+        match error {
+            crate::wit::bulwark::plugin::metrics::Error::InvalidName(message) => {
+                MetricsError::InvalidName { message }
+            }
+            crate::wit::bulwark::plugin::metrics::Error::DuplicateLabel(label) => {
+                MetricsError::DuplicateLabel { label }
+            }
+            crate::wit::bulwark::plugin::metrics::Error::KindMismatch(name) => {
+                MetricsError::KindMismatch { name }
+            }
+            crate::wit::bulwark::plugin::metrics::Error::Cardinality(message) => {
+                MetricsError::Cardinality { message }
+            }
+        }
+    }
+}
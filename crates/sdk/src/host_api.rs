@@ -1,5 +1,8 @@
+#[cfg(not(feature = "test"))]
+use std::sync::OnceLock;
 use {
     forwarded_header_value::ForwardedHeaderValue,
+    serde::de::DeserializeOwned,
     std::{collections::HashMap, net::IpAddr, str},
 };
 
@@ -49,7 +52,7 @@ pub struct Verdict {
 
 /// Returns all of the plugin's configuration key names.
 pub fn config_keys() -> Vec<String> {
-    crate::wit::bulwark::plugin::config::config_keys()
+    crate::host::config::config_keys()
 }
 
 /// Returns a named plugin configuration value as a [`Value`].
@@ -94,7 +97,90 @@ pub fn config_keys() -> Vec<String> {
 /// }
 /// ```
 pub fn config_var(key: &str) -> Option<Value> {
-    crate::wit::bulwark::plugin::config::config_var(key).map(|v| v.into())
+    with_config(|config| config.get(key).cloned())
+}
+
+/// Calls a function with the entire plugin configuration, which is read from the host once per plugin instance.
+#[cfg(not(feature = "test"))]
+fn with_config<T>(f: impl FnOnce(&Value) -> T) -> T {
+    static CONFIG: OnceLock<Value> = OnceLock::new();
+    f(CONFIG.get_or_init(read_config))
+}
+
+/// Calls a function with the entire plugin configuration.
+///
+/// Tests may install a different mock host at any time, so the configuration is read again on every call.
+#[cfg(feature = "test")]
+fn with_config<T>(f: impl FnOnce(&Value) -> T) -> T {
+    f(&read_config())
+}
+
+/// Reads the entire plugin configuration from the host.
+///
+/// The JSON form is used rather than `config-var` because it preserves values nested to any depth.
+fn read_config() -> Value {
+    let config: Map<String, Value> = serde_json::from_str(&crate::host::config::config_json())
+        .expect("host should always return a JSON-encoded object");
+    Value::Object(config)
+}
+
+/// Deserializes the plugin's entire configuration into a typed value.
+///
+/// Missing keys and defaults are handled by the value's [`Deserialize`](serde::Deserialize) implementation, e.g.
+/// with `#[serde(default)]`. Plugin instances only live for a single request, so the configuration is typically
+/// deserialized once in `handle_init` and kept in a static for the remaining handlers.
+///
+/// # Example
+///
+#[cfg_attr(doctest, doc = " ````no_test")]
+/// ```rust
+/// use bulwark_sdk::*;
+/// use serde::Deserialize;
+/// use std::collections::HashMap;
+/// use std::sync::OnceLock;
+///
+/// #[derive(Deserialize)]
+/// struct RateLimiterConfig {
+///     limit: i64,
+///     #[serde(default = "default_window")]
+///     window: i64,
+/// }
+///
+/// fn default_window() -> i64 {
+///     60
+/// }
+///
+/// static CONFIG: OnceLock<RateLimiterConfig> = OnceLock::new();
+///
+/// struct RateLimiter;
+///
+/// #[bulwark_plugin]
+/// impl HttpHandlers for RateLimiter {
+///     fn handle_init() -> Result<(), Error> {
+///         // Fails the init handler, and so the request, if the config can't be deserialized.
+///         let _ = CONFIG.set(config()?);
+///         Ok(())
+///     }
+///
+///     fn handle_request_decision(
+///         req: Request,
+///         _labels: HashMap<String, String>,
+///     ) -> Result<HandlerOutput, Error> {
+///         let config = CONFIG.get().ok_or(error!("config not loaded"))?;
+///         let mut output = HandlerOutput::default();
+///         if let Some(ip) = client_ip(&req) {
+///             let rate = redis::incr_rate_limit(ip.to_string(), 1, config.window)?;
+///             if rate.attempts > config.limit {
+///                 output.decision = Decision::restricted(0.5);
+///             }
+///         }
+///         Ok(output)
+///     }
+/// }
+/// ```
+pub fn config<T: DeserializeOwned>() -> Result<T, crate::ConfigError> {
+    // Deserializing from the parsed value keeps positions within the encoded JSON out of error messages.
+    Ok(with_config(|config| T::deserialize(config))?)
 }
 
 /// Returns the true remote client IP address.
@@ -132,7 +218,7 @@ pub fn config_var(key: &str) -> Option<Value> {
 /// }
 /// ```
 pub fn client_ip(req: &Request) -> Option<IpAddr> {
-    let proxy_hops = crate::wit::bulwark::plugin::config::proxy_hops();
+    let proxy_hops = crate::host::config::proxy_hops();
 
     if let Some(forwarded) = req.headers().get("forwarded") {
         return parse_forwarded_ip(forwarded.as_bytes(), proxy_hops as usize);
@@ -0,0 +1,313 @@
+//! The `http_client` module sends outbound HTTP requests from a plugin.
+//!
+//! Requests are blocking, and are made by the host on the plugin's behalf. In order for a request to succeed, a
+//! plugin's configuration must explicitly declare a permission grant for the domain being requested. Requests to
+//! any other domain fail with [`HttpError::Permission`].
+//!
+//! # Example
+//!
+#![cfg_attr(doctest, doc = " ````no_test")]
+//! ```rust
+//! use bulwark_sdk::*;
+//! use serde::Deserialize;
+//! use std::collections::HashMap;
+//!
+//! #[derive(Deserialize)]
+//! struct Reputation {
+//!     score: f64,
+//! }
+//!
+//! struct IpReputation;
+//!
+//! #[bulwark_plugin]
+//! impl HttpHandlers for IpReputation {
+//!     fn handle_request_decision(
+//!         req: Request,
+//!         _labels: HashMap<String, String>,
+//!     ) -> Result<HandlerOutput, Error> {
+//!         let mut output = HandlerOutput::default();
+//!         if let Some(ip) = client_ip(&req) {
+//!             let reputation: Reputation =
+//!                 http_client::get_json(format!("https://reputation.example.com/ip/{}", ip))?;
+//!             output.decision = Decision::restricted(reputation.score);
+//!         }
+//!         Ok(output)
+//!     }
+//! }
+//! ```
+
+use crate::{Bytes, HttpError, Request, Response};
+use serde::{de::DeserializeOwned, Serialize};
+use std::time::Duration;
+
+/// Sends a request, waiting for the complete response.
+///
+/// Only the host's default timeouts apply. Responses are returned whatever their status code.
+///
+/// # Arguments
+///
+/// * `request` - The request to send. The URI must be absolute.
+pub fn send(request: Request) -> Result<Response, HttpError> {
+    transport(request, None)
+}
+
+/// Sends a request with a timeout, waiting for the complete response.
+///
+/// The timeout applies separately to connecting, to receiving the first byte of the response, and to the gap
+/// between subsequent bytes of the response.
+///
+/// # Arguments
+///
+/// * `request` - The request to send. The URI must be absolute.
+/// * `timeout` - The longest time to wait at each stage of the request.
+pub fn send_with_timeout(request: Request, timeout: Duration) -> Result<Response, HttpError> {
+    transport(request, Some(timeout))
+}
+
+/// Sends a `GET` request, deserializing a successful JSON response.
+///
+/// # Arguments
+///
+/// * `uri` - The absolute URI to request.
+pub fn get_json<U: AsRef<str>, T: DeserializeOwned>(uri: U) -> Result<T, HttpError> {
+    let request = http::Request::get(uri.as_ref())
+        .header(http::header::ACCEPT, "application/json")
+        .body(Bytes::new())?;
+    json_response(send(request)?)
+}
+
+/// Sends a `POST` request with a JSON body, deserializing a successful JSON response.
+///
+/// # Arguments
+///
+/// * `uri` - The absolute URI to request.
+/// * `body` - The value to serialize as the request body.
+pub fn post_json<U: AsRef<str>, B: Serialize, T: DeserializeOwned>(
+    uri: U,
+    body: &B,
+) -> Result<T, HttpError> {
+    let request = http::Request::post(uri.as_ref())
+        .header(http::header::ACCEPT, "application/json")
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(Bytes::from(serde_json::to_vec(body)?))?;
+    json_response(send(request)?)
+}
+
+/// Deserializes a JSON response body, rejecting responses without a success status.
+pub fn json_response<T: DeserializeOwned>(response: Response) -> Result<T, HttpError> {
+    if !response.status().is_success() {
+        return Err(HttpError::Status {
+            status: response.status().as_u16(),
+        });
+    }
+    Ok(serde_json::from_slice(response.body())?)
+}
+
+#[cfg(not(feature = "test"))]
+fn transport(request: Request, timeout: Option<Duration>) -> Result<Response, HttpError> {
+    wasi::send(request, timeout)
+}
+
+#[cfg(feature = "test")]
+fn transport(request: Request, timeout: Option<Duration>) -> Result<Response, HttpError> {
+    crate::mock::send(request, timeout)
+}
+
+/// Sends requests through `wasi:http/outgoing-handler`.
+#[cfg_attr(feature = "test", allow(dead_code))]
+mod wasi {
+    use crate::wit::wasi::http::{outgoing_handler, types};
+    use crate::wit::wasi::io::streams::StreamError;
+    use crate::{Bytes, HttpError, Request, Response};
+    use std::time::Duration;
+
+    /// The largest write a blocking write is allowed to perform.
+    const MAX_WRITE_SIZE: usize = 4096;
+    /// The amount requested by each blocking read.
+    const READ_SIZE: u64 = 65536;
+
+    pub(super) fn send(request: Request, timeout: Option<Duration>) -> Result<Response, HttpError> {
+        let (parts, body) = request.into_parts();
+        let authority = parts
+            .uri
+            .authority()
+            .ok_or_else(|| HttpError::InvalidRequest {
+                message: "uri must be absolute".to_string(),
+            })?
+            .to_string();
+
+        let headers = types::Fields::from_list(
+            &parts
+                .headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.as_bytes().to_vec()))
+                .collect::<Vec<_>>(),
+        )
+        .map_err(|err| HttpError::InvalidRequest {
+            message: format!("invalid header: {:?}", err),
+        })?;
+        let outgoing_request = types::OutgoingRequest::new(headers);
+        let invalid = |field: &str| HttpError::InvalidRequest {
+            message: format!("invalid {}", field),
+        };
+        outgoing_request
+            .set_method(&method(&parts.method))
+            .map_err(|_| invalid("method"))?;
+        outgoing_request
+            .set_scheme(Some(&match parts.uri.scheme_str() {
+                Some("http") => types::Scheme::Http,
+                Some("https") | None => types::Scheme::Https,
+                Some(other) => types::Scheme::Other(other.to_string()),
+            }))
+            .map_err(|_| invalid("scheme"))?;
+        outgoing_request
+            .set_authority(Some(&authority))
+            .map_err(|_| invalid("authority"))?;
+        outgoing_request
+            .set_path_with_query(parts.uri.path_and_query().map(|pq| pq.as_str()))
+            .map_err(|_| invalid("path"))?;
+
+        let options = timeout.map(|timeout| {
+            let options = types::RequestOptions::new();
+            let nanos = Some(timeout.as_nanos().min(u64::MAX as u128) as u64);
+            // Hosts that don't support a timeout reject it, leaving their default in place.
+            let _ = options.set_connect_timeout(nanos);
+            let _ = options.set_first_byte_timeout(nanos);
+            let _ = options.set_between_bytes_timeout(nanos);
+            options
+        });
+
+        let outgoing_body = outgoing_request
+            .body()
+            .map_err(|_| remote("request body unavailable"))?;
+        let future_response = outgoing_handler::handle(outgoing_request, options)
+            .map_err(|code| error_code(code, &authority))?;
+        write_body(&outgoing_body, &body, &authority)?;
+        types::OutgoingBody::finish(outgoing_body, None)
+            .map_err(|code| error_code(code, &authority))?;
+
+        future_response.subscribe().block();
+        let incoming_response = future_response
+            .get()
+            .ok_or_else(|| remote("response not ready"))?
+            .map_err(|_| remote("response already taken"))?
+            .map_err(|code| error_code(code, &authority))?;
+        read_response(incoming_response, &authority)
+    }
+
+    fn method(method: &http::Method) -> types::Method {
+        match method.as_str() {
+            "GET" => types::Method::Get,
+            "HEAD" => types::Method::Head,
+            "POST" => types::Method::Post,
+            "PUT" => types::Method::Put,
+            "DELETE" => types::Method::Delete,
+            "CONNECT" => types::Method::Connect,
+            "OPTIONS" => types::Method::Options,
+            "TRACE" => types::Method::Trace,
+            "PATCH" => types::Method::Patch,
+            other => types::Method::Other(other.to_string()),
+        }
+    }
+
+    fn write_body(
+        outgoing_body: &types::OutgoingBody,
+        body: &Bytes,
+        authority: &str,
+    ) -> Result<(), HttpError> {
+        if body.is_empty() {
+            return Ok(());
+        }
+        let stream = outgoing_body
+            .write()
+            .map_err(|_| remote("request body stream unavailable"))?;
+        for chunk in body.chunks(MAX_WRITE_SIZE) {
+            stream
+                .blocking_write_and_flush(chunk)
+                .map_err(|err| stream_error(err, "request body", authority))?;
+        }
+        Ok(())
+    }
+
+    fn read_response(
+        incoming_response: types::IncomingResponse,
+        authority: &str,
+    ) -> Result<Response, HttpError> {
+        let mut builder = http::Response::builder().status(incoming_response.status());
+        for (name, value) in incoming_response.headers().entries() {
+            builder = builder.header(name, value);
+        }
+
+        let incoming_body = incoming_response
+            .consume()
+            .map_err(|_| remote("response body unavailable"))?;
+        let mut buffer = Vec::new();
+        {
+            let stream = incoming_body
+                .stream()
+                .map_err(|_| remote("response body stream unavailable"))?;
+            loop {
+                match stream.blocking_read(READ_SIZE) {
+                    Ok(chunk) => buffer.extend(chunk),
+                    Err(StreamError::Closed) => break,
+                    Err(err) => return Err(stream_error(err, "response body", authority)),
+                }
+            }
+        }
+        // Trailers aren't exposed, but the body's failure to complete still is.
+        let trailers = types::IncomingBody::finish(incoming_body);
+        trailers.subscribe().block();
+        if let Some(Ok(Err(code))) = trailers.get() {
+            return Err(error_code(code, authority));
+        }
+
+        Ok(builder.body(Bytes::from(buffer))?)
+    }
+
+    fn remote(message: &str) -> HttpError {
+        HttpError::Remote {
+            message: message.to_string(),
+        }
+    }
+
+    fn stream_error(err: StreamError, context: &str, authority: &str) -> HttpError {
+        match err {
+            StreamError::Closed => remote(&format!("{} stream closed", context)),
+            StreamError::LastOperationFailed(err) => {
+                // The stream error may carry the HTTP error that caused it.
+                match types::http_error_code(&err) {
+                    Some(code) => error_code(code, authority),
+                    None => remote(&format!(
+                        "{} stream failed: {}",
+                        context,
+                        err.to_debug_string()
+                    )),
+                }
+            }
+        }
+    }
+
+    fn error_code(code: types::ErrorCode, authority: &str) -> HttpError {
+        match code {
+            types::ErrorCode::HttpRequestDenied => HttpError::Permission {
+                authority: authority.to_string(),
+            },
+            types::ErrorCode::HttpRequestUriInvalid
+            | types::ErrorCode::HttpRequestMethodInvalid
+            | types::ErrorCode::HttpRequestUriTooLong
+            | types::ErrorCode::HttpRequestHeaderSize(_)
+            | types::ErrorCode::HttpRequestHeaderSectionSize(_) => HttpError::InvalidRequest {
+                message: format!("{:?}", code),
+            },
+            types::ErrorCode::DnsTimeout
+            | types::ErrorCode::ConnectionTimeout
+            | types::ErrorCode::ConnectionReadTimeout
+            | types::ErrorCode::ConnectionWriteTimeout
+            | types::ErrorCode::HttpResponseTimeout => HttpError::Timeout,
+            types::ErrorCode::HttpResponseBodySize(_) => HttpError::BodyTooLarge,
+            code => HttpError::Remote {
+                message: format!("{:?}", code),
+            },
+        }
+    }
+}
@@ -18,8 +18,19 @@ pub mod wit {
 mod errors;
 mod from;
 mod host_api;
+pub mod http_client;
+pub mod log;
+pub mod metrics;
+#[cfg(feature = "test")]
+pub mod mock;
 pub mod redis;
 
+// Host functions are always called through `host` so that the `test` feature can route them to the mock host.
+#[cfg(feature = "test")]
+use mock::host;
+#[cfg(not(feature = "test"))]
+use wit::bulwark::plugin as host;
+
 pub use bulwark_decision::*;
 pub use errors::*;
 pub use host_api::*;
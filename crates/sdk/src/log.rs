@@ -0,0 +1,105 @@
+//! Structured logging from within a plugin.
+//!
+//! Events are emitted immediately within the span of the plugin execution that produced them, so they appear
+//! alongside the request they relate to in Bulwark's logs. Unlike output written to stdout or stderr, each event
+//! carries a level and a set of key/value fields. The [`trace!`](crate::trace), [`debug!`](crate::debug),
+//! [`info!`](crate::info), and [`warn!`](crate::warn) macros are the usual way to emit events. Since
+//! [`error!`](crate::error) creates an [`Error`](crate::Error), events at the error level are emitted with
+//! [`log!`](macro@crate::log) instead.
+//!
+//! # Example
+//!
+#![cfg_attr(doctest, doc = " ````no_test")]
+//! ```rust
+//! use bulwark_sdk::*;
+//!
+//! let attempts = 12;
+//! info!("checked rate limit");
+//! warn!(attempts = attempts, ip = "192.0.2.1"; "rate limit exceeded after {} attempts", attempts);
+//! log!(log::Level::Error, "state unavailable");
+//! ```
+
+// NOTE: variants are documented via Markdown instead of normal rustdoc because the underlying type is from the macro.
+/// The severity of a log event.
+///
+/// # Variants
+///
+/// * `Trace` - Very verbose information, typically only useful when debugging a plugin.
+/// * `Debug` - Information useful when debugging a plugin.
+/// * `Info` - Information about the normal operation of a plugin.
+/// * `Warn` - Potentially problematic conditions that did not prevent the plugin from running.
+/// * `Error` - Failures that prevented the plugin from doing what it intended.
+pub type Level = crate::wit::bulwark::plugin::log::Level;
+
+/// Emits a log event with the given level, message, and key/value fields.
+///
+/// This is typically called through one of the logging macros rather than directly.
+///
+/// # Arguments
+///
+/// * `level` - The severity of the event.
+/// * `message` - The message describing the event.
+/// * `fields` - Key/value pairs to attach to the event.
+pub fn emit<M: AsRef<str>>(level: Level, message: M, fields: &[(String, String)]) {
+    crate::host::log::emit(level, message.as_ref(), fields)
+}
+
+/// Emits a log event at the given [`Level`].
+///
+/// Fields are optional `key = value` pairs separated from the message by a semicolon. Any value implementing
+/// [`ToString`] may be used, and the message accepts the same arguments as [`format!`].
+///
+/// # Example
+///
+#[cfg_attr(doctest, doc = " ````no_test")]
+/// ```rust
+/// use bulwark_sdk::*;
+///
+/// log!(log::Level::Error, "state unavailable");
+/// log!(log::Level::Error, key = "rl:192.0.2.1"; "state unavailable for {}", "rate limit");
+/// ```
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($key:ident = $value:expr),+ ; $($arg:tt)+) => {
+        $crate::log::emit(
+            $level,
+            format!($($arg)+),
+            &[$((stringify!($key).to_string(), $value.to_string())),+],
+        )
+    };
+    ($level:expr, $($arg:tt)+) => {
+        $crate::log::emit($level, format!($($arg)+), &[])
+    };
+}
+
+/// Emits a log event at the trace level. See [`log!`] for the syntax.
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Trace, $($arg)+)
+    };
+}
+
+/// Emits a log event at the debug level. See [`log!`] for the syntax.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Debug, $($arg)+)
+    };
+}
+
+/// Emits a log event at the info level. See [`log!`] for the syntax.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Info, $($arg)+)
+    };
+}
+
+/// Emits a log event at the warn level. See [`log!`] for the syntax.
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Warn, $($arg)+)
+    };
+}
@@ -0,0 +1,91 @@
+//! The `metrics` module records counters, gauges, and histograms from a plugin.
+//!
+//! Metrics are forwarded by the host to the same Prometheus or StatsD exporter as Bulwark's own metrics. Names are
+//! prefixed with `plugin_metric_` and the plugin's reference is added as a `plugin` label, so a histogram named
+//! `feature_score` recorded by the `bot_detection` plugin is reported as `plugin_metric_feature_score` with
+//! `plugin="bot_detection"`. Metric and label names must start with a letter and contain only letters, digits, and
+//! underscores, and the `plugin` label is reserved for the host. Since names are shared between plugins, a name
+//! recorded as one kind of metric by any plugin is rejected with [`MetricsError::KindMismatch`] if recorded as
+//! another kind.
+//!
+//! The host limits the number of metrics a plugin may record, the number of labels on each metric, and the number
+//! of distinct label combinations for each metric. Values that would exceed a limit are rejected with
+//! [`MetricsError::Cardinality`], so labels should be drawn from small, fixed sets of values rather than from
+//! request data like IP addresses.
+//!
+//! # Example
+//!
+#![cfg_attr(doctest, doc = " ````no_test")]
+//! ```rust
+//! use bulwark_sdk::*;
+//!
+//! metrics::increment_counter("requests_scored", 1, &[("model", "v2")])?;
+//! metrics::record_histogram("feature_score", 0.75, &[("model", "v2"), ("feature", "mouse_velocity")])?;
+//! metrics::set_gauge("model_generation", 2.0, &[])?;
+//! ```
+
+use crate::MetricsError;
+
+/// Increments the named counter by the given value.
+///
+/// # Arguments
+///
+/// * `name` - The name of the counter, without the plugin's namespace.
+/// * `value` - The amount to increment the counter by.
+/// * `labels` - Key/value pairs that distinguish the series of the counter.
+pub fn increment_counter<N: AsRef<str>>(
+    name: N,
+    value: u64,
+    labels: &[(&str, &str)],
+) -> Result<(), MetricsError> {
+    Ok(crate::host::metrics::increment_counter(
+        name.as_ref(),
+        value,
+        &to_labels(labels),
+    )?)
+}
+
+/// Sets the named gauge to the given value.
+///
+/// # Arguments
+///
+/// * `name` - The name of the gauge, without the plugin's namespace.
+/// * `value` - The new value of the gauge.
+/// * `labels` - Key/value pairs that distinguish the series of the gauge.
+pub fn set_gauge<N: AsRef<str>>(
+    name: N,
+    value: f64,
+    labels: &[(&str, &str)],
+) -> Result<(), MetricsError> {
+    Ok(crate::host::metrics::set_gauge(
+        name.as_ref(),
+        value,
+        &to_labels(labels),
+    )?)
+}
+
+/// Records the given value in the named histogram.
+///
+/// # Arguments
+///
+/// * `name` - The name of the histogram, without the plugin's namespace.
+/// * `value` - The value to record.
+/// * `labels` - Key/value pairs that distinguish the series of the histogram.
+pub fn record_histogram<N: AsRef<str>>(
+    name: N,
+    value: f64,
+    labels: &[(&str, &str)],
+) -> Result<(), MetricsError> {
+    Ok(crate::host::metrics::record_histogram(
+        name.as_ref(),
+        value,
+        &to_labels(labels),
+    )?)
+}
+
+fn to_labels(labels: &[(&str, &str)]) -> Vec<(String, String)> {
+    labels
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
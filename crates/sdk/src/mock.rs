@@ -0,0 +1,177 @@
+//! A mock host for running plugins natively in unit tests.
+//!
+//! With the `test` feature enabled, every host function the SDK calls is answered by the [`MockHost`] installed on
+//! the current thread rather than by the Bulwark host. Since tests run on separate threads, each test sees only the
+//! host it installed. Plugins should add the feature to their dev-dependencies only, so that it never ends up in a
+//! WebAssembly build:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! bulwark-sdk = { version = "0.5.0", features = ["test"] }
+//! ```
+//!
+//! The `bulwark_plugin` macro makes each handler, including the generated no-op handlers, callable on the plugin
+//! type in test builds, so a unit test can install a host, build a request, and call a handler directly:
+//!
+//! ```rust,ignore
+//! use bulwark_sdk::*;
+//! use std::collections::HashMap;
+//!
+//! struct ExamplePlugin;
+//!
+//! #[bulwark_plugin]
+//! impl HttpHandlers for ExamplePlugin {
+//!     fn handle_request_decision(
+//!         req: Request,
+//!         _labels: HashMap<String, String>,
+//!     ) -> Result<HandlerOutput, Error> {
+//!         let mut output = HandlerOutput::default();
+//!         let rate = redis::incr_rate_limit(format!("ip:{}", client_ip(&req).unwrap()), 1, 60)?;
+//!         if rate.attempts > config_var("limit").unwrap().as_i64().unwrap() {
+//!             output.decision = Decision::restricted(0.5);
+//!         }
+//!         Ok(output)
+//!     }
+//! }
+//!
+//! #[cfg(test)]
+//! mod tests {
+//!     use super::*;
+//!     use bulwark_sdk::mock::MockHost;
+//!
+//!     #[test]
+//!     fn test_rate_limit() -> Result<(), Error> {
+//!         MockHost::new().config_var("limit", 1).proxy_hops(1).install();
+//!         let request = || {
+//!             http::Request::builder()
+//!                 .uri("/")
+//!                 .header("x-forwarded-for", "192.0.2.1")
+//!                 .body(Bytes::new())
+//!         };
+//!
+//!         let output = ExamplePlugin::handle_request_decision(request()?, HashMap::new())?;
+//!         assert_eq!(output.decision, Decision::default());
+//!         let output = ExamplePlugin::handle_request_decision(request()?, HashMap::new())?;
+//!         assert_eq!(output.decision, Decision::restricted(0.5));
+//!         Ok(())
+//!     }
+//! }
+//! ```
+//!
+//! Redis is simulated in memory, including the rate limit and circuit breaker scripts, and expirations are measured
+//! against the mock clock, which follows the system clock unless [`MockHost::time`] or [`set_time`] fixes it.
+//! Outbound HTTP requests sent through [`http_client`](crate::http_client) are answered with the responses given to
+//! [`MockHost::http_response`], and are recorded so that tests can inspect them with [`take_requests`]. Only
+//! [`http_client`](crate::http_client) is covered: calls a plugin makes to `wasi:http/outgoing-handler` itself,
+//! directly or through another crate, bypass the mock and can't run natively. Log events are likewise recorded for
+//! [`take_logs`], and metrics for [`take_metrics`]. The host's limits on metric names and cardinality are not
+//! enforced by the mock.
+
+use crate::Request;
+use serde_json::{Map, Value};
+use std::{cell::RefCell, collections::BTreeMap};
+
+mod http;
+pub(crate) mod log;
+pub(crate) mod metrics;
+pub(crate) mod redis;
+
+pub(crate) use self::http::send;
+pub use self::http::take_requests;
+pub use self::log::{take_logs, LogEvent};
+pub use self::metrics::{take_metrics, MetricEvent, MetricKind};
+
+thread_local! {
+    static HOST: RefCell<MockHost> = RefCell::new(MockHost::new());
+}
+
+/// The configuration and state that the mock host functions answer with.
+#[derive(Debug, Default)]
+pub struct MockHost {
+    config: Map<String, Value>,
+    proxy_hops: u8,
+    state_permissions: Option<Vec<String>>,
+    http_permissions: Option<Vec<String>>,
+    time: Option<i64>,
+    redis: BTreeMap<String, redis::Entry>,
+    http_responses: Vec<(String, self::http::MockResponse)>,
+    requests: Vec<Request>,
+    logs: Vec<LogEvent>,
+    metrics: Vec<MetricEvent>,
+}
+
+impl MockHost {
+    /// Creates a mock host with no config, no proxy hops, an empty Redis, and unrestricted access to state keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets a plugin config value.
+    pub fn config_var<K: Into<String>, V: Into<Value>>(mut self, key: K, value: V) -> Self {
+        self.config.insert(key.into(), value.into());
+        self
+    }
+
+    /// Sets the number of proxy hops expected exterior to Bulwark, used by [`client_ip`](crate::client_ip).
+    pub fn proxy_hops(mut self, proxy_hops: u8) -> Self {
+        self.proxy_hops = proxy_hops;
+        self
+    }
+
+    /// Fixes the mock clock at the given unix timestamp in seconds.
+    pub fn time(mut self, unix_time: i64) -> Self {
+        self.time = Some(unix_time);
+        self
+    }
+
+    /// Installs the mock host for the current thread, replacing any previously installed host and its state.
+    pub fn install(self) {
+        HOST.with(|host| *host.borrow_mut() = self);
+    }
+}
+
+/// Fixes the mock clock of the installed host at the given unix timestamp in seconds.
+///
+/// This is typically used to move past the end of a rate limit or circuit breaker window.
+pub fn set_time(unix_time: i64) {
+    HOST.with(|host| host.borrow_mut().time = Some(unix_time));
+}
+
+/// Calls a function with the mock host installed on the current thread.
+fn with_host<T>(f: impl FnOnce(&mut MockHost) -> T) -> T {
+    HOST.with(|host| f(&mut host.borrow_mut()))
+}
+
+impl MockHost {
+    /// Returns the current unix timestamp in seconds according to the mock clock.
+    fn now(&self) -> i64 {
+        self.time.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs() as i64)
+        })
+    }
+}
+
+/// Stand-ins for the host imports, mirroring the generated bindings in [`crate::wit`].
+pub(crate) mod host {
+    pub(crate) use super::{log, metrics, redis};
+
+    pub(crate) mod config {
+        use super::super::with_host;
+
+        pub(crate) fn config_keys() -> Vec<String> {
+            with_host(|host| host.config.keys().cloned().collect())
+        }
+
+        pub(crate) fn config_json() -> String {
+            with_host(|host| {
+                serde_json::to_string(&host.config).expect("config should always serialize")
+            })
+        }
+
+        pub(crate) fn proxy_hops() -> u8 {
+            with_host(|host| host.proxy_hops)
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! The outbound HTTP handler of the mock host.
+//!
+//! Only requests sent through [`http_client`](crate::http_client) reach the mock. A plugin that calls
+//! `wasi:http/outgoing-handler` itself, directly or through another crate, bypasses the mock host entirely, so those
+//! calls can't be answered in native tests.
+
+use super::{with_host, MockHost, HOST};
+use crate::{Bytes, HttpError, Request, Response};
+use std::time::Duration;
+
+impl MockHost {
+    /// Grants outbound HTTP access to the given domain.
+    ///
+    /// Once any domain has been granted, requests to other domains fail with a permission error, the same as they
+    /// would for a plugin whose config lacked the grant.
+    pub fn http_permission<D: Into<String>>(mut self, domain: D) -> Self {
+        self.http_permissions
+            .get_or_insert_with(Vec::new)
+            .push(domain.into());
+        self
+    }
+
+    /// Answers every outbound HTTP request for the given absolute URI with a response.
+    ///
+    /// Requests for URIs without a response fail with a remote error.
+    pub fn http_response<U: Into<String>>(mut self, uri: U, response: Response) -> Self {
+        let (parts, body) = response.into_parts();
+        self.http_responses.push((
+            uri.into(),
+            MockResponse {
+                status: parts.status,
+                headers: parts.headers,
+                body,
+            },
+        ));
+        self
+    }
+}
+
+/// Removes and returns the outbound HTTP requests sent through the installed host, in the order they were sent.
+pub fn take_requests() -> Vec<Request> {
+    HOST.with(|host| std::mem::take(&mut host.borrow_mut().requests))
+}
+
+/// A response to an outbound HTTP request, kept in parts so it can be returned more than once.
+#[derive(Debug, Clone)]
+pub(super) struct MockResponse {
+    status: http::StatusCode,
+    headers: http::HeaderMap,
+    body: Bytes,
+}
+
+/// Stands in for the host's outbound HTTP handler.
+pub(crate) fn send(request: Request, _timeout: Option<Duration>) -> Result<Response, HttpError> {
+    let authority = request
+        .uri()
+        .authority()
+        .ok_or_else(|| HttpError::InvalidRequest {
+            message: "uri must be absolute".to_string(),
+        })?
+        .to_string();
+    with_host(|host| {
+        if let Some(domains) = &host.http_permissions {
+            if !domains
+                .iter()
+                .any(|domain| Some(domain.as_str()) == request.uri().host())
+            {
+                return Err(HttpError::Permission { authority });
+            }
+        }
+        let uri = request.uri().to_string();
+        let method = request.method().clone();
+        let response = host
+            .http_responses
+            .iter()
+            .find(|(response_uri, _)| *response_uri == uri)
+            .map(|(_, response)| response.clone());
+        host.requests.push(request);
+
+        let response = response.ok_or_else(|| HttpError::Remote {
+            message: format!("no mock response for {} {}", method, uri),
+        })?;
+        let mut builder = http::Response::builder().status(response.status);
+        if let Some(headers) = builder.headers_mut() {
+            *headers = response.headers;
+        }
+        Ok(builder.body(response.body)?)
+    })
+}
@@ -0,0 +1,30 @@
+//! The log events recorded by the mock host.
+
+use super::{with_host, HOST};
+use crate::log::Level;
+
+/// Removes and returns the log events emitted through the installed host, in the order they were emitted.
+pub fn take_logs() -> Vec<LogEvent> {
+    HOST.with(|host| std::mem::take(&mut host.borrow_mut().logs))
+}
+
+/// A log event emitted by a plugin through the mock host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEvent {
+    /// The severity of the event.
+    pub level: Level,
+    /// The message describing the event.
+    pub message: String,
+    /// The key/value pairs attached to the event, in the order they were given.
+    pub fields: Vec<(String, String)>,
+}
+
+pub(crate) fn emit(level: Level, message: &str, fields: &[(String, String)]) {
+    with_host(|host| {
+        host.logs.push(LogEvent {
+            level,
+            message: message.to_string(),
+            fields: fields.to_vec(),
+        })
+    })
+}
@@ -0,0 +1,69 @@
+//! The metrics recorded by the mock host.
+//!
+//! The host's limits on metric names and cardinality are not enforced.
+
+use super::{with_host, HOST};
+use crate::wit::bulwark::plugin::metrics::Error;
+
+/// Removes and returns the metrics recorded through the installed host, in the order they were recorded.
+pub fn take_metrics() -> Vec<MetricEvent> {
+    HOST.with(|host| std::mem::take(&mut host.borrow_mut().metrics))
+}
+
+/// The kinds of metric a plugin may record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+/// A metric recorded by a plugin through the mock host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricEvent {
+    /// The kind of metric recorded.
+    pub kind: MetricKind,
+    /// The name of the metric, without the plugin's namespace.
+    pub name: String,
+    /// The amount a counter was incremented by, the value a gauge was set to, or the value a histogram recorded.
+    pub value: f64,
+    /// The key/value pairs that distinguish the series of the metric, in the order they were given.
+    pub labels: Vec<(String, String)>,
+}
+
+pub(crate) fn increment_counter(
+    name: &str,
+    value: u64,
+    labels: &[(String, String)],
+) -> Result<(), Error> {
+    record(MetricKind::Counter, name, value as f64, labels)
+}
+
+pub(crate) fn set_gauge(name: &str, value: f64, labels: &[(String, String)]) -> Result<(), Error> {
+    record(MetricKind::Gauge, name, value, labels)
+}
+
+pub(crate) fn record_histogram(
+    name: &str,
+    value: f64,
+    labels: &[(String, String)],
+) -> Result<(), Error> {
+    record(MetricKind::Histogram, name, value, labels)
+}
+
+fn record(
+    kind: MetricKind,
+    name: &str,
+    value: f64,
+    labels: &[(String, String)],
+) -> Result<(), Error> {
+    with_host(|host| {
+        host.metrics.push(MetricEvent {
+            kind,
+            name: name.to_string(),
+            value,
+            labels: labels.to_vec(),
+        });
+        Ok(())
+    })
+}
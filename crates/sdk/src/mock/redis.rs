@@ -0,0 +1,413 @@
+//! The in-memory Redis that answers the mock host's state functions.
+//!
+//! The rate limit and circuit breaker functions mirror the host's Lua scripts, using the same keys.
+
+use super::{with_host, MockHost};
+use crate::wit::bulwark::plugin::redis::{Breaker, Error, Rate};
+use std::collections::BTreeSet;
+
+impl MockHost {
+    /// Grants access to state keys starting with the given prefix.
+    ///
+    /// Once any prefix has been granted, access to keys outside of the granted prefixes fails with a permission
+    /// error, the same as it would for a plugin whose config lacked the grant.
+    pub fn state_permission<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.state_permissions
+            .get_or_insert_with(Vec::new)
+            .push(prefix.into());
+        self
+    }
+}
+
+/// A value held by the in-memory Redis.
+#[derive(Debug, Clone)]
+pub(super) struct Entry {
+    value: EntryValue,
+    /// The unix timestamp at which the key is removed.
+    expire_at: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+enum EntryValue {
+    Bytes(Vec<u8>),
+    Set(BTreeSet<String>),
+}
+
+impl MockHost {
+    fn verify_state_prefix(&self, key: &str) -> Result<(), Error> {
+        if let Some(prefixes) = &self.state_permissions {
+            if !prefixes.iter().any(|prefix| key.starts_with(prefix)) {
+                return Err(Error::Permission(key.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the live entry for a key, removing it first if it has expired.
+    fn entry(&mut self, key: &str) -> Option<&mut Entry> {
+        let now = self.now();
+        if matches!(self.redis.get(key), Some(Entry { expire_at: Some(expire_at), .. }) if *expire_at <= now)
+        {
+            self.redis.remove(key);
+        }
+        self.redis.get_mut(key)
+    }
+
+    fn get(&mut self, key: &str) -> Result<Option<Vec<u8>>, Error> {
+        match self.entry(key) {
+            Some(Entry {
+                value: EntryValue::Bytes(bytes),
+                ..
+            }) => Ok(Some(bytes.clone())),
+            Some(_) => Err(wrong_type()),
+            None => Ok(None),
+        }
+    }
+
+    fn get_i64(&mut self, key: &str) -> Result<Option<i64>, Error> {
+        self.get(key)?
+            .map(|bytes| {
+                std::str::from_utf8(&bytes)
+                    .ok()
+                    .and_then(|value| value.parse::<i64>().ok())
+                    .ok_or_else(not_an_integer)
+            })
+            .transpose()
+    }
+
+    /// Sets a key, clearing any expiration, as Redis does.
+    fn set(&mut self, key: &str, value: &[u8]) {
+        self.redis.insert(
+            key.to_string(),
+            Entry {
+                value: EntryValue::Bytes(value.to_vec()),
+                expire_at: None,
+            },
+        );
+    }
+
+    /// Increments a key, keeping its expiration.
+    fn incr_by(&mut self, key: &str, delta: i64) -> Result<i64, Error> {
+        let value = self
+            .get_i64(key)?
+            .unwrap_or_default()
+            .checked_add(delta)
+            .ok_or_else(|| Error::Remote("increment or decrement would overflow".to_string()))?;
+        let bytes = value.to_string().into_bytes();
+        match self.entry(key) {
+            Some(entry) => entry.value = EntryValue::Bytes(bytes),
+            None => self.set(key, &bytes),
+        }
+        Ok(value)
+    }
+
+    fn expire_at(&mut self, key: &str, unix_time: i64) -> bool {
+        match self.entry(key) {
+            Some(entry) => {
+                entry.expire_at = Some(unix_time);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn set_members(&mut self, key: &str) -> Result<Option<&mut BTreeSet<String>>, Error> {
+        match self.entry(key) {
+            Some(Entry {
+                value: EntryValue::Set(members),
+                ..
+            }) => Ok(Some(members)),
+            Some(_) => Err(wrong_type()),
+            None => Ok(None),
+        }
+    }
+}
+
+fn wrong_type() -> Error {
+    Error::Remote("WRONGTYPE: Operation against a key holding the wrong kind of value".to_string())
+}
+
+fn not_an_integer() -> Error {
+    Error::Remote("value is not an integer or out of range".to_string())
+}
+
+pub(crate) fn get(key: &str) -> Result<Option<Vec<u8>>, Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+        host.get(key)
+    })
+}
+
+pub(crate) fn set(key: &str, value: &[u8]) -> Result<(), Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+        host.set(key, value);
+        Ok(())
+    })
+}
+
+pub(crate) fn del(keys: &[String]) -> Result<u32, Error> {
+    with_host(|host| {
+        for key in keys {
+            host.verify_state_prefix(key)?;
+        }
+        let mut removed = 0;
+        for key in keys {
+            if host.entry(key).is_some() {
+                host.redis.remove(key);
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    })
+}
+
+pub(crate) fn incr(key: &str) -> Result<i64, Error> {
+    incr_by(key, 1)
+}
+
+pub(crate) fn incr_by(key: &str, delta: i64) -> Result<i64, Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+        host.incr_by(key, delta)
+    })
+}
+
+pub(crate) fn sadd(key: &str, values: &[String]) -> Result<u32, Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+        if host.set_members(key)?.is_none() {
+            host.redis.insert(
+                key.to_string(),
+                Entry {
+                    value: EntryValue::Set(BTreeSet::new()),
+                    expire_at: None,
+                },
+            );
+        }
+        let members = host
+            .set_members(key)?
+            .expect("set should have been created");
+        Ok(values
+            .iter()
+            .filter(|value| members.insert(value.to_string()))
+            .count() as u32)
+    })
+}
+
+pub(crate) fn smembers(key: &str) -> Result<Vec<String>, Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+        Ok(host
+            .set_members(key)?
+            .map(|members| members.iter().cloned().collect())
+            .unwrap_or_default())
+    })
+}
+
+pub(crate) fn srem(key: &str, values: &[String]) -> Result<u32, Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+        let Some(members) = host.set_members(key)? else {
+            return Ok(0);
+        };
+        let removed = values.iter().filter(|value| members.remove(*value)).count();
+        // Redis removes sets once they're empty.
+        if members.is_empty() {
+            host.redis.remove(key);
+        }
+        Ok(removed as u32)
+    })
+}
+
+pub(crate) fn expire(key: &str, ttl: u64) -> Result<(), Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+        let unix_time = host.now().saturating_add(ttl as i64);
+        host.expire_at(key, unix_time);
+        Ok(())
+    })
+}
+
+pub(crate) fn expire_at(key: &str, unix_time: u64) -> Result<(), Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+        host.expire_at(key, unix_time as i64);
+        Ok(())
+    })
+}
+
+pub(crate) fn incr_rate_limit(key: &str, delta: i64, window: i64) -> Result<Rate, Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+        if delta < 0 {
+            return Err(Error::InvalidArgument("delta must be positive".to_string()));
+        }
+        if window < 0 {
+            return Err(Error::InvalidArgument(
+                "window must be positive".to_string(),
+            ));
+        }
+
+        // Mirrors the host's increment_rate_limit script.
+        let counter_key = format!("bulwark:rl:{}", key);
+        let expiration_key = format!("{}:exp", counter_key);
+        let timestamp = host.now();
+        let mut expiration = host.get_i64(&expiration_key)?;
+        let next_expiration = timestamp + window;
+        if !matches!(expiration, Some(expiration) if timestamp <= expiration) {
+            host.set(&expiration_key, next_expiration.to_string().as_bytes());
+            host.set(&counter_key, b"0");
+            host.expire_at(&expiration_key, next_expiration + 1);
+            host.expire_at(&counter_key, next_expiration + 1);
+            expiration = Some(next_expiration);
+        }
+        let attempts = host.incr_by(&counter_key, delta)?;
+        Ok(Rate {
+            attempts,
+            expiration: expiration.unwrap_or_default(),
+        })
+    })
+}
+
+pub(crate) fn check_rate_limit(key: &str) -> Result<Option<Rate>, Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+
+        // Mirrors the host's check_rate_limit script.
+        let counter_key = format!("bulwark:rl:{}", key);
+        let expiration_key = format!("{}:exp", counter_key);
+        let timestamp = host.now();
+        let mut attempts = host.get_i64(&counter_key)?.unwrap_or_default();
+        let mut expiration = host.get_i64(&expiration_key)?.unwrap_or_default();
+        if timestamp > expiration {
+            host.redis.remove(&counter_key);
+            host.redis.remove(&expiration_key);
+            attempts = 0;
+            expiration = 0;
+        }
+        Ok((attempts > 0).then_some(Rate {
+            attempts,
+            expiration,
+        }))
+    })
+}
+
+pub(crate) fn incr_breaker(
+    key: &str,
+    success_delta: i64,
+    failure_delta: i64,
+    window: i64,
+) -> Result<Breaker, Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+        if success_delta < 0 {
+            return Err(Error::InvalidArgument(
+                "success_delta must be positive".to_string(),
+            ));
+        }
+        if failure_delta < 0 {
+            return Err(Error::InvalidArgument(
+                "failure_delta must be positive".to_string(),
+            ));
+        }
+        if window < 0 {
+            return Err(Error::InvalidArgument(
+                "window must be positive".to_string(),
+            ));
+        }
+
+        // Mirrors the host's increment_breaker script.
+        let keys = BreakerKeys::new(key);
+        let expiration = host.now() + window;
+        let generation = host.incr_by(&keys.generation, 1)?;
+        let (successes, failures, consecutive_successes, consecutive_failures);
+        if success_delta > 0 {
+            successes = host.incr_by(&keys.successes, success_delta)?;
+            failures = host.get_i64(&keys.failures)?.unwrap_or_default();
+            consecutive_successes = host.incr_by(&keys.consecutive_successes, success_delta)?;
+            host.set(&keys.consecutive_failures, b"0");
+            consecutive_failures = 0;
+        } else {
+            successes = host.get_i64(&keys.successes)?.unwrap_or_default();
+            failures = host.incr_by(&keys.failures, failure_delta)?;
+            host.set(&keys.consecutive_successes, b"0");
+            consecutive_successes = 0;
+            consecutive_failures = host.incr_by(&keys.consecutive_failures, failure_delta)?;
+        }
+        host.set(&keys.expiration, expiration.to_string().as_bytes());
+        for key in keys.all() {
+            host.expire_at(key, expiration + 1);
+        }
+        Ok(Breaker {
+            generation,
+            successes,
+            failures,
+            consecutive_successes,
+            consecutive_failures,
+            expiration,
+        })
+    })
+}
+
+pub(crate) fn check_breaker(key: &str) -> Result<Option<Breaker>, Error> {
+    with_host(|host| {
+        host.verify_state_prefix(key)?;
+
+        // Mirrors the host's check_breaker script.
+        let keys = BreakerKeys::new(key);
+        let generation = host.get_i64(&keys.generation)?.unwrap_or_default();
+        if generation <= 0 {
+            for key in keys.all() {
+                host.redis.remove(key);
+            }
+            return Ok(None);
+        }
+        Ok(Some(Breaker {
+            generation,
+            successes: host.get_i64(&keys.successes)?.unwrap_or_default(),
+            failures: host.get_i64(&keys.failures)?.unwrap_or_default(),
+            consecutive_successes: host
+                .get_i64(&keys.consecutive_successes)?
+                .unwrap_or_default(),
+            consecutive_failures: host
+                .get_i64(&keys.consecutive_failures)?
+                .unwrap_or_default(),
+            expiration: host.get_i64(&keys.expiration)?.unwrap_or_default(),
+        }))
+    })
+}
+
+/// The Redis keys holding a circuit breaker's counters.
+struct BreakerKeys {
+    generation: String,
+    successes: String,
+    failures: String,
+    consecutive_successes: String,
+    consecutive_failures: String,
+    expiration: String,
+}
+
+impl BreakerKeys {
+    fn new(key: &str) -> Self {
+        Self {
+            generation: format!("bulwark:bk:g:{}", key),
+            successes: format!("bulwark:bk:s:{}", key),
+            failures: format!("bulwark:bk:f:{}", key),
+            consecutive_successes: format!("bulwark:bk:cs:{}", key),
+            consecutive_failures: format!("bulwark:bk:cf:{}", key),
+            expiration: format!("bulwark:bk:{}:exp", key),
+        }
+    }
+
+    fn all(&self) -> [&String; 6] {
+        [
+            &self.generation,
+            &self.successes,
+            &self.failures,
+            &self.consecutive_successes,
+            &self.consecutive_failures,
+            &self.expiration,
+        ]
+    }
+}
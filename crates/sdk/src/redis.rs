@@ -28,7 +28,7 @@ pub type Rate = crate::wit::bulwark::plugin::redis::Rate;
 /// * `key` - The key name corresponding to the state value.
 pub fn get<K: AsRef<str>>(key: K) -> Result<Option<Vec<u8>>, crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::get(key)?)
+    Ok(crate::host::redis::get(key)?)
 }
 
 /// Returns the named state value retrieved from Redis as a string.
@@ -41,7 +41,7 @@ pub fn get<K: AsRef<str>>(key: K) -> Result<Option<Vec<u8>>, crate::RemoteStateE
 /// * `key` - The key name corresponding to the state value.
 pub fn get_string<K: AsRef<str>>(key: K) -> Result<Option<String>, crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    let bytes = crate::wit::bulwark::plugin::redis::get(key)?;
+    let bytes = crate::host::redis::get(key)?;
     if let Some(bytes) = bytes {
         Ok(Some(String::from_utf8(bytes)?))
     } else {
@@ -61,7 +61,7 @@ pub fn get_string<K: AsRef<str>>(key: K) -> Result<Option<String>, crate::Remote
 /// * `key` - The key name corresponding to the state value.
 pub fn get_i64<K: AsRef<str>>(key: K) -> Result<Option<i64>, crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    let bytes = crate::wit::bulwark::plugin::redis::get(key)?;
+    let bytes = crate::host::redis::get(key)?;
     if let Some(bytes) = bytes {
         Ok(Some(parse_i64(bytes)?))
     } else {
@@ -81,7 +81,7 @@ pub fn get_i64<K: AsRef<str>>(key: K) -> Result<Option<i64>, crate::RemoteStateE
 pub fn set<K: AsRef<str>, V: AsRef<[u8]>>(key: K, value: V) -> Result<(), crate::RemoteStateError> {
     let key: &str = key.as_ref();
     let value: &[u8] = value.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::set(key, value)?)
+    Ok(crate::host::redis::set(key, value)?)
 }
 
 /// Set a named string value in Redis.
@@ -99,10 +99,7 @@ pub fn set_string<K: AsRef<str>, V: AsRef<str>>(
 ) -> Result<(), crate::RemoteStateError> {
     let key: &str = key.as_ref();
     let value: &str = value.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::set(
-        key,
-        value.as_bytes(),
-    )?)
+    Ok(crate::host::redis::set(key, value.as_bytes())?)
 }
 
 /// Set a named integer value in Redis.
@@ -116,10 +113,7 @@ pub fn set_string<K: AsRef<str>, V: AsRef<str>>(
 /// * `value` - The value to record. Values are byte strings, but may be interpreted differently by Redis depending on context.
 pub fn set_i64<K: AsRef<str>>(key: K, value: i64) -> Result<(), crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::set(
-        key,
-        value.to_string().as_bytes(),
-    )?)
+    Ok(crate::host::redis::set(key, value.to_string().as_bytes())?)
 }
 
 /// Deletes named values in Redis.
@@ -134,7 +128,7 @@ pub fn del<I: IntoIterator<Item = T>, T: Into<String>>(
     keys: I,
 ) -> Result<u32, crate::RemoteStateError> {
     let keys: Vec<String> = keys.into_iter().map(|s| s.into()).collect();
-    Ok(crate::wit::bulwark::plugin::redis::del(keys.as_slice())?)
+    Ok(crate::host::redis::del(keys.as_slice())?)
 }
 
 /// Increments a named counter in Redis.
@@ -149,7 +143,7 @@ pub fn del<I: IntoIterator<Item = T>, T: Into<String>>(
 /// * `key` - The key name corresponding to the state counter.
 pub fn incr<K: AsRef<str>>(key: K) -> Result<i64, crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::incr(key)?)
+    Ok(crate::host::redis::incr(key)?)
 }
 
 /// Increments a named counter in Redis by a specified delta value.
@@ -165,7 +159,7 @@ pub fn incr<K: AsRef<str>>(key: K) -> Result<i64, crate::RemoteStateError> {
 /// * `delta` - The amount to increase the counter by.
 pub fn incr_by<K: AsRef<str>>(key: K, delta: i64) -> Result<i64, crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::incr_by(key, delta)?)
+    Ok(crate::host::redis::incr_by(key, delta)?)
 }
 
 /// Adds a set of members to a set in Redis.
@@ -183,10 +177,7 @@ pub fn sadd<K: AsRef<str>, I: IntoIterator<Item = T>, T: Into<String>>(
 ) -> Result<u32, crate::RemoteStateError> {
     let key: &str = key.as_ref();
     let members: Vec<String> = members.into_iter().map(|s| s.into()).collect();
-    Ok(crate::wit::bulwark::plugin::redis::sadd(
-        key,
-        members.as_slice(),
-    )?)
+    Ok(crate::host::redis::sadd(key, members.as_slice())?)
 }
 
 /// Retrieves a set of members stored in Redis.
@@ -196,7 +187,7 @@ pub fn sadd<K: AsRef<str>, I: IntoIterator<Item = T>, T: Into<String>>(
 /// * `key` - The key name corresponding to the set.
 pub fn smembers<K: AsRef<str>>(key: K) -> Result<Vec<String>, crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::smembers(key)?)
+    Ok(crate::host::redis::smembers(key)?)
 }
 
 /// Removes members from a set in Redis.
@@ -214,10 +205,7 @@ pub fn srem<K: AsRef<str>, I: IntoIterator<Item = T>, T: Into<String>>(
 ) -> Result<u32, crate::RemoteStateError> {
     let key: &str = key.as_ref();
     let members: Vec<String> = members.into_iter().map(|s| s.into()).collect();
-    Ok(crate::wit::bulwark::plugin::redis::srem(
-        key,
-        members.as_slice(),
-    )?)
+    Ok(crate::host::redis::srem(key, members.as_slice())?)
 }
 
 /// Sets an expiration on a named value in Redis with a TTL.
@@ -231,7 +219,7 @@ pub fn srem<K: AsRef<str>, I: IntoIterator<Item = T>, T: Into<String>>(
 /// * `ttl` - The time-to-live for the value in seconds.
 pub fn expire<K: AsRef<str>>(key: K, ttl: u64) -> Result<(), crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::expire(key, ttl)?)
+    Ok(crate::host::redis::expire(key, ttl)?)
 }
 
 /// Sets an expiration on a named value in Redis to a specific time.
@@ -245,9 +233,7 @@ pub fn expire<K: AsRef<str>>(key: K, ttl: u64) -> Result<(), crate::RemoteStateE
 /// * `unix_time` - The unix timestamp in seconds.
 pub fn expire_at<K: AsRef<str>>(key: K, unix_time: u64) -> Result<(), crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::expire_at(
-        key, unix_time,
-    )?)
+    Ok(crate::host::redis::expire_at(key, unix_time)?)
 }
 
 /// Increments a rate limit, returning the number of attempts so far and the expiration time.
@@ -305,9 +291,7 @@ pub fn incr_rate_limit<K: AsRef<str>>(
     window: i64,
 ) -> Result<Rate, crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::incr_rate_limit(
-        key, delta, window,
-    )?)
+    Ok(crate::host::redis::incr_rate_limit(key, delta, window)?)
 }
 
 /// Checks a rate limit, returning the number of attempts so far and the expiration time.
@@ -367,7 +351,7 @@ pub fn incr_rate_limit<K: AsRef<str>>(
 /// See [`incr_rate_limit`] for a simpler example covering only the request handler.
 pub fn check_rate_limit<K: AsRef<str>>(key: K) -> Result<Option<Rate>, crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::check_rate_limit(key)?)
+    Ok(crate::host::redis::check_rate_limit(key)?)
 }
 
 /// Increments a circuit breaker, returning the generation count, success count, failure count,
@@ -431,7 +415,7 @@ pub fn incr_breaker<K: AsRef<str>>(
         true => (delta, 0),
         false => (0, delta),
     };
-    Ok(crate::wit::bulwark::plugin::redis::incr_breaker(
+    Ok(crate::host::redis::incr_breaker(
         key,
         success_delta,
         failure_delta,
@@ -499,7 +483,7 @@ pub fn incr_breaker<K: AsRef<str>>(
 #[inline]
 pub fn check_breaker<K: AsRef<str>>(key: K) -> Result<Option<Breaker>, crate::RemoteStateError> {
     let key: &str = key.as_ref();
-    Ok(crate::wit::bulwark::plugin::redis::check_breaker(key)?)
+    Ok(crate::host::redis::check_breaker(key)?)
 }
 
 /// Parses a counter value from state stored as a string.
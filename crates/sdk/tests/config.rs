@@ -0,0 +1,45 @@
+use bulwark_sdk::{mock::MockHost, *};
+use serde::Deserialize;
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct PluginConfig {
+    limit: i64,
+    #[serde(default)]
+    ranges: Vec<String>,
+    #[serde(default = "default_window")]
+    window: u64,
+}
+
+fn default_window() -> u64 {
+    60
+}
+
+#[test]
+fn test_typed_config() -> Result<(), ConfigError> {
+    MockHost::new()
+        .config_var("limit", 10)
+        .config_var("ranges", vec!["192.0.2.0/24"])
+        .install();
+    assert_eq!(
+        config::<PluginConfig>()?,
+        PluginConfig {
+            limit: 10,
+            ranges: vec!["192.0.2.0/24".to_string()],
+            window: 60,
+        }
+    );
+
+    MockHost::new().config_var("limit", "ten").install();
+    let err = config::<PluginConfig>().unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("invalid plugin config: invalid type: string \"ten\""));
+
+    MockHost::new().install();
+    let err = config::<PluginConfig>().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid plugin config: missing field `limit`"
+    );
+    Ok(())
+}
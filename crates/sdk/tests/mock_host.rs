@@ -0,0 +1,284 @@
+// The plugin bindings generated by `bulwark_plugin` include unsafe traits without safety docs.
+#![allow(clippy::missing_safety_doc)]
+
+use bulwark_sdk::{mock::MockHost, *};
+use std::collections::HashMap;
+
+struct RateLimiter;
+
+#[bulwark_plugin]
+impl HttpHandlers for RateLimiter {
+    fn handle_request_decision(
+        req: Request,
+        _labels: HashMap<String, String>,
+    ) -> Result<HandlerOutput, Error> {
+        let mut output = HandlerOutput::default();
+        let ip = client_ip(&req).ok_or(error!("missing client ip"))?;
+        let limit = config_var("limit")
+            .and_then(|limit| limit.as_i64())
+            .ok_or(error!("limit not set"))?;
+        let rate = redis::incr_rate_limit(format!("rl:{}", ip), 1, 60)?;
+        if rate.attempts > limit {
+            output.decision = Decision::restricted(0.5);
+            output.tags = vec!["rate-limited".to_string()];
+        }
+        Ok(output)
+    }
+}
+
+fn request(forwarded_for: &str) -> Result<Request, Error> {
+    Ok(http::Request::builder()
+        .uri("/")
+        .header("x-forwarded-for", forwarded_for)
+        .body(Bytes::new())?)
+}
+
+#[test]
+fn test_mock_handlers() -> Result<(), Error> {
+    MockHost::new()
+        .config_var("limit", 2)
+        .proxy_hops(2)
+        .state_permission("rl:")
+        .time(1_700_000_000)
+        .install();
+
+    for _ in 0..2 {
+        let output = RateLimiter::handle_request_decision(
+            request("192.0.2.1, 198.51.100.1")?,
+            HashMap::new(),
+        )?;
+        assert_eq!(output.decision, Decision::default());
+    }
+    let output =
+        RateLimiter::handle_request_decision(request("192.0.2.1, 198.51.100.1")?, HashMap::new())?;
+    assert_eq!(output.decision, Decision::restricted(0.5));
+    assert_eq!(output.tags, vec!["rate-limited"]);
+
+    // The rate limit is keyed on the client IP, two hops from the end.
+    let output =
+        RateLimiter::handle_request_decision(request("192.0.2.2, 198.51.100.1")?, HashMap::new())?;
+    assert_eq!(output.decision, Decision::default());
+
+    // The window resets once it has passed.
+    mock::set_time(1_700_000_061);
+    let output =
+        RateLimiter::handle_request_decision(request("192.0.2.1, 198.51.100.1")?, HashMap::new())?;
+    assert_eq!(output.decision, Decision::default());
+
+    // Handlers left undefined get no-op defaults.
+    let enrichment = RateLimiter::handle_request_enrichment(request("192.0.2.1")?, HashMap::new())?;
+    assert!(enrichment.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_mock_config() {
+    MockHost::new()
+        .config_var("name", "example")
+        .config_var("ranges", vec!["192.0.2.0/24"])
+        .config_var("rules", serde_json::json!([{ "tags": ["sqli"] }]))
+        .install();
+
+    let mut keys = config_keys();
+    keys.sort();
+    assert_eq!(keys, vec!["name", "ranges", "rules"]);
+    assert_eq!(config_var("name"), Some(Value::from("example")));
+    assert_eq!(
+        config_var("ranges"),
+        Some(serde_json::json!(["192.0.2.0/24"]))
+    );
+    assert_eq!(
+        config_var("rules"),
+        Some(serde_json::json!([{ "tags": ["sqli"] }]))
+    );
+    assert_eq!(config_var("missing"), None);
+}
+
+#[test]
+fn test_mock_redis() -> Result<(), RemoteStateError> {
+    MockHost::new().time(1_700_000_000).install();
+
+    redis::set_string("greeting", "hello")?;
+    assert_eq!(redis::get_string("greeting")?, Some("hello".to_string()));
+    assert!(matches!(
+        redis::incr("greeting"),
+        Err(RemoteStateError::Remote { .. })
+    ));
+    assert_eq!(redis::incr_by("counter", 5)?, 5);
+    assert_eq!(redis::get_i64("counter")?, Some(5));
+
+    redis::expire("counter", 10)?;
+    mock::set_time(1_700_000_010);
+    assert_eq!(redis::get_i64("counter")?, None);
+
+    assert_eq!(redis::sadd("set", ["a", "b", "a"])?, 2);
+    assert_eq!(redis::srem("set", ["b", "c"])?, 1);
+    assert_eq!(redis::smembers("set")?, vec!["a"]);
+    assert_eq!(redis::del(["set", "greeting", "missing"])?, 2);
+
+    assert!(matches!(
+        redis::incr_rate_limit("key", -1, 60),
+        Err(RemoteStateError::InvalidArgument { message }) if message == "delta must be positive"
+    ));
+    assert!(redis::check_rate_limit("key")?.is_none());
+    let rate = redis::incr_rate_limit("key", 3, 60)?;
+    assert_eq!((rate.attempts, rate.expiration), (3, 1_700_000_070));
+    let rate = redis::check_rate_limit("key")?.expect("rate limit should be active");
+    assert_eq!((rate.attempts, rate.expiration), (3, 1_700_000_070));
+
+    assert!(redis::check_breaker("key")?.is_none());
+    redis::incr_breaker("key", 1, true, 60)?;
+    redis::incr_breaker("key", 2, false, 60)?;
+    let breaker = redis::incr_breaker("key", 1, false, 60)?;
+    assert_eq!(
+        (
+            breaker.generation,
+            breaker.successes,
+            breaker.failures,
+            breaker.consecutive_successes,
+            breaker.consecutive_failures,
+            breaker.expiration,
+        ),
+        (3, 1, 3, 0, 3, 1_700_000_070)
+    );
+    let checked = redis::check_breaker("key")?.expect("breaker should be active");
+    assert_eq!(checked.generation, 3);
+    Ok(())
+}
+
+#[test]
+fn test_mock_redis_permissions() {
+    MockHost::new().state_permission("allowed:").install();
+
+    assert!(redis::set_string("allowed:key", "value").is_ok());
+    assert!(matches!(
+        redis::get("denied:key"),
+        Err(RemoteStateError::Permission { key }) if key == "denied:key"
+    ));
+}
+
+#[test]
+fn test_mock_http() -> Result<(), HttpError> {
+    MockHost::new()
+        .http_permission("api.example.com")
+        .http_response(
+            "https://api.example.com/ip/192.0.2.1",
+            http::Response::builder()
+                .status(200)
+                .header("content-type", "application/json")
+                .body(Bytes::from_static(br#"{"score": 0.75}"#))?,
+        )
+        .http_response(
+            "https://api.example.com/ip/192.0.2.2",
+            http::Response::builder().status(404).body(Bytes::new())?,
+        )
+        .install();
+
+    let reputation: serde_json::Value =
+        http_client::get_json("https://api.example.com/ip/192.0.2.1")?;
+    assert_eq!(reputation, serde_json::json!({ "score": 0.75 }));
+    assert!(matches!(
+        http_client::get_json::<_, serde_json::Value>("https://api.example.com/ip/192.0.2.2"),
+        Err(HttpError::Status { status: 404 })
+    ));
+    assert!(matches!(
+        http_client::get_json::<_, serde_json::Value>("https://api.example.com/ip/192.0.2.3"),
+        Err(HttpError::Remote { .. })
+    ));
+
+    let err = http_client::post_json::<_, _, serde_json::Value>(
+        "https://other.example.com/report",
+        &serde_json::json!({ "ip": "192.0.2.1" }),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "access to http host 'other.example.com' denied"
+    );
+
+    let requests = mock::take_requests();
+    assert_eq!(
+        requests
+            .iter()
+            .map(|request| request.uri().to_string())
+            .collect::<Vec<_>>(),
+        vec![
+            "https://api.example.com/ip/192.0.2.1",
+            "https://api.example.com/ip/192.0.2.2",
+            "https://api.example.com/ip/192.0.2.3",
+        ]
+    );
+    assert_eq!(requests[0].headers()["accept"], "application/json");
+    assert!(mock::take_requests().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_mock_log() {
+    MockHost::new().install();
+
+    let attempts = 12;
+    info!("checked rate limit");
+    warn!(attempts = attempts, ip = "192.0.2.1"; "rate limit exceeded after {} attempts", attempts);
+    log!(log::Level::Error, "state unavailable");
+
+    assert_eq!(
+        mock::take_logs(),
+        vec![
+            mock::LogEvent {
+                level: log::Level::Info,
+                message: "checked rate limit".to_string(),
+                fields: vec![],
+            },
+            mock::LogEvent {
+                level: log::Level::Warn,
+                message: "rate limit exceeded after 12 attempts".to_string(),
+                fields: vec![
+                    ("attempts".to_string(), "12".to_string()),
+                    ("ip".to_string(), "192.0.2.1".to_string()),
+                ],
+            },
+            mock::LogEvent {
+                level: log::Level::Error,
+                message: "state unavailable".to_string(),
+                fields: vec![],
+            },
+        ]
+    );
+    assert!(mock::take_logs().is_empty());
+}
+
+#[test]
+fn test_mock_metrics() -> Result<(), MetricsError> {
+    MockHost::new().install();
+
+    metrics::increment_counter("requests_scored", 1, &[("model", "v2")])?;
+    metrics::record_histogram("feature_score", 0.75, &[("feature", "mouse_velocity")])?;
+    metrics::set_gauge("model_generation", 2.0, &[])?;
+
+    assert_eq!(
+        mock::take_metrics(),
+        vec![
+            mock::MetricEvent {
+                kind: mock::MetricKind::Counter,
+                name: "requests_scored".to_string(),
+                value: 1.0,
+                labels: vec![("model".to_string(), "v2".to_string())],
+            },
+            mock::MetricEvent {
+                kind: mock::MetricKind::Histogram,
+                name: "feature_score".to_string(),
+                value: 0.75,
+                labels: vec![("feature".to_string(), "mouse_velocity".to_string())],
+            },
+            mock::MetricEvent {
+                kind: mock::MetricKind::Gauge,
+                name: "model_generation".to_string(),
+                value: 2.0,
+                labels: vec![],
+            },
+        ]
+    );
+    assert!(mock::take_metrics().is_empty());
+    Ok(())
+}
@@ -103,6 +103,10 @@ impl EcsFormatter {
             } else {
                 message
             };
+            // Plugin logs are collected alongside the request rather than replacing its message.
+            if message == "plugin log" {
+                return EcsFormatter::parse_plugin_log_event(event, ecs_event);
+            }
             ecs_event.message = message.to_string();
 
             match message {
@@ -345,6 +349,42 @@ impl EcsFormatter {
         Ok(())
     }
 
+    /// Parses `"plugin log"` messages emitted by plugins through the log host API.
+    ///
+    /// Each event is appended to the request's plugin logs, with the plugin's fields decoded from JSON.
+    fn parse_plugin_log_event(event: &Event, ecs_event: &mut EcsEvent) -> fmt::Result {
+        let mut log_field_set = EcsBulwarkLogFieldSet {
+            timestamp: event.timestamp(),
+            plugin: String::new(),
+            level: event.level().as_str().to_ascii_lowercase(),
+            message: String::new(),
+            fields: Map::new(),
+        };
+
+        for field in event.fields().iter() {
+            let unquoted_value = quoted_string::to_content::<TraceQuoteSpec>(field.value())
+                .map_err(|_| fmt::Error)?;
+            match field.key() {
+                "plugin" => log_field_set.plugin = unquoted_value.to_string(),
+                "content" => log_field_set.message = unquoted_value.to_string(),
+                "fields" => {
+                    log_field_set.fields =
+                        serde_json::from_str(&unquoted_value).map_err(|_| fmt::Error)?;
+                }
+                _ => {}
+            }
+        }
+
+        let mut bulwark = ecs_event.bulwark.clone().unwrap_or_default();
+        bulwark
+            .logs
+            .get_or_insert_with(Vec::new)
+            .push(log_field_set);
+        ecs_event.bulwark = Some(bulwark);
+
+        Ok(())
+    }
+
     /// Parses unrecognized messages on a "best effort" basis. Not currently implemented.
     fn parse_unknown_event(event: &Event, _ecs_event: &mut EcsEvent) -> fmt::Result {
         for _field in event.fields().iter() {
@@ -648,6 +688,9 @@ pub(crate) struct EcsBulwarkFieldSet {
     /// The decision components that contributed to the outcome.
     #[serde(skip_serializing_if = "Option::is_none")]
     plugins: Option<serde_json::Map<String, serde_json::Value>>,
+    /// The events logged by plugins while processing the request, in the order they were emitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logs: Option<Vec<EcsBulwarkLogFieldSet>>,
 }
 
 impl std::fmt::Debug for EcsBulwarkFieldSet {
@@ -657,6 +700,7 @@ impl std::fmt::Debug for EcsBulwarkFieldSet {
             .field("restrict", &self.restrict)
             .field("unknown", &self.unknown)
             .field("plugins", &self.plugins)
+            .field("logs", &self.logs)
             .finish()
     }
 }
@@ -673,9 +717,26 @@ pub(crate) struct EcsBulwarkDecisionFieldSet {
     score: f64,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub(crate) struct EcsBulwarkLogFieldSet {
+    /// Date/time when the plugin emitted the event.
+    #[serde(rename(serialize = "@timestamp", deserialize = "@timestamp"))]
+    timestamp: DateTime<Utc>,
+    /// The reference of the plugin that emitted the event.
+    plugin: String,
+    /// The level the plugin logged the event at.
+    level: String,
+    /// The message logged by the plugin.
+    message: String,
+    /// The key/value pairs the plugin attached to the event.
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    fields: Map<String, Value>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tracing_forest::ForestLayer;
 
     #[test]
     fn tracing_quoted_strings() -> Result<(), Box<dyn std::error::Error>> {
@@ -688,4 +749,68 @@ mod tests {
 
         Ok(())
     }
+
+    /// Collects the output of the [`EcsFormatter`] for each completed tree.
+    #[derive(Clone, Default)]
+    struct CaptureProcessor(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl tracing_forest::Processor for CaptureProcessor {
+        fn process(&self, tree: Tree) -> tracing_forest::processor::Result {
+            let output = EcsFormatter
+                .fmt(&tree)
+                .expect("tree should format as an ecs event");
+            self.0.lock().unwrap().push(output);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn plugin_log_events() -> Result<(), Box<dyn std::error::Error>> {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let processor = CaptureProcessor::default();
+        let subscriber =
+            tracing_subscriber::Registry::default().with(ForestLayer::from(processor.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("handle request").in_scope(|| {
+                tracing::warn!(
+                    message = "plugin log",
+                    plugin = "example",
+                    content = "rate limit \"exceeded\"",
+                    fields = "{\"attempts\":\"12\",\"ip\":\"192.0.2.1\"}"
+                );
+                tracing::info!(
+                    message = "plugin log",
+                    plugin = "example",
+                    content = "done",
+                    fields = "{}"
+                );
+                tracing::info!(
+                    message = "process request",
+                    method = "GET",
+                    uri = "/",
+                    user_agent = "curl"
+                );
+            });
+        });
+
+        let output = processor.0.lock().unwrap();
+        assert_eq!(output.len(), 1);
+        let ecs_event: Value = serde_json::from_str(&output[0])?;
+        assert_ne!(ecs_event["message"], "plugin log");
+        let logs = ecs_event["bulwark"]["logs"]
+            .as_array()
+            .ok_or("missing plugin logs")?;
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0]["plugin"], "example");
+        assert_eq!(logs[0]["level"], "warn");
+        assert_eq!(logs[0]["message"], "rate limit \"exceeded\"");
+        assert_eq!(logs[0]["fields"]["attempts"], "12");
+        assert_eq!(logs[0]["fields"]["ip"], "192.0.2.1");
+        assert_eq!(logs[1]["level"], "info");
+        assert_eq!(logs[1]["message"], "done");
+        assert!(logs[1].get("fields").is_none());
+
+        Ok(())
+    }
 }
@@ -10,6 +10,10 @@ pub enum CliArgumentError {
 pub enum ServiceError {
     #[error("error starting envoy external processor service: {0}")]
     ExtProcessorService(#[from] tonic::transport::Error),
+    #[error("error starting envoy external authorization service: {0}")]
+    ExtAuthzService(tonic::transport::Error),
+    #[error("error starting http service: {0}")]
+    HttpService(std::io::Error),
     #[error("error starting admin service: {0}")]
     AdminService(#[from] std::io::Error),
 }
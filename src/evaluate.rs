@@ -0,0 +1,367 @@
+//! The evaluate module measures how well a config separates labeled malicious traffic from benign traffic.
+//!
+//! Every request is scored with its pignistic restrict value, see
+//! [`DecisionValues::score`](bulwark_ext_processor::DecisionValues::score), both for the combined decision and for
+//! each plugin individually. A request counts as flagged at a given threshold when its score reaches it, the same
+//! comparison used for the restricted outcome.
+
+use bulwark_ext_processor::{BulwarkProcessor, DecideRequest};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt, io::BufRead, path::PathBuf};
+
+/// Whether a labeled request is an attack that should be flagged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Label {
+    Malicious,
+    Benign,
+}
+
+/// A single line of an evaluation dataset.
+///
+/// Other fields are ignored, so capture records can be labeled by adding a `label` field to each line.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LabeledRequest {
+    pub label: Label,
+    pub request: DecideRequest,
+}
+
+/// The counts of flagged and unflagged requests at a single threshold.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConfusionMatrix {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub true_negatives: usize,
+    pub false_negatives: usize,
+}
+
+impl ConfusionMatrix {
+    /// The fraction of flagged requests that were malicious, if any were flagged.
+    pub fn precision(&self) -> Option<f64> {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_positives,
+        )
+    }
+
+    /// The fraction of malicious requests that were flagged, if there were any.
+    pub fn recall(&self) -> Option<f64> {
+        ratio(
+            self.true_positives,
+            self.true_positives + self.false_negatives,
+        )
+    }
+
+    /// The fraction of benign requests that were flagged, if there were any.
+    pub fn false_positive_rate(&self) -> Option<f64> {
+        ratio(
+            self.false_positives,
+            self.false_positives + self.true_negatives,
+        )
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> Option<f64> {
+    (denominator > 0).then(|| numerator as f64 / denominator as f64)
+}
+
+/// Formats an optional ratio, using a dash where it's undefined.
+struct RatioFormatter(Option<f64>);
+
+impl fmt::Display for RatioFormatter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(value) => write!(f, "{:.3}", value),
+            None => write!(f, "-"),
+        }
+    }
+}
+
+/// The scores given to labeled requests by either the combined decision or a single plugin.
+#[derive(Debug, Clone, Default)]
+pub struct ScoreSet {
+    samples: Vec<(f64, Label)>,
+}
+
+impl ScoreSet {
+    pub fn add(&mut self, score: f64, label: Label) {
+        self.samples.push((score, label));
+    }
+
+    /// Counts the requests flagged at a threshold.
+    pub fn confusion_matrix(&self, threshold: f64) -> ConfusionMatrix {
+        let mut matrix = ConfusionMatrix::default();
+        for (score, label) in &self.samples {
+            match (*score >= threshold, label) {
+                (true, Label::Malicious) => matrix.true_positives += 1,
+                (true, Label::Benign) => matrix.false_positives += 1,
+                (false, Label::Benign) => matrix.true_negatives += 1,
+                (false, Label::Malicious) => matrix.false_negatives += 1,
+            }
+        }
+        matrix
+    }
+
+    /// The area under the ROC curve, taken over every distinct score, if both labels are present.
+    pub fn auc(&self) -> Option<f64> {
+        let positives = self
+            .samples
+            .iter()
+            .filter(|(_, label)| *label == Label::Malicious)
+            .count();
+        let negatives = self.samples.len() - positives;
+        if positives == 0 || negatives == 0 {
+            return None;
+        }
+
+        let mut samples = self.samples.clone();
+        samples.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let (mut true_positives, mut false_positives) = (0, 0);
+        let (mut tpr, mut fpr) = (0.0, 0.0);
+        let mut area = 0.0;
+        for (index, (score, label)) in samples.iter().enumerate() {
+            match label {
+                Label::Malicious => true_positives += 1,
+                Label::Benign => false_positives += 1,
+            }
+            // Tied scores are flagged together, so they form a single point on the curve.
+            if samples.get(index + 1).map(|(next, _)| next) != Some(score) {
+                let next_tpr = true_positives as f64 / positives as f64;
+                let next_fpr = false_positives as f64 / negatives as f64;
+                area += (next_fpr - fpr) * (tpr + next_tpr) / 2.0;
+                (tpr, fpr) = (next_tpr, next_fpr);
+            }
+        }
+        Some(area)
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+/// The results of evaluating a config against a labeled dataset.
+#[derive(Debug)]
+pub struct EvaluationReport {
+    /// The configured restrict threshold, used for each confusion matrix.
+    pub threshold: f64,
+    /// The number of equal intervals the curves split the 0 to 1 threshold range into.
+    pub steps: usize,
+    pub combined: ScoreSet,
+    /// Scores for each plugin, keyed by plugin reference. Plugins only score requests on routes that run them.
+    pub plugins: BTreeMap<String, ScoreSet>,
+    /// Lines that could not be parsed or decided on, along with their location.
+    pub errors: Vec<(String, String)>,
+}
+
+impl EvaluationReport {
+    fn new(threshold: f64, steps: usize) -> Self {
+        Self {
+            threshold,
+            steps,
+            combined: ScoreSet::default(),
+            plugins: BTreeMap::new(),
+            errors: vec![],
+        }
+    }
+
+    /// Decides on every request in the datasets in order, collecting combined and per-plugin scores.
+    pub async fn evaluate(
+        processor: &BulwarkProcessor,
+        paths: &[PathBuf],
+        threshold: f64,
+        steps: usize,
+    ) -> Result<Self, std::io::Error> {
+        let mut report = Self::new(threshold, steps);
+        for path in paths {
+            let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+            for (index, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let location = format!("{}:{}", path.display(), index + 1);
+                let labeled_request = match serde_json::from_str::<LabeledRequest>(&line) {
+                    Ok(labeled_request) => labeled_request,
+                    Err(err) => {
+                        report
+                            .errors
+                            .push((location, format!("invalid labeled request: {}", err)));
+                        continue;
+                    }
+                };
+                match processor.decide(labeled_request.request).await {
+                    Ok(response) => {
+                        let label = labeled_request.label;
+                        report.combined.add(response.decision.score, label);
+                        for (reference, output) in response.plugins {
+                            report
+                                .plugins
+                                .entry(reference)
+                                .or_default()
+                                .add(output.decision.score, label);
+                        }
+                    }
+                    Err(err) => report.errors.push((location, err.to_string())),
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn write_score_set(&self, f: &mut fmt::Formatter<'_>, scores: &ScoreSet) -> fmt::Result {
+        let matrix = scores.confusion_matrix(self.threshold);
+        writeln!(
+            f,
+            "  tp {}  fp {}  tn {}  fn {}",
+            matrix.true_positives,
+            matrix.false_positives,
+            matrix.true_negatives,
+            matrix.false_negatives
+        )?;
+        writeln!(
+            f,
+            "  precision {}  recall {}  fpr {}  auc {}",
+            RatioFormatter(matrix.precision()),
+            RatioFormatter(matrix.recall()),
+            RatioFormatter(matrix.false_positive_rate()),
+            RatioFormatter(scores.auc())
+        )?;
+        writeln!(f, "  threshold  precision  recall  fpr")?;
+        for step in 0..=self.steps {
+            let threshold = step as f64 / self.steps as f64;
+            let matrix = scores.confusion_matrix(threshold);
+            writeln!(
+                f,
+                "  {:<9.3}  {:<9}  {:<6}  {}",
+                threshold,
+                RatioFormatter(matrix.precision()).to_string(),
+                RatioFormatter(matrix.recall()).to_string(),
+                RatioFormatter(matrix.false_positive_rate())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for EvaluationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (location, message) in &self.errors {
+            writeln!(f, "! {}: {}", location, message)?;
+        }
+        if !self.errors.is_empty() {
+            writeln!(f)?;
+        }
+
+        let matrix = self.combined.confusion_matrix(self.threshold);
+        writeln!(
+            f,
+            "evaluated {} requests: {} malicious, {} benign, {} failed",
+            self.combined.len(),
+            matrix.true_positives + matrix.false_negatives,
+            matrix.false_positives + matrix.true_negatives,
+            self.errors.len()
+        )?;
+
+        writeln!(f)?;
+        writeln!(f, "combined (threshold {:.3})", self.threshold)?;
+        self.write_score_set(f, &self.combined)?;
+        for (reference, scores) in &self.plugins {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "plugin {} ({} requests, threshold {:.3})",
+                reference,
+                scores.len(),
+                self.threshold
+            )?;
+            self.write_score_set(f, scores)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score_set(samples: &[(f64, Label)]) -> ScoreSet {
+        let mut scores = ScoreSet::default();
+        for (score, label) in samples {
+            scores.add(*score, *label);
+        }
+        scores
+    }
+
+    #[test]
+    fn test_confusion_matrix() {
+        let scores = score_set(&[
+            (0.9, Label::Malicious),
+            (0.8, Label::Benign),
+            (0.7, Label::Malicious),
+            (0.5, Label::Benign),
+        ]);
+        let matrix = scores.confusion_matrix(0.8);
+        assert_eq!(
+            matrix,
+            ConfusionMatrix {
+                true_positives: 1,
+                false_positives: 1,
+                true_negatives: 1,
+                false_negatives: 1,
+            }
+        );
+        assert_eq!(matrix.precision(), Some(0.5));
+        assert_eq!(matrix.recall(), Some(0.5));
+        assert_eq!(matrix.false_positive_rate(), Some(0.5));
+
+        let matrix = scores.confusion_matrix(1.0);
+        assert_eq!(matrix.precision(), None);
+        assert_eq!(matrix.recall(), Some(0.0));
+    }
+
+    #[test]
+    fn test_auc() {
+        // Perfectly separated
+        let scores = score_set(&[(0.9, Label::Malicious), (0.2, Label::Benign)]);
+        assert_eq!(scores.auc(), Some(1.0));
+
+        // Inverted
+        let scores = score_set(&[(0.2, Label::Malicious), (0.9, Label::Benign)]);
+        assert_eq!(scores.auc(), Some(0.0));
+
+        // Ties can't be separated at any threshold
+        let scores = score_set(&[(0.5, Label::Malicious), (0.5, Label::Benign)]);
+        assert_eq!(scores.auc(), Some(0.5));
+
+        let scores = score_set(&[
+            (0.9, Label::Malicious),
+            (0.8, Label::Benign),
+            (0.7, Label::Malicious),
+            (0.5, Label::Benign),
+        ]);
+        assert_eq!(scores.auc(), Some(0.75));
+
+        let scores = score_set(&[(0.9, Label::Malicious)]);
+        assert_eq!(scores.auc(), None);
+    }
+
+    #[test]
+    fn test_labeled_capture_record() -> Result<(), Box<dyn std::error::Error>> {
+        let labeled_request: LabeledRequest = serde_json::from_str(
+            r#"{
+                "timestamp": "2024-01-01T00:00:00Z",
+                "label": "malicious",
+                "request": {"method": "GET", "uri": "https://example.com/"},
+                "verdict": {}
+            }"#,
+        )?;
+        assert_eq!(labeled_request.label, Label::Malicious);
+        assert_eq!(labeled_request.request.uri, "https://example.com/");
+        Ok(())
+    }
+}
@@ -1,17 +1,24 @@
 pub mod admin;
 pub mod ecs;
 pub mod errors;
+pub mod evaluate;
+pub mod replay;
 
 use {
     crate::admin::{AdminState, HealthState, MetricsState},
     axum::{
-        extract::Path, extract::State, http::StatusCode, response::Json, routing::get, Router,
-        ServiceExt,
+        extract::ConnectInfo, extract::Path, extract::State, http::HeaderMap, http::StatusCode,
+        response::Json, routing::get, routing::post, Router, ServiceExt,
+    },
+    bulwark_ext_processor::{
+        BulwarkProcessor, DecideError, DecideRequest, DecideResponse, HandlerError,
     },
-    bulwark_ext_processor::BulwarkProcessor,
     clap::{Parser, Subcommand},
     color_eyre::eyre::Result,
-    envoy_control_plane::envoy::service::ext_proc::v3::external_processor_server::ExternalProcessorServer,
+    envoy_control_plane::envoy::service::{
+        auth::v3::authorization_server::AuthorizationServer,
+        ext_proc::v3::external_processor_server::ExternalProcessorServer,
+    },
     errors::*,
     metrics_exporter_prometheus::Matcher,
     metrics_exporter_statsd::StatsdBuilder,
@@ -39,7 +46,7 @@ use {
 struct Cli {
     /// Log levels: error, warn, info, debug, trace
     ///
-    /// Default is "info".
+    /// Default is "info", or "warn" when replaying or evaluating traffic.
     #[arg(short = 'l', long)]
     log_level: Option<String>,
 
@@ -61,6 +68,74 @@ enum Command {
         /// Sets a custom config file
         #[arg(short, long, value_name = "FILE")]
         config: PathBuf,
+        /// Applies a named profile from the config file
+        #[arg(short, long, value_name = "PROFILE")]
+        profile: Option<String>,
+    },
+    /// Launch as an Envoy external authorization service
+    ///
+    /// Only request phases are run, plugins that make response decisions are not supported.
+    ExtAuthz {
+        /// Sets a custom config file
+        #[arg(short, long, value_name = "FILE")]
+        config: PathBuf,
+        /// Applies a named profile from the config file
+        #[arg(short, long, value_name = "PROFILE")]
+        profile: Option<String>,
+    },
+    /// Launch as an HTTP auth request service for nginx, Traefik or Caddy
+    ///
+    /// Only request phases are run, plugins that make response decisions are not supported.
+    AuthRequest {
+        /// Sets a custom config file
+        #[arg(short, long, value_name = "FILE")]
+        config: PathBuf,
+        /// Applies a named profile from the config file
+        #[arg(short, long, value_name = "PROFILE")]
+        profile: Option<String>,
+    },
+    /// Launch as an HTTP service that decides on JSON-encoded requests out-of-band
+    ///
+    /// Requests are posted to `/decide`.
+    Decide {
+        /// Sets a custom config file
+        #[arg(short, long, value_name = "FILE")]
+        config: PathBuf,
+        /// Applies a named profile from the config file
+        #[arg(short, long, value_name = "PROFILE")]
+        profile: Option<String>,
+    },
+    /// Replay captured traffic through a config, printing how each verdict changes
+    ///
+    /// Capture files are written by the services when `capture.path` is set.
+    Replay {
+        /// Sets the config file to replay against
+        #[arg(short, long, value_name = "FILE")]
+        config: PathBuf,
+        /// Applies a named profile from the config file
+        #[arg(short, long, value_name = "PROFILE")]
+        profile: Option<String>,
+        /// The capture files to replay, in order
+        #[arg(value_name = "CAPTURE", required = true)]
+        captures: Vec<PathBuf>,
+    },
+    /// Evaluate a config against requests labeled malicious or benign
+    ///
+    /// Reports a confusion matrix at the restrict threshold and precision, recall and false positive rates across
+    /// thresholds, for the combined decision and for each plugin.
+    Evaluate {
+        /// Sets the config file to evaluate
+        #[arg(short, long, value_name = "FILE")]
+        config: PathBuf,
+        /// Applies a named profile from the config file
+        #[arg(short, long, value_name = "PROFILE")]
+        profile: Option<String>,
+        /// Sets the number of intervals between thresholds in the reported curves
+        #[arg(long, value_name = "STEPS", default_value_t = 20)]
+        steps: usize,
+        /// The NDJSON datasets of labeled requests
+        #[arg(value_name = "DATASET", required = true)]
+        datasets: Vec<PathBuf>,
     },
     // TODO: Implement ReverseProxy subcommand
     // TODO: Implement Check subcommand
@@ -82,6 +157,55 @@ enum Command {
         #[arg(last = true)]
         compiler_args: Vec<String>,
     },
+    /// Inspect Bulwark configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+/// The `config` subcommands supported by the Bulwark CLI.
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print a JSON Schema for the config file format
+    Schema,
+    /// Print the effective config with includes merged and presets resolved
+    Show {
+        /// Sets the config file to load
+        #[arg(short, long, value_name = "FILE")]
+        config: PathBuf,
+        /// Applies a named profile from the config file
+        #[arg(short, long, value_name = "PROFILE")]
+        profile: Option<String>,
+    },
+    /// Print the semantic differences between two config files
+    Diff {
+        /// The config file to compare from
+        #[arg(value_name = "FILE")]
+        before: PathBuf,
+        /// The config file to compare to
+        #[arg(value_name = "FILE")]
+        after: PathBuf,
+        /// Applies a named profile from the config file being compared from
+        #[arg(long, value_name = "PROFILE")]
+        before_profile: Option<String>,
+        /// Applies a named profile from the config file being compared to
+        #[arg(long, value_name = "PROFILE")]
+        after_profile: Option<String>,
+    },
+}
+
+/// The kinds of service that Bulwark can be launched as.
+#[derive(Clone, Copy)]
+enum ServiceMode {
+    /// A gRPC service for Envoy's `ext_proc` filter, see [`ExternalProcessorServer`].
+    ExtProcessor,
+    /// A gRPC service for Envoy's `ext_authz` filter, see [`AuthorizationServer`].
+    ExtAuthz,
+    /// An HTTP service for auth request subrequests, see [`BulwarkProcessor::handle_auth_request`].
+    AuthRequest,
+    /// An HTTP service for out-of-band decisions, see [`BulwarkProcessor::decide`].
+    Decide,
 }
 
 /// An [`EnvFilter`] pattern to limit matched log events to error events.
@@ -106,7 +230,15 @@ fn init_tracing(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
 
     LogTracer::init().expect("log tracer init failed");
 
-    let log_level: &str = cli.log_level.as_ref().map_or("info", |ll| ll.as_str());
+    // Per-request logs would bury the replay and evaluation reports, which are also written to stdout.
+    let default_log_level = match cli.command {
+        Some(Command::Replay { .. } | Command::Evaluate { .. }) => "warn",
+        _ => "info",
+    };
+    let log_level: &str = cli
+        .log_level
+        .as_ref()
+        .map_or(default_log_level, |ll| ll.as_str());
     let log_format: &str = cli.log_format.as_ref().map_or("ecs", |lf| lf.as_str());
     let mut ecs_layer = None;
     let mut forest_layer = None;
@@ -147,148 +279,255 @@ fn init_tracing(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // TODO: tokio runtime builder to control runtime parameters
+/// Answers an auth request subrequest with the original request's verdict.
+async fn auth_request_handler(
+    State(processor): State<BulwarkProcessor>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    processor
+        .handle_auth_request(&headers, Some(remote_addr.ip()))
+        .await
+        .map(axum::body::Body::from)
+}
 
-    let cli = Cli::parse();
-    init_tracing(&cli)?;
+/// Decides on a JSON-encoded request, see [`DecideRequest`].
+async fn decide_handler(
+    State(processor): State<BulwarkProcessor>,
+    Json(decide_request): Json<DecideRequest>,
+) -> Result<Json<DecideResponse>, (StatusCode, String)> {
+    processor
+        .decide(decide_request)
+        .await
+        .map(Json)
+        .map_err(|err| {
+            let status = match err {
+                DecideError::Handler(HandlerError::RouteMatch(_)) => StatusCode::NOT_FOUND,
+                DecideError::Handler(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::BAD_REQUEST,
+            };
+            (status, err.to_string())
+        })
+}
 
-    // You can check for the existence of subcommands, and if found use their
-    // matches just as you would the top level cmd
-    match &cli.command.ok_or(CliArgumentError::MissingSubcommand)? {
-        Command::ExtProcessor { config } => {
-            let mut service_tasks: JoinSet<std::result::Result<(), ServiceError>> = JoinSet::new();
-
-            let config_root = bulwark_config::toml::load_config(config)?;
-            let port = config_root.service.port;
-            let admin_port = config_root.service.admin_port;
-            let admin_enabled = config_root.service.admin_enabled;
-            let prometheus_handle;
-
-            if let Some(statsd_host) = &config_root.metrics.statsd_host {
-                prometheus_handle = None;
-                let prefix = if config_root.metrics.statsd_prefix.is_empty() {
-                    None
-                } else {
-                    Some(config_root.metrics.statsd_prefix.as_str())
-                };
-                let recorder = StatsdBuilder::from(
-                    statsd_host,
-                    config_root.metrics.statsd_port.unwrap_or(8125),
-                )
+/// Launches Bulwark as the given kind of service, along with the admin service.
+async fn serve(
+    config: &PathBuf,
+    profile: Option<&str>,
+    service_mode: ServiceMode,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut service_tasks: JoinSet<std::result::Result<(), ServiceError>> = JoinSet::new();
+
+    let config_root = bulwark_config::load_config_with_profile(config, profile)?;
+    let port = config_root.service.port;
+    let admin_port = config_root.service.admin_port;
+    let admin_enabled = config_root.service.admin_enabled;
+    let prometheus_handle;
+
+    if let Some(statsd_host) = &config_root.metrics.statsd_host {
+        prometheus_handle = None;
+        let prefix = if config_root.metrics.statsd_prefix.is_empty() {
+            None
+        } else {
+            Some(config_root.metrics.statsd_prefix.as_str())
+        };
+        let recorder =
+            StatsdBuilder::from(statsd_host, config_root.metrics.statsd_port.unwrap_or(8125))
                 .with_queue_size(config_root.metrics.statsd_queue_size)
                 .with_buffer_size(config_root.metrics.statsd_buffer_size)
                 .histogram_is_distribution()
                 .build(prefix)
                 .map_err(MetricsError::from)?;
 
-                metrics::set_boxed_recorder(Box::new(recorder)).map_err(MetricsError::from)?;
-            } else {
-                let thresholds = config_root.thresholds;
-                prometheus_handle = Some(
-                    crate::admin::PrometheusBuilder::new()
-                        // Setting buckets forces histograms to be rendered as native histograms rather than summaries
-                        .set_buckets_for_metric(
-                            Matcher::Suffix("decision_score".to_string()),
-                            &[
-                                thresholds.trust,
-                                thresholds.suspicious,
-                                thresholds.restrict,
-                                1.0,
-                            ],
-                        )
-                        .map_err(MetricsError::from)?
-                        .set_buckets_for_metric(
-                            Matcher::Suffix("decision_conflict".to_string()),
-                            &[0.01, 0.1, 0.25, 0.5, 0.75, 1.0, 5.0],
-                        )
-                        .map_err(MetricsError::from)?
-                        .set_buckets_for_metric(
-                            Matcher::Full("combined_conflict".to_string()),
-                            &[0.01, 0.1, 0.25, 0.5, 0.75, 1.0, 5.0],
-                        )
-                        .map_err(MetricsError::from)?
-                        .install_recorder()
-                        .map_err(MetricsError::from)?,
-                );
+        metrics::set_boxed_recorder(Box::new(recorder)).map_err(MetricsError::from)?;
+    } else {
+        let thresholds = config_root.thresholds;
+        prometheus_handle = Some(
+            crate::admin::PrometheusBuilder::new()
+                // Setting buckets forces histograms to be rendered as native histograms rather than summaries
+                .set_buckets_for_metric(
+                    Matcher::Suffix("decision_score".to_string()),
+                    &[
+                        thresholds.trust,
+                        thresholds.suspicious,
+                        thresholds.restrict,
+                        1.0,
+                    ],
+                )
+                .map_err(MetricsError::from)?
+                .set_buckets_for_metric(
+                    Matcher::Suffix("decision_conflict".to_string()),
+                    &[0.01, 0.1, 0.25, 0.5, 0.75, 1.0, 5.0],
+                )
+                .map_err(MetricsError::from)?
+                .set_buckets_for_metric(
+                    Matcher::Full("combined_conflict".to_string()),
+                    &[0.01, 0.1, 0.25, 0.5, 0.75, 1.0, 5.0],
+                )
+                .map_err(MetricsError::from)?
+                .install_recorder()
+                .map_err(MetricsError::from)?,
+        );
 
-                // TODO: Enable process metrics collection. (libproc.h issue, maybe behind cfg feature)
-                // let process = metrics_process::Collector::default();
-                // process.describe();
-            }
+        // TODO: Enable process metrics collection. (libproc.h issue, maybe behind cfg feature)
+        // let process = metrics_process::Collector::default();
+        // process.describe();
+    }
+
+    let admin_state = Arc::new(Mutex::new(AdminState {
+        health: HealthState {
+            live: true,
+            started: false,
+            ready: false,
+        },
+        metrics: MetricsState::new(
+            prometheus_handle,
+            // TODO: Enable process metrics collection. (libproc.h issue, maybe behind cfg feature)
+            // collect: move || process.collect(),
+        ),
+    }));
+
+    // TODO: need a reference to the bulwark processor to pass to the admin service but that doesn't exist yet
+
+    if admin_enabled {
+        let admin_state = admin_state.clone();
 
-            let admin_state = Arc::new(Mutex::new(AdminState {
-                health: HealthState {
-                    live: true,
-                    started: false,
-                    ready: false,
-                },
-                metrics: MetricsState::new(
-                    prometheus_handle,
-                    // TODO: Enable process metrics collection. (libproc.h issue, maybe behind cfg feature)
-                    // collect: move || process.collect(),
+        service_tasks.spawn(async move {
+            // And run our service using `hyper`.
+            let addr = SocketAddr::from((IpAddr::V4(Ipv4Addr::UNSPECIFIED), admin_port));
+            let app = ServiceExt::<axum::extract::Request>::into_make_service(
+                NormalizePathLayer::trim_trailing_slash().layer(
+                    Router::new()
+                        .route("/health", get(admin::default_probe_handler)) // :probe is optional and defaults to liveness probe
+                        .route("/health/:probe", get(admin::probe_handler))
+                        .route("/metrics", get(admin::metrics_handler))
+                        .with_state(admin_state),
                 ),
-            }));
-
-            // TODO: need a reference to the bulwark processor to pass to the admin service but that doesn't exist yet
-
-            if admin_enabled {
-                let admin_state = admin_state.clone();
-
-                service_tasks.spawn(async move {
-                    // And run our service using `hyper`.
-                    let addr = SocketAddr::from((IpAddr::V4(Ipv4Addr::UNSPECIFIED), admin_port));
-                    let app = ServiceExt::<axum::extract::Request>::into_make_service(
-                        NormalizePathLayer::trim_trailing_slash().layer(
-                            Router::new()
-                                .route("/health", get(admin::default_probe_handler)) // :probe is optional and defaults to liveness probe
-                                .route("/health/:probe", get(admin::probe_handler))
-                                .route("/metrics", get(admin::metrics_handler))
-                                .with_state(admin_state),
-                        ),
-                    );
-
-                    let listener = tokio::net::TcpListener::bind(&addr).await?;
-                    axum::serve(listener, app)
-                        .await
-                        .map_err(ServiceError::AdminService)
-                });
-            }
+            );
 
-            let bulwark_processor = BulwarkProcessor::new(config_root).await?;
-            let ext_processor = ExternalProcessorServer::new(bulwark_processor);
+            let listener = tokio::net::TcpListener::bind(&addr).await?;
+            axum::serve(listener, app)
+                .await
+                .map_err(ServiceError::AdminService)
+        });
+    }
 
-            {
-                let admin_state = admin_state.clone();
+    let bulwark_processor = BulwarkProcessor::new(config_root).await?;
+    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port); // TODO: make socket addr configurable?
+
+    {
+        let admin_state = admin_state.clone();
 
-                service_tasks.spawn(async move {
-                    {
-                        let mut admin_state = admin_state.lock().expect("poisoned mutex");
-                        admin_state.health.started = true;
-                        admin_state.health.ready = true;
+        service_tasks.spawn(async move {
+            {
+                let mut admin_state = admin_state.lock().expect("poisoned mutex");
+                admin_state.health.started = true;
+                admin_state.health.ready = true;
+            }
+            match service_mode {
+                ServiceMode::ExtProcessor => Server::builder()
+                    .add_service(ExternalProcessorServer::new(bulwark_processor))
+                    .serve(addr)
+                    .await
+                    .map_err(ServiceError::ExtProcessorService),
+                ServiceMode::ExtAuthz => Server::builder()
+                    .add_service(AuthorizationServer::new(bulwark_processor))
+                    .serve(addr)
+                    .await
+                    .map_err(ServiceError::ExtAuthzService),
+                ServiceMode::AuthRequest | ServiceMode::Decide => {
+                    let app = match service_mode {
+                        // Proxies are configured with an arbitrary auth endpoint, so every path is handled.
+                        ServiceMode::AuthRequest => Router::new().fallback(auth_request_handler),
+                        _ => Router::new().route("/decide", post(decide_handler)),
                     }
-                    Server::builder()
-                        .add_service(ext_processor)
-                        .serve(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port)) // TODO: make socket addr configurable?
+                    .with_state(bulwark_processor);
+                    let listener = tokio::net::TcpListener::bind(&addr)
                         .await
-                        .map_err(ServiceError::ExtProcessorService)
-                });
-            }
-
-            while let Some(r) = service_tasks.join_next().await {
-                match r {
-                    Ok(Ok(_)) => {}
-                    Ok(Err(e)) => error!(
-                        message = "service could not start",
-                        error_message = ?e,
-                    ),
-                    Err(e) => error!(
-                        message = "join error on service initialization",
-                        error_message = ?e,
-                    ),
+                        .map_err(ServiceError::HttpService)?;
+                    axum::serve(
+                        listener,
+                        app.into_make_service_with_connect_info::<SocketAddr>(),
+                    )
+                    .await
+                    .map_err(ServiceError::HttpService)
                 }
             }
+        });
+    }
+
+    while let Some(r) = service_tasks.join_next().await {
+        match r {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!(
+                message = "service could not start",
+                error_message = ?e,
+            ),
+            Err(e) => error!(
+                message = "join error on service initialization",
+                error_message = ?e,
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // TODO: tokio runtime builder to control runtime parameters
+
+    let cli = Cli::parse();
+    init_tracing(&cli)?;
+
+    // You can check for the existence of subcommands, and if found use their
+    // matches just as you would the top level cmd
+    match &cli.command.ok_or(CliArgumentError::MissingSubcommand)? {
+        Command::ExtProcessor { config, profile } => {
+            serve(config, profile.as_deref(), ServiceMode::ExtProcessor).await?;
+        }
+        Command::ExtAuthz { config, profile } => {
+            serve(config, profile.as_deref(), ServiceMode::ExtAuthz).await?;
+        }
+        Command::AuthRequest { config, profile } => {
+            serve(config, profile.as_deref(), ServiceMode::AuthRequest).await?;
+        }
+        Command::Decide { config, profile } => {
+            serve(config, profile.as_deref(), ServiceMode::Decide).await?;
+        }
+        Command::Replay {
+            config,
+            profile,
+            captures,
+        } => {
+            let mut config_root =
+                bulwark_config::load_config_with_profile(config, profile.as_deref())?;
+            // Replayed requests must not be captured again.
+            config_root.capture.path = None;
+            let bulwark_processor = BulwarkProcessor::new(config_root).await?;
+            let report = replay::ReplayReport::replay(&bulwark_processor, captures).await?;
+            print!("{}", report);
+        }
+        Command::Evaluate {
+            config,
+            profile,
+            steps,
+            datasets,
+        } => {
+            let mut config_root =
+                bulwark_config::load_config_with_profile(config, profile.as_deref())?;
+            // Evaluated requests must not be captured.
+            config_root.capture.path = None;
+            let threshold = config_root.thresholds.restrict;
+            let bulwark_processor = BulwarkProcessor::new(config_root).await?;
+            let report = evaluate::EvaluationReport::evaluate(
+                &bulwark_processor,
+                datasets,
+                threshold,
+                (*steps).max(1),
+            )
+            .await?;
+            print!("{}", report);
         }
         Command::Build {
             path,
@@ -307,6 +546,36 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             bulwark_build::interactive_build_plugin(&path, output, compiler_args)?;
         }
+        Command::Config { command } => match command {
+            ConfigCommand::Schema => {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&bulwark_config::file::config_schema())?
+                );
+            }
+            ConfigCommand::Show { config, profile } => {
+                let config_root =
+                    bulwark_config::load_config_with_profile(config, profile.as_deref())?;
+                let effective_config = bulwark_config::EffectiveConfig::new(&config_root)?;
+                println!("{}", serde_json::to_string_pretty(&effective_config)?);
+            }
+            ConfigCommand::Diff {
+                before,
+                after,
+                before_profile,
+                after_profile,
+            } => {
+                let before = bulwark_config::EffectiveConfig::new(
+                    &bulwark_config::load_config_with_profile(before, before_profile.as_deref())?,
+                )?;
+                let after = bulwark_config::EffectiveConfig::new(
+                    &bulwark_config::load_config_with_profile(after, after_profile.as_deref())?,
+                )?;
+                for change in before.diff(&after) {
+                    println!("{}", change);
+                }
+            }
+        },
     }
 
     Ok(())
@@ -0,0 +1,316 @@
+//! The replay module feeds captured traffic through a config, comparing the verdicts it reaches with the captured ones.
+
+use bulwark_ext_processor::{
+    BulwarkProcessor, CaptureRecord, CapturedVerdict, DecideError, DecideResponse,
+};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::BufRead,
+    path::{Path, PathBuf},
+};
+
+/// The outcomes in the order they're reported, from least to most restrictive.
+const OUTCOMES: [&str; 4] = ["trusted", "accepted", "suspected", "restricted"];
+
+/// The parts of a verdict that are compared on replay.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerdictSummary {
+    pub outcome: String,
+    pub score: f64,
+    pub tags: Vec<String>,
+}
+
+impl VerdictSummary {
+    /// Whether two verdicts differ in outcome, tags, or score at the precision it's reported with.
+    fn differs(&self, other: &VerdictSummary) -> bool {
+        self.outcome != other.outcome
+            || self.tags != other.tags
+            || format!("{:.3}", self.score) != format!("{:.3}", other.score)
+    }
+}
+
+impl From<&CapturedVerdict> for VerdictSummary {
+    fn from(verdict: &CapturedVerdict) -> Self {
+        let mut tags = verdict.tags.clone();
+        tags.sort();
+        Self {
+            outcome: verdict.outcome.clone(),
+            score: verdict.decision.score,
+            tags,
+        }
+    }
+}
+
+impl From<&DecideResponse> for VerdictSummary {
+    fn from(response: &DecideResponse) -> Self {
+        Self {
+            outcome: response.outcome.clone(),
+            score: response.decision.score,
+            tags: response.tags.clone(),
+        }
+    }
+}
+
+impl fmt::Display for VerdictSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:.3})", self.outcome, self.score)
+    }
+}
+
+/// A single captured request whose verdict changed on replay, or that could not be replayed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayChange {
+    /// The replayed verdict differs from the captured one.
+    Verdict {
+        location: String,
+        method: String,
+        uri: String,
+        before: VerdictSummary,
+        after: VerdictSummary,
+    },
+    /// The capture line could not be parsed or the request could not be decided on.
+    Error { location: String, message: String },
+}
+
+impl fmt::Display for ReplayChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayChange::Verdict {
+                location,
+                method,
+                uri,
+                before,
+                after,
+            } => {
+                write!(
+                    f,
+                    "~ {} {} {}: {} -> {}",
+                    location, method, uri, before, after
+                )?;
+                let added = after.tags.iter().filter(|tag| !before.tags.contains(tag));
+                let removed = before.tags.iter().filter(|tag| !after.tags.contains(tag));
+                let tag_changes: Vec<String> = added
+                    .map(|tag| format!("+{}", tag))
+                    .chain(removed.map(|tag| format!("-{}", tag)))
+                    .collect();
+                if !tag_changes.is_empty() {
+                    write!(f, " [{}]", tag_changes.join(" "))?;
+                }
+                Ok(())
+            }
+            ReplayChange::Error { location, message } => write!(f, "! {}: {}", location, message),
+        }
+    }
+}
+
+/// The per-request changes and aggregate outcome shifts from replaying one or more capture files.
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    pub changes: Vec<ReplayChange>,
+    /// The number of requests for each pair of captured and replayed outcomes.
+    pub transitions: BTreeMap<(String, String), usize>,
+}
+
+impl ReplayReport {
+    /// Replays every record in the capture files in order.
+    ///
+    /// Records are decided on one at a time, so plugins that keep state see requests in the order they were captured.
+    pub async fn replay(
+        processor: &BulwarkProcessor,
+        paths: &[PathBuf],
+    ) -> Result<Self, std::io::Error> {
+        let mut report = Self::default();
+        for path in paths {
+            let reader = std::io::BufReader::new(std::fs::File::open(path)?);
+            for (index, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let location = Self::location(path, index + 1);
+                match serde_json::from_str::<CaptureRecord>(&line) {
+                    Ok(record) => {
+                        let result = processor.decide(record.request.clone()).await;
+                        report.add(location, &record, result);
+                    }
+                    Err(err) => report.changes.push(ReplayChange::Error {
+                        location,
+                        message: format!("invalid capture record: {}", err),
+                    }),
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn location(path: &Path, line: usize) -> String {
+        format!("{}:{}", path.display(), line)
+    }
+
+    /// Compares the result of replaying a record with its captured verdict.
+    fn add(
+        &mut self,
+        location: String,
+        record: &CaptureRecord,
+        result: Result<DecideResponse, DecideError>,
+    ) {
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                self.changes.push(ReplayChange::Error {
+                    location,
+                    message: err.to_string(),
+                });
+                return;
+            }
+        };
+        let before = VerdictSummary::from(&record.verdict);
+        let after = VerdictSummary::from(&response);
+        *self
+            .transitions
+            .entry((before.outcome.clone(), after.outcome.clone()))
+            .or_default() += 1;
+        if before.differs(&after) {
+            self.changes.push(ReplayChange::Verdict {
+                location,
+                method: record.request.method.clone(),
+                uri: record.request.uri.clone(),
+                before,
+                after,
+            });
+        }
+    }
+
+    /// The number of requests replayed without error.
+    pub fn replayed(&self) -> usize {
+        self.transitions.values().sum()
+    }
+
+    /// The number of requests with each outcome before and after replay.
+    pub fn outcome_counts(&self) -> BTreeMap<&str, (usize, usize)> {
+        let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for ((before, after), count) in &self.transitions {
+            counts.entry(before).or_default().0 += count;
+            counts.entry(after).or_default().1 += count;
+        }
+        counts
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        if !self.changes.is_empty() {
+            writeln!(f)?;
+        }
+
+        let errors = self
+            .changes
+            .iter()
+            .filter(|change| matches!(change, ReplayChange::Error { .. }))
+            .count();
+        writeln!(
+            f,
+            "replayed {} requests: {} changed, {} failed",
+            self.replayed(),
+            self.changes.len() - errors,
+            errors
+        )?;
+
+        let counts = self.outcome_counts();
+        for outcome in OUTCOMES {
+            let (before, after) = counts.get(outcome).copied().unwrap_or_default();
+            write!(f, "  {}: {} -> {}", outcome, before, after)?;
+            if before != after {
+                write!(f, " ({:+})", after as i64 - before as i64)?;
+            }
+            writeln!(f)?;
+        }
+        for ((before, after), count) in &self.transitions {
+            if before != after {
+                writeln!(f, "  {} -> {}: {}", before, after, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(outcome: &str, score: f64, tags: &[&str]) -> CaptureRecord {
+        serde_json::from_value(serde_json::json!({
+            "timestamp": "2024-01-01T00:00:00Z",
+            "request": {"method": "GET", "uri": "https://example.com/"},
+            "truncated": false,
+            "labels": {},
+            "verdict": {
+                "decision": {"accept": 0.0, "restrict": score, "unknown": 1.0 - score, "score": score},
+                "outcome": outcome,
+                "action": "allow",
+                "tags": tags,
+            },
+        }))
+        .unwrap()
+    }
+
+    fn response(outcome: &str, score: f64, tags: &[&str]) -> DecideResponse {
+        DecideResponse {
+            decision: bulwark_ext_processor::DecisionValues {
+                accept: 0.0,
+                restrict: score,
+                unknown: 1.0 - score,
+                score,
+            },
+            outcome: outcome.to_string(),
+            action: bulwark_config::Action::Allow,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            labels: BTreeMap::new(),
+            plugins: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_replay_report() {
+        let mut report = ReplayReport::default();
+        report.add(
+            "capture.ndjson:1".to_string(),
+            &record("accepted", 0.5, &[]),
+            Ok(response("accepted", 0.5, &[])),
+        );
+        report.add(
+            "capture.ndjson:2".to_string(),
+            &record("accepted", 0.5, &["scanner"]),
+            Ok(response("restricted", 0.91, &["bot"])),
+        );
+        report.add(
+            "capture.ndjson:3".to_string(),
+            &record("accepted", 0.5, &[]),
+            Err(DecideError::Request(
+                bulwark_ext_processor::RequestError::MissingMethod,
+            )),
+        );
+
+        assert_eq!(report.replayed(), 2);
+        assert_eq!(
+            report.outcome_counts(),
+            BTreeMap::from([("accepted", (2, 1)), ("restricted", (0, 1))])
+        );
+        assert_eq!(
+            report.to_string(),
+            "~ capture.ndjson:2 GET https://example.com/: accepted (0.500) -> restricted (0.910) [+bot -scanner]\n\
+             ! capture.ndjson:3: missing http method pseudo-header\n\
+             \n\
+             replayed 2 requests: 1 changed, 1 failed\n  \
+             trusted: 0 -> 0\n  \
+             accepted: 2 -> 1 (-1)\n  \
+             suspected: 0 -> 0\n  \
+             restricted: 0 -> 1 (+1)\n  \
+             accepted -> restricted: 1\n"
+        );
+    }
+}
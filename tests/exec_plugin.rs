@@ -21,7 +21,9 @@ fn test_blank_slate_exec() -> Result<(), Box<dyn std::error::Error>> {
             runtime: bulwark_config::Runtime::default(),
             state: bulwark_config::State::default(),
             thresholds: bulwark_config::Thresholds::default(),
+            actions: bulwark_config::Actions::default(),
             metrics: bulwark_config::Metrics::default(),
+            capture: bulwark_config::Capture::default(),
             plugins: vec![],
             presets: vec![],
             resources: vec![],
@@ -116,7 +118,9 @@ fn test_evil_bit_benign_exec() -> Result<(), Box<dyn std::error::Error>> {
             runtime: bulwark_config::Runtime::default(),
             state: bulwark_config::State::default(),
             thresholds: bulwark_config::Thresholds::default(),
+            actions: bulwark_config::Actions::default(),
             metrics: bulwark_config::Metrics::default(),
+            capture: bulwark_config::Capture::default(),
             plugins: vec![],
             presets: vec![],
             resources: vec![],
@@ -210,7 +214,9 @@ fn test_evil_bit_evil_exec() -> Result<(), Box<dyn std::error::Error>> {
             runtime: bulwark_config::Runtime::default(),
             state: bulwark_config::State::default(),
             thresholds: bulwark_config::Thresholds::default(),
+            actions: bulwark_config::Actions::default(),
             metrics: bulwark_config::Metrics::default(),
+            capture: bulwark_config::Capture::default(),
             plugins: vec![],
             presets: vec![],
             resources: vec![],
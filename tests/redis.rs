@@ -28,7 +28,9 @@ async fn test_redis_plugin() -> Result<(), Box<dyn std::error::Error>> {
             ..Default::default()
         },
         thresholds: bulwark_config::Thresholds::default(),
+        actions: bulwark_config::Actions::default(),
         metrics: bulwark_config::Metrics::default(),
+        capture: bulwark_config::Capture::default(),
         plugins: vec![bulwark_config::Plugin {
             reference: "redis_plugin".to_string(),
             path: base
@@ -36,6 +38,7 @@ async fn test_redis_plugin() -> Result<(), Box<dyn std::error::Error>> {
                 .to_str()
                 .unwrap()
                 .to_string(),
+            sha256: None,
             weight: 1.0,
             config: serde_json::map::Map::new(),
             permissions: bulwark_config::Permissions {
@@ -43,6 +46,10 @@ async fn test_redis_plugin() -> Result<(), Box<dyn std::error::Error>> {
                 http: vec![],
                 state: vec!["test".to_string(), "bulwark".to_string()],
             },
+            http_client: bulwark_config::HttpClient::default(),
+            timeout: bulwark_config::Timeout::default(),
+            after: vec![],
+            decisive: false,
         }],
         presets: vec![],
         resources: vec![],